@@ -2,28 +2,84 @@ use std::env;
 
 use askama::Template;
 use axum::{
-    http::StatusCode,
-    response::{Html, IntoResponse},
+    body::Body,
+    http::{HeaderMap, HeaderName, Request, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
     routing::get,
     Json, Router,
 };
 use axum_extra::extract::CookieJar;
 use serde::Serialize;
-use tower_http::services::ServeDir;
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    services::ServeDir,
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::{Level, Span};
+use tracing_subscriber::EnvFilter;
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
 
 #[tokio::main]
 async fn main() {
+    init_tracing();
+
+    let request_id_header = HeaderName::from_static(REQUEST_ID_HEADER);
     let app = Router::new()
         .nest_service("/assets", ServeDir::new("assets"))
         .route("/", get(root))
-        .route("/protected", get(protected));
+        .route("/protected", get(protected))
+        // Each layer wraps the ones above it, so requests are given an
+        // ID of our own before they are traced.
+        .layer(PropagateRequestIdLayer::new(request_id_header.clone()))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(make_request_span)
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .layer(SetRequestIdLayer::new(request_id_header, MakeRequestUuid))
+        .layer(middleware::from_fn(drop_request_id));
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
 
-    println!("listening on {}", listener.local_addr().unwrap());
+    tracing::info!(address = %listener.local_addr().unwrap(), "listening");
     axum::serve(listener, app).await.unwrap();
 }
 
+// Log to stdout, as JSON if LOG_FORMAT=json. RUST_LOG picks what is logged.
+fn init_tracing() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    if env::var("LOG_FORMAT").is_ok_and(|format| format == "json") {
+        subscriber.json().init();
+    } else {
+        subscriber.init();
+    }
+}
+
+// Requests reach us straight from browsers, so an ID one came with could say
+// anything. Drop it so that every request gets a fresh one, which is what we
+// pass on to auth-service.
+async fn drop_request_id(mut request: Request<Body>, next: Next) -> Response {
+    request.headers_mut().remove(REQUEST_ID_HEADER);
+    next.run(request).await
+}
+
+fn make_request_span(request: &Request<Body>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        path = %request.uri().path(),
+        request_id,
+    )
+}
+
 #[derive(Template)]
 #[template(path = "index.html")]
 struct IndexTemplate {
@@ -46,7 +102,7 @@ async fn root() -> impl IntoResponse {
     Html(template.render().unwrap())
 }
 
-async fn protected(jar: CookieJar) -> impl IntoResponse {
+async fn protected(headers: HeaderMap, jar: CookieJar) -> impl IntoResponse {
     let jwt_cookie = match jar.get("jwt") {
         Some(cookie) => cookie,
         None => {
@@ -63,9 +119,16 @@ async fn protected(jar: CookieJar) -> impl IntoResponse {
     let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
     let url = format!("http://{}:3000/verify-token", auth_hostname);
 
-    let response = match api_client.post(&url).json(&verify_token_body).send().await {
+    // pass our request ID on, so that auth-service logs the check under it too
+    let mut request = api_client.post(&url).json(&verify_token_body);
+    if let Some(request_id) = headers.get(REQUEST_ID_HEADER) {
+        request = request.header(REQUEST_ID_HEADER, request_id.as_bytes());
+    }
+
+    let response = match request.send().await {
         Ok(response) => response,
-        Err(_) => {
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to reach auth-service");
             return StatusCode::INTERNAL_SERVER_ERROR.into_response();
         }
     };
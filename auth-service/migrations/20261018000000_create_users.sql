@@ -0,0 +1,8 @@
+-- requires_2fa is an INTEGER (0 or 1) rather than a BOOLEAN because sqlx's
+-- Any driver cannot decode SQLite booleans; this keeps one schema for both
+-- PostgreSQL and SQLite.
+CREATE TABLE IF NOT EXISTS users (
+    email TEXT PRIMARY KEY NOT NULL,
+    password_hash TEXT NOT NULL,
+    requires_2fa INTEGER NOT NULL DEFAULT 0
+);
@@ -0,0 +1,4 @@
+-- Accounts created before email verification existed are taken to be
+-- verified, so that turning verification on doesn't lock them out; new
+-- accounts are inserted with verified = 0.
+ALTER TABLE users ADD COLUMN verified INTEGER NOT NULL DEFAULT 1;
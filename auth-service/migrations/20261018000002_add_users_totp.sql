@@ -0,0 +1,5 @@
+-- two_fa_method is 'email' or 'totp'. A TOTP secret is pending from
+-- enrollment until the user confirms it with a first code.
+ALTER TABLE users ADD COLUMN two_fa_method TEXT NOT NULL DEFAULT 'email';
+ALTER TABLE users ADD COLUMN totp_secret TEXT;
+ALTER TABLE users ADD COLUMN pending_totp_secret TEXT;
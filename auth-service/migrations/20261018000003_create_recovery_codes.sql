@@ -0,0 +1,6 @@
+-- SHA-256 hashes of each user's unused 2FA recovery codes
+CREATE TABLE IF NOT EXISTS recovery_codes (
+    email TEXT NOT NULL,
+    code_hash TEXT NOT NULL,
+    PRIMARY KEY (email, code_hash)
+);
@@ -0,0 +1,5 @@
+-- The ID each user keeps for good, whatever their email address. Users
+-- from before it are given theirs when the store connects, as PostgreSQL
+-- and SQLite have no UUID function in common to fill them in here.
+ALTER TABLE users ADD COLUMN id TEXT;
+CREATE UNIQUE INDEX IF NOT EXISTS users_id ON users (id);
@@ -0,0 +1,3 @@
+-- The time step of the last TOTP code accepted for each user, so that a
+-- code can't be used again while it is still current.
+ALTER TABLE users ADD COLUMN totp_last_step BIGINT;
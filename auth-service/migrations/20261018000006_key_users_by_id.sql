@@ -0,0 +1,34 @@
+-- Every user has an ID by now (see `SqlUserStore::connect`), so it becomes
+-- the key, and recovery codes are kept by it rather than by email address.
+-- PostgreSQL and SQLite have no way in common to change a table's primary
+-- key in place, so both tables are rebuilt.
+CREATE TABLE users_new (
+    id TEXT PRIMARY KEY NOT NULL,
+    email TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    requires_2fa INTEGER NOT NULL DEFAULT 0,
+    verified INTEGER NOT NULL DEFAULT 1,
+    two_fa_method TEXT NOT NULL DEFAULT 'email',
+    totp_secret TEXT,
+    pending_totp_secret TEXT,
+    totp_last_step BIGINT
+);
+INSERT INTO users_new (id, email, password_hash, requires_2fa, verified, two_fa_method,
+                       totp_secret, pending_totp_secret, totp_last_step)
+SELECT id, email, password_hash, requires_2fa, verified, two_fa_method,
+       totp_secret, pending_totp_secret, totp_last_step
+FROM users;
+
+CREATE TABLE recovery_codes_new (
+    user_id TEXT NOT NULL,
+    code_hash TEXT NOT NULL,
+    PRIMARY KEY (user_id, code_hash)
+);
+INSERT INTO recovery_codes_new (user_id, code_hash)
+SELECT users.id, recovery_codes.code_hash
+FROM recovery_codes JOIN users ON users.email = recovery_codes.email;
+
+DROP TABLE recovery_codes;
+DROP TABLE users;
+ALTER TABLE users_new RENAME TO users;
+ALTER TABLE recovery_codes_new RENAME TO recovery_codes;
@@ -1,8 +1,20 @@
 use tokio::sync::RwLock;
 
 use crate::{
-    domain::{BannedTokenStore, TwoFACodeStore, UserStore},
-    services::{HashMapTwoFACodeStore, HashMapUserStore, HashSetBannedTokenStore},
+    domain::{
+        AccountEventHook, BannedTokenStore, EmailClient, FailedLoginStore, RateLimiter,
+        RefreshTokenStore, TwoFACodeStore, UserStore,
+    },
+    services::{
+        HashMapFailedLoginStore, HashMapRateLimiter, HashMapRefreshTokenStore,
+        HashMapTwoFACodeStore, HashMapUserStore, HashSetBannedTokenStore, LogAccountEventHook,
+        StdoutEmailClient,
+    },
+    settings::Settings,
+    utils::{
+        jwt_key::{JwtKeyring, SharedKeyring},
+        metrics::Metrics,
+    },
 };
 use std::sync::Arc;
 
@@ -11,37 +23,56 @@ use std::sync::Arc;
 pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
 pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
 pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
+pub type RefreshTokenStoreType = Arc<RwLock<dyn RefreshTokenStore + Send + Sync>>;
+pub type FailedLoginStoreType = Arc<RwLock<dyn FailedLoginStore + Send + Sync>>;
+// Sending email or events and counting requests only need shared access,
+// so no lock is required.
+pub type EmailClientType = Arc<dyn EmailClient + Send + Sync>;
+pub type RateLimiterType = Arc<dyn RateLimiter + Send + Sync>;
+pub type AccountEventHookType = Arc<dyn AccountEventHook + Send + Sync>;
 
 /// Axum application state
-/// Only includes a user store for now, will likely include more state in the future.
 #[derive(Clone)]
 pub struct AppState {
     pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub failed_login_store: FailedLoginStoreType,
+    pub email_client: EmailClientType,
+    pub rate_limiter: RateLimiterType,
+    pub account_event_hook: AccountEventHookType,
+    pub jwt_keyring: SharedKeyring,
+    pub settings: Arc<Settings>,
+    pub metrics: Arc<Metrics>,
 }
 
 impl AppState {
-    pub fn new(
-        user_store: UserStoreType,
-        banned_token_store: BannedTokenStoreType,
-        two_fa_code_store: TwoFACodeStoreType,
-    ) -> Self {
-        Self {
-            user_store,
-            banned_token_store,
-            two_fa_code_store,
-        }
-    }
-}
-
-/// Create a new AppState defaulting to a HashMapUserStore user store.
-impl Default for AppState {
-    fn default() -> Self {
+    /// Create a new AppState for `settings`, defaulting to in-memory stores,
+    /// printing email to stdout and logging account events.
+    pub fn new(settings: Settings, jwt_keyring: JwtKeyring) -> Self {
         Self {
             user_store: Arc::new(RwLock::new(HashMapUserStore::default())),
-            banned_token_store: Arc::new(RwLock::new(HashSetBannedTokenStore::default())),
-            two_fa_code_store: Arc::new(RwLock::new(HashMapTwoFACodeStore::default())),
+            banned_token_store: Arc::new(RwLock::new(HashSetBannedTokenStore::new(
+                settings.token_ttl,
+                settings.token_policy.leeway,
+            ))),
+            two_fa_code_store: Arc::new(RwLock::new(HashMapTwoFACodeStore::new(
+                settings.two_fa_code_ttl,
+                settings.max_two_fa_attempts,
+            ))),
+            refresh_token_store: Arc::new(RwLock::new(HashMapRefreshTokenStore::new(
+                settings.refresh_token_ttl,
+            ))),
+            failed_login_store: Arc::new(RwLock::new(HashMapFailedLoginStore::new(
+                settings.lockout,
+            ))),
+            email_client: Arc::new(StdoutEmailClient),
+            rate_limiter: Arc::new(HashMapRateLimiter::default()),
+            account_event_hook: Arc::new(LogAccountEventHook),
+            jwt_keyring: SharedKeyring::new(jwt_keyring),
+            settings: Arc::new(settings),
+            metrics: Arc::new(Metrics::default()),
         }
     }
 }
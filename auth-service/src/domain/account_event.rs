@@ -0,0 +1,63 @@
+use serde::Serialize;
+
+use super::UserId;
+
+/// Something that happened to an account which other systems may want
+/// to act on. Users are named by their ID, which other services know them
+/// by too, rather than by email address, which needn't end up in their
+/// logs. Serialized with an `event` field naming the variant, e.g.
+/// `{"event":"locked","user_id":"...","locked_for_seconds":60}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AccountEvent {
+    /// Too many failed logins in a row; logins are refused for a while.
+    Locked {
+        user_id: UserId,
+        locked_for_seconds: u64,
+    },
+    /// The password was reset through an emailed token, ending every session.
+    PasswordReset { user_id: UserId },
+    /// A recovery code stood in for the user's second factor at login.
+    RecoveryCodeUsed {
+        user_id: UserId,
+        remaining_codes: usize,
+    },
+    /// The user replaced their recovery codes with a new set.
+    RecoveryCodesRegenerated { user_id: UserId },
+    /// The user deleted their account; whatever else is kept about them
+    /// should go too.
+    Deleted { user_id: UserId },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountEventHookError {
+    DeliveryFailed(String),
+}
+
+/// Passes account events on, e.g. to alert the user or their admins.
+#[async_trait::async_trait]
+pub trait AccountEventHook: std::fmt::Debug + Send + Sync {
+    async fn notify(&self, event: &AccountEvent) -> Result<(), AccountEventHookError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_names_the_event() {
+        let user_id: UserId = "1b4e28ba-2fa1-4d3b-a3f5-ef19b5a7633b".parse().unwrap();
+        let event = AccountEvent::Locked {
+            user_id,
+            locked_for_seconds: 60,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "event": "locked",
+                "user_id": "1b4e28ba-2fa1-4d3b-a3f5-ef19b5a7633b",
+                "locked_for_seconds": 60,
+            })
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+/// A way the user proved who they are, as listed in a token's `amr`
+/// claim. Names follow RFC 8176 where it has one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AuthMethod {
+    /// Their password
+    #[serde(rename = "pwd")]
+    Password,
+    /// A code from an authenticator app
+    #[serde(rename = "otp")]
+    Totp,
+    /// A code emailed to them
+    #[serde(rename = "email")]
+    EmailCode,
+    /// A recovery code in place of their second factor
+    #[serde(rename = "recovery")]
+    RecoveryCode,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serializes_to_amr_values() {
+        let amr = vec![
+            AuthMethod::Password,
+            AuthMethod::Totp,
+            AuthMethod::EmailCode,
+            AuthMethod::RecoveryCode,
+        ];
+        let json = serde_json::to_string(&amr).unwrap();
+        assert_eq!(json, r#"["pwd","otp","email","recovery"]"#);
+        assert_eq!(serde_json::from_str::<Vec<AuthMethod>>(&json).unwrap(), amr);
+    }
+}
@@ -1,6 +1,12 @@
 use crate::domain::{LoginAttemptId, TwoFACode};
+use crate::utils::auth::PasswordHashError;
+use std::time::Duration;
+use uuid::Uuid;
 
-use super::{Email, Password, Token, User};
+use super::{
+    AuthMethod, Email, HashedPassword, Password, RateLimit, RateLimitDecision, RecoveryCode,
+    RefreshToken, Token, TotpSecret, TwoFAMethod, User, UserId,
+};
 
 #[derive(Debug, PartialEq, Default)]
 pub enum UserStoreError {
@@ -8,6 +14,8 @@ pub enum UserStoreError {
     UserNotFound,
     InvalidCredentials,
     IncorrectCredentials,
+    RecoveryCodeNotFound,
+    TotpStepUsed,
     #[default]
     UnexpectedError,
 }
@@ -16,11 +24,84 @@ pub enum UserStoreError {
 pub trait UserStore: Send + Sync {
     async fn add_user(&self, user: User) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
+    /// Look up a user by the ID they were given at signup, e.g. the `sub`
+    /// of an access token.
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError>;
+    /// Look up a user and check the candidate password against their stored
+    /// hash. An unknown email is `IncorrectCredentials` too, after as long a
+    /// check, so that neither gives away who is registered. Backends only
+    /// need to implement `get_user` to get this for free.
     async fn validate_user(
         &self,
         email: &Email,
         password: &Password,
-    ) -> Result<User, UserStoreError>;
+    ) -> Result<User, UserStoreError> {
+        let user = match self.get_user(email).await {
+            Ok(user) => user,
+            Err(UserStoreError::UserNotFound) => {
+                let _ = HashedPassword::dummy().verify_raw_password(password).await;
+                return Err(UserStoreError::IncorrectCredentials);
+            }
+            Err(e) => return Err(e),
+        };
+        match user.password.verify_raw_password(password).await {
+            Ok(()) => Ok(user),
+            Err(PasswordHashError::IncorrectPassword) => Err(UserStoreError::IncorrectCredentials),
+            // a corrupt stored hash or a failed hashing task is our fault,
+            // not a wrong password
+            Err(e) => {
+                tracing::error!(error = ?e, "Failed to check password");
+                Err(UserStoreError::UnexpectedError)
+            }
+        }
+    }
+    /// Replace the password of an existing user.
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: HashedPassword,
+    ) -> Result<(), UserStoreError>;
+    /// Record that the user has verified their email address.
+    async fn mark_verified(&self, email: &Email) -> Result<(), UserStoreError>;
+    /// Hold `secret` until the user confirms enrolling their authenticator
+    /// app, replacing any secret already pending.
+    async fn set_pending_totp_secret(
+        &self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError>;
+    /// Make `secret` the user's authenticator secret and switch them to
+    /// TOTP 2FA, clearing the pending secret. `step` is that of the code
+    /// that confirmed it, which is then used up.
+    async fn enable_totp(
+        &self,
+        email: &Email,
+        secret: TotpSecret,
+        step: u64,
+    ) -> Result<(), UserStoreError>;
+    /// Use up the TOTP time `step` of a code the user gave, or return
+    /// `TotpStepUsed` if a code from it or a later step was used already.
+    async fn use_totp_step(&self, email: &Email, step: u64) -> Result<(), UserStoreError>;
+    /// Turn 2FA on for the user, asking for `method` at login.
+    async fn set_two_fa_method(
+        &self,
+        email: &Email,
+        method: TwoFAMethod,
+    ) -> Result<(), UserStoreError>;
+    /// Replace the user's recovery codes, keeping only their hashes.
+    async fn set_recovery_codes(
+        &self,
+        id: &UserId,
+        codes: &[RecoveryCode],
+    ) -> Result<(), UserStoreError>;
+    /// Use up one of the user's recovery codes, returning how many they have
+    /// left, or `RecoveryCodeNotFound` if `code` isn't one of them.
+    async fn use_recovery_code(
+        &self,
+        id: &UserId,
+        code: &RecoveryCode,
+    ) -> Result<usize, UserStoreError>;
+    /// Delete the user along with their recovery codes.
     async fn delete_user(&self, email: &Email) -> Result<User, UserStoreError>;
 }
 
@@ -32,11 +113,74 @@ pub enum BannedTokenResult {
     TokenUnbanned,
 }
 
+#[derive(Debug, PartialEq, Default)]
+pub enum BannedTokenStoreError {
+    /// The token has no `jti` to ban it by, so can't be one of ours.
+    InvalidToken,
+    #[default]
+    UnexpectedError,
+}
+
+/// Tokens are banned by their `jti` claim, which every token we issue,
+/// access or emailed, has a random one of.
 #[async_trait::async_trait]
 pub trait BannedTokenStore: std::fmt::Debug + Send + Sync {
-    async fn ban(&self, token: Token) -> BannedTokenResult;
-    async fn is_banned(&self, token: &Token) -> bool;
-    async fn unban(&self, token: &Token) -> BannedTokenResult;
+    async fn ban(&self, token: Token) -> Result<BannedTokenResult, BannedTokenStoreError>;
+    /// Whether `token` has been banned, itself or along with every other
+    /// token of its user.
+    async fn is_banned(&self, token: &Token) -> Result<bool, BannedTokenStoreError>;
+    /// Ban every access token issued to the user before now, e.g. once their
+    /// password has changed. Tokens issued from now on are unaffected.
+    async fn ban_user(&self, id: &UserId) -> Result<(), BannedTokenStoreError>;
+    async fn unban(&self, token: &Token) -> Result<BannedTokenResult, BannedTokenStoreError>;
+    /// Number of tokens currently held by the store.
+    async fn size(&self) -> Result<usize, BannedTokenStoreError>;
+    /// Drop tokens whose `exp` has passed, since they are invalid anyway,
+    /// returning how many were removed. Stores that expire entries on
+    /// their own don't need to override this.
+    async fn prune_expired(&self) -> Result<usize, BannedTokenStoreError> {
+        Ok(0)
+    }
+}
+
+/// What a refresh token was issued for. Every token rotated from the
+/// same login shares a `family_id`, so that they can be revoked together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshTokenRecord {
+    pub user_id: UserId,
+    pub family_id: Uuid,
+    /// How the user logged in, for the tokens it is exchanged for to say.
+    pub amr: Vec<AuthMethod>,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub enum RefreshTokenStoreError {
+    #[default]
+    TokenNotFound,
+    /// The token was already exchanged once; carries its family so that
+    /// the caller can revoke it.
+    TokenReused(Uuid),
+    UnexpectedError,
+}
+
+#[async_trait::async_trait]
+pub trait RefreshTokenStore: std::fmt::Debug + Send + Sync {
+    async fn add(
+        &mut self,
+        token: RefreshToken,
+        record: RefreshTokenRecord,
+    ) -> Result<(), RefreshTokenStoreError>;
+    /// Mark `token` as used and return what it was issued for. Expired
+    /// tokens and tokens of revoked families are not found; tokens that
+    /// were already consumed return `TokenReused`.
+    async fn consume(
+        &mut self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError>;
+    /// Invalidate every token in the family, used or not.
+    async fn revoke_family(&mut self, family_id: &Uuid) -> Result<(), RefreshTokenStoreError>;
+    /// Invalidate every token issued to the user, ending all of their sessions.
+    async fn revoke_user(&mut self, user_id: &UserId) -> Result<(), RefreshTokenStoreError>;
 }
 
 #[async_trait::async_trait]
@@ -48,11 +192,59 @@ pub trait TwoFACodeStore: std::fmt::Debug + Send + Sync {
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError>;
     async fn remove(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+    /// Fetch the pending code for `email`. Expired codes are not returned.
     async fn get(&self, email: &Email) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+    /// Count a wrong guess against the pending code for `email`. Once the
+    /// maximum number of guesses is reached the code is removed and
+    /// `TooManyAttempts` is returned.
+    async fn record_failed_attempt(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+    /// Number of codes still waiting to be verified.
+    async fn size(&self) -> Result<usize, TwoFACodeStoreError>;
 }
 
 #[derive(Debug, PartialEq, Default)]
 pub enum TwoFACodeStoreError {
     #[default]
     EmailNotFound,
+    CodeExpired,
+    TooManyAttempts,
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub enum RateLimiterError {
+    #[default]
+    UnexpectedError,
+}
+
+#[async_trait::async_trait]
+pub trait RateLimiter: std::fmt::Debug + Send + Sync {
+    /// Count a request against `key` and decide whether it may go ahead.
+    /// Limited requests are counted too, so a client that keeps retrying
+    /// stays limited until it backs off.
+    async fn hit(&self, key: &str, limit: RateLimit)
+        -> Result<RateLimitDecision, RateLimiterError>;
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub enum FailedLoginStoreError {
+    #[default]
+    UnexpectedError,
+}
+
+/// Tracks failed logins per account, locking accounts that fail too
+/// often as set out by a [`LockoutPolicy`](super::LockoutPolicy).
+#[async_trait::async_trait]
+pub trait FailedLoginStore: std::fmt::Debug + Send + Sync {
+    /// How much longer `email` is locked out for, if it is.
+    async fn locked_for(&self, email: &Email) -> Result<Option<Duration>, FailedLoginStoreError>;
+    /// Count a failed login for `email`, returning how long it is now
+    /// locked out for if this failure locked it.
+    async fn record_failure(
+        &mut self,
+        email: &Email,
+    ) -> Result<Option<Duration>, FailedLoginStoreError>;
+    /// Forget the failed logins of `email` and lift any lockout, after a
+    /// successful login or when an admin unlocks it.
+    async fn reset(&mut self, email: &Email) -> Result<(), FailedLoginStoreError>;
 }
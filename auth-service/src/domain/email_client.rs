@@ -0,0 +1,18 @@
+use super::Email;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmailClientError {
+    InvalidMessage(String),
+    DeliveryFailed(String),
+}
+
+/// Delivers email to users, e.g. 2FA codes sent at login.
+#[async_trait::async_trait]
+pub trait EmailClient: std::fmt::Debug + Send + Sync {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str,
+    ) -> Result<(), EmailClientError>;
+}
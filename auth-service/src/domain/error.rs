@@ -1,7 +1,10 @@
 use super::UserStoreError;
 use crate::{
-    domain::TwoFACodeStoreError,
-    utils::auth::{GenerateTokenError, LoginAttemptIdError},
+    domain::{
+        BannedTokenStoreError, EmailClientError, FailedLoginStoreError, RefreshTokenStoreError,
+        TwoFACodeStoreError,
+    },
+    utils::auth::{GenerateTokenError, LoginAttemptIdError, PasswordHashError, TwoFACodeError},
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -14,6 +17,10 @@ pub enum AuthApiError {
     MissingToken,
     InvalidToken,
     InvalidTwoFaCode,
+    TooManyRequests,
+    AccountLocked,
+    EmailNotVerified,
+    TotpNotEnrolled,
 }
 
 impl From<UserStoreError> for AuthApiError {
@@ -44,7 +51,13 @@ impl From<jsonwebtoken::errors::Error> for AuthApiError {
 
 impl From<LoginAttemptIdError> for AuthApiError {
     fn from(_error: LoginAttemptIdError) -> Self {
-        AuthApiError::InvalidTwoFaCode
+        AuthApiError::InvalidCredentials
+    }
+}
+
+impl From<TwoFACodeError> for AuthApiError {
+    fn from(_error: TwoFACodeError) -> Self {
+        AuthApiError::InvalidCredentials
     }
 }
 
@@ -53,3 +66,42 @@ impl From<TwoFACodeStoreError> for AuthApiError {
         AuthApiError::UnexpectedError
     }
 }
+
+impl From<BannedTokenStoreError> for AuthApiError {
+    fn from(error: BannedTokenStoreError) -> Self {
+        match error {
+            BannedTokenStoreError::InvalidToken => AuthApiError::InvalidToken,
+            BannedTokenStoreError::UnexpectedError => AuthApiError::UnexpectedError,
+        }
+    }
+}
+
+impl From<FailedLoginStoreError> for AuthApiError {
+    fn from(_error: FailedLoginStoreError) -> Self {
+        AuthApiError::UnexpectedError
+    }
+}
+
+impl From<RefreshTokenStoreError> for AuthApiError {
+    fn from(error: RefreshTokenStoreError) -> Self {
+        match error {
+            RefreshTokenStoreError::UnexpectedError => AuthApiError::UnexpectedError,
+            _ => AuthApiError::InvalidToken,
+        }
+    }
+}
+
+impl From<EmailClientError> for AuthApiError {
+    fn from(_error: EmailClientError) -> Self {
+        AuthApiError::UnexpectedError
+    }
+}
+
+impl From<PasswordHashError> for AuthApiError {
+    fn from(error: PasswordHashError) -> Self {
+        match error {
+            PasswordHashError::IncorrectPassword => AuthApiError::IncorrectCredentials,
+            _ => AuthApiError::UnexpectedError,
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, SaltString},
+    Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
+};
+use serde::{Deserialize, Serialize};
+
+use super::Password;
+use crate::utils::auth::PasswordHashError;
+
+/// An Argon2id password hash, stored in PHC string format
+/// (e.g. `$argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>`).
+/// Because the algorithm and its parameters are part of the
+/// string, they can be changed later without invalidating
+/// existing hashes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct HashedPassword(String);
+
+// A hash with the same parameters as real ones, of a password nobody is
+// given, for checking passwords against when there is no user to check
+// them against.
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$i3ErWMWzLeUXUoV9P/waKw$Iv8fYvfw5WDZXX4N5lst6caavhZy2SwC6K9w1sjFsF0";
+
+impl HashedPassword {
+    /// A hash no password is known to match, taking as long to check as
+    /// any other.
+    pub fn dummy() -> Self {
+        HashedPassword(DUMMY_PASSWORD_HASH.to_owned())
+    }
+
+    /// Hash a validated plaintext password with a fresh random salt.
+    /// Hashing is CPU- and memory-intensive, so it runs on the blocking pool.
+    pub async fn parse(password: Password) -> Result<Self, PasswordHashError> {
+        tokio::task::spawn_blocking(move || {
+            let salt = SaltString::generate(&mut OsRng);
+            argon2()
+                .hash_password(password.as_ref().as_bytes(), &salt)
+                .map(|hash| HashedPassword(hash.to_string()))
+                .map_err(|_| PasswordHashError::HashingFailed)
+        })
+        .await
+        .map_err(|_| PasswordHashError::HashingFailed)?
+    }
+
+    /// Wrap an existing PHC string, e.g. one loaded from a database.
+    pub fn parse_password_hash(hash: String) -> Result<Self, PasswordHashError> {
+        PasswordHash::new(&hash).map_err(|_| PasswordHashError::InvalidHash)?;
+        Ok(HashedPassword(hash))
+    }
+
+    /// Check a plaintext password against this hash, using the
+    /// parameters recorded in the PHC string.
+    pub async fn verify_raw_password(&self, candidate: &Password) -> Result<(), PasswordHashError> {
+        let hash = self.0.clone();
+        let candidate = candidate.clone();
+        tokio::task::spawn_blocking(move || {
+            let expected = PasswordHash::new(&hash).map_err(|_| PasswordHashError::InvalidHash)?;
+            argon2()
+                .verify_password(candidate.as_ref().as_bytes(), &expected)
+                .map_err(|e| match e {
+                    argon2::password_hash::Error::Password => PasswordHashError::IncorrectPassword,
+                    _ => PasswordHashError::InvalidHash,
+                })
+        })
+        .await
+        .map_err(|_| PasswordHashError::HashingFailed)?
+    }
+}
+
+impl AsRef<str> for HashedPassword {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Argon2id with the OWASP-recommended minimum parameters.
+fn argon2() -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, Params::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_hash_is_phc_string() {
+        let password = Password::from_str("password123").unwrap();
+        let hashed = HashedPassword::parse(password).await.unwrap();
+        assert!(hashed.as_ref().starts_with("$argon2id$v=19$"));
+        assert!(HashedPassword::parse_password_hash(hashed.as_ref().to_owned()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_hash_does_not_contain_password() {
+        let password = Password::from_str("password123").unwrap();
+        let hashed = HashedPassword::parse(password).await.unwrap();
+        assert!(!hashed.as_ref().contains("password123"));
+    }
+
+    #[tokio::test]
+    async fn test_same_password_hashes_differently() {
+        let first = HashedPassword::parse("password123".parse().unwrap())
+            .await
+            .unwrap();
+        let second = HashedPassword::parse("password123".parse().unwrap())
+            .await
+            .unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[tokio::test]
+    async fn test_verify_correct_password() {
+        let hashed = HashedPassword::parse("password123".parse().unwrap())
+            .await
+            .unwrap();
+        let candidate = Password::from_str("password123").unwrap();
+        assert!(hashed.verify_raw_password(&candidate).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_verify_incorrect_password() {
+        let hashed = HashedPassword::parse("password123".parse().unwrap())
+            .await
+            .unwrap();
+        let candidate = Password::from_str("password234").unwrap();
+        assert_eq!(
+            hashed.verify_raw_password(&candidate).await,
+            Err(PasswordHashError::IncorrectPassword)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dummy_hash_rejects_passwords() {
+        assert!(HashedPassword::parse_password_hash(DUMMY_PASSWORD_HASH.to_owned()).is_ok());
+        let candidate = Password::from_str("password123").unwrap();
+        assert_eq!(
+            HashedPassword::dummy()
+                .verify_raw_password(&candidate)
+                .await,
+            Err(PasswordHashError::IncorrectPassword)
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_hash() {
+        assert_eq!(
+            HashedPassword::parse_password_hash("password123".to_owned()),
+            Err(PasswordHashError::InvalidHash)
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+/// When repeated failed logins lock an account, and for how long.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LockoutPolicy {
+    /// Failed logins in a row that lock the account.
+    pub max_failed_attempts: u32,
+    /// How long the first lockout lasts; each one after it lasts twice as
+    /// long as the last, up to `max_lockout`.
+    pub base_lockout: Duration,
+    pub max_lockout: Duration,
+    /// How long after the last failed login the account's record is
+    /// wiped clean, so that the occasional typo never adds up to a lockout.
+    pub forget_after: Duration,
+}
+
+impl LockoutPolicy {
+    /// How long the `lockouts`th lockout in a row lasts.
+    pub fn lockout(&self, lockouts: u32) -> Duration {
+        let doublings = lockouts.saturating_sub(1).min(31);
+        self.base_lockout
+            .checked_mul(1 << doublings)
+            .unwrap_or(self.max_lockout)
+            .min(self.max_lockout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockouts_double_up_to_max() {
+        let policy = LockoutPolicy {
+            max_failed_attempts: 5,
+            base_lockout: Duration::from_secs(60),
+            max_lockout: Duration::from_secs(600),
+            forget_after: Duration::from_secs(3600),
+        };
+        assert_eq!(policy.lockout(1), Duration::from_secs(60));
+        assert_eq!(policy.lockout(2), Duration::from_secs(120));
+        assert_eq!(policy.lockout(4), Duration::from_secs(480));
+        assert_eq!(policy.lockout(5), Duration::from_secs(600));
+        assert_eq!(policy.lockout(u32::MAX), Duration::from_secs(600));
+    }
+}
@@ -23,3 +23,9 @@ impl Default for LoginAttemptId {
         LoginAttemptId(Uuid::new_v4().to_string())
     }
 }
+
+impl AsRef<str> for LoginAttemptId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
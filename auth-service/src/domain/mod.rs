@@ -1,23 +1,53 @@
+mod account_event;
+pub use account_event::*;
+
+mod auth_method;
+pub use auth_method::AuthMethod;
+
 mod data_stores;
 pub use data_stores::*;
 
 mod email;
 pub use email::*;
 
+mod email_client;
+pub use email_client::*;
+
 mod password;
 pub use password::*;
 
+mod hashed_password;
+pub use hashed_password::HashedPassword;
+
 mod error;
 pub use error::AuthApiError;
 
 mod user;
 pub use user::User;
 
+mod user_id;
+pub use user_id::UserId;
+
 mod token;
 pub use token::Token;
 
+mod refresh_token;
+pub use refresh_token::RefreshToken;
+
+mod lockout;
+pub use lockout::LockoutPolicy;
+
 mod login_attempt_id;
 pub use login_attempt_id::LoginAttemptId;
 
 mod two_fa_code;
 pub use two_fa_code::TwoFACode;
+
+mod totp;
+pub use totp::{TotpSecret, TwoFAMethod};
+
+mod recovery_code;
+pub use recovery_code::RecoveryCode;
+
+mod rate_limit;
+pub use rate_limit::{RateLimit, RateLimitDecision};
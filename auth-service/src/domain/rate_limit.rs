@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+/// At most `max_requests` per `window`, for one client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub max_requests: u32,
+    pub window: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateLimitDecision {
+    Allowed,
+    Limited { retry_after: Duration },
+}
+
+impl RateLimit {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+        }
+    }
+
+    /// Decide on a request given the hits counted in the previous window
+    /// and in the current one (including this request), `elapsed` into the
+    /// current window. This is a sliding window approximated from the two
+    /// fixed ones: the previous window's hits are weighted by how much of
+    /// it the sliding window still overlaps.
+    pub fn decide(&self, previous: u64, current: u64, elapsed: Duration) -> RateLimitDecision {
+        let window = self.window.as_secs_f64();
+        let elapsed = elapsed.as_secs_f64().min(window);
+        let max = f64::from(self.max_requests);
+        let overlap = 1.0 - elapsed / window;
+        if previous as f64 * overlap + current as f64 <= max {
+            return RateLimitDecision::Allowed;
+        }
+        // Work out when one more request would be let through, assuming
+        // none are made in the meantime.
+        let wait = if previous > 0 && (current as f64) < max {
+            // once enough of the previous window has slid out
+            window * (1.0 - (max - current as f64 - 1.0) / previous as f64) - elapsed
+        } else {
+            // once this window has become the previous one and slid out far enough
+            let into_next = window * (1.0 - (max - 1.0) / current as f64);
+            window - elapsed + into_next.max(0.0)
+        };
+        // to the millisecond, so float error doesn't leak into `Retry-After`
+        RateLimitDecision::Limited {
+            retry_after: Duration::from_millis((wait.max(0.0) * 1000.0).round() as u64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit() -> RateLimit {
+        RateLimit::new(10, Duration::from_secs(60))
+    }
+
+    fn retry_after(decision: RateLimitDecision) -> Duration {
+        match decision {
+            RateLimitDecision::Limited { retry_after } => retry_after,
+            RateLimitDecision::Allowed => panic!("Request should be limited"),
+        }
+    }
+
+    #[test]
+    fn test_allows_up_to_max_requests() {
+        let limit = limit();
+        assert_eq!(
+            limit.decide(0, 10, Duration::from_secs(1)),
+            RateLimitDecision::Allowed
+        );
+        // the 11 hits still count for 9/11 of the next window, 10.909s into it
+        assert_eq!(
+            retry_after(limit.decide(0, 11, Duration::from_secs(1))),
+            Duration::from_millis(59_000 + 10_909)
+        );
+    }
+
+    #[test]
+    fn test_previous_window_counts_less_as_it_slides_out() {
+        let limit = limit();
+        // a quarter of the way in, 3/4 of the previous 8 still count
+        assert!(matches!(
+            limit.decide(8, 5, Duration::from_secs(15)),
+            RateLimitDecision::Limited { .. }
+        ));
+        // half way in, only half do
+        assert_eq!(
+            limit.decide(8, 5, Duration::from_secs(30)),
+            RateLimitDecision::Allowed
+        );
+    }
+
+    #[test]
+    fn test_retry_after_waits_for_previous_window_to_slide_out() {
+        // 8 * 3/4 + 5 = 11; another request fits once 8 * x + 5 + 1 <= 10,
+        // i.e. x = 1/2, at 30s
+        let decision = limit().decide(8, 5, Duration::from_secs(15));
+        assert_eq!(retry_after(decision), Duration::from_secs(15));
+    }
+
+    #[test]
+    fn test_retry_after_can_reach_into_the_next_window() {
+        // 20 hits this window still count for 9/20 of the next one
+        let decision = limit().decide(0, 20, Duration::from_secs(50));
+        assert_eq!(retry_after(decision), Duration::from_secs(10 + 33));
+    }
+}
@@ -0,0 +1,115 @@
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::{fmt, str::FromStr};
+
+use crate::utils::auth::TwoFACodeError;
+
+// No 0/o, 1/i/l, to keep codes easy to copy off paper
+const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+const LENGTH: usize = 16;
+const GROUP: usize = 4;
+
+/// A single-use code that stands in for the second factor, for a user who
+/// has lost access to theirs. Shown as `xxxx-xxxx-xxxx-xxxx`; dashes,
+/// spaces and case are ignored when parsing.
+#[derive(Clone, PartialEq)]
+pub struct RecoveryCode(String);
+
+impl RecoveryCode {
+    /// What the code is stored as. Codes are random enough that a fast hash
+    /// is as good as a slow one, and it lets stores look them up directly.
+    pub fn hash(&self) -> String {
+        Sha256::digest(self.0.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+impl Default for RecoveryCode {
+    fn default() -> Self {
+        let mut rng = rand::rng();
+        let code = (0..LENGTH)
+            .map(|_| ALPHABET[rng.random_range(0..ALPHABET.len())] as char)
+            .collect();
+        Self(code)
+    }
+}
+
+impl FromStr for RecoveryCode {
+    type Err = TwoFACodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code: String = s
+            .chars()
+            .filter(|c| *c != '-' && !c.is_whitespace())
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        if code.len() == LENGTH && code.bytes().all(|c| ALPHABET.contains(&c)) {
+            Ok(Self(code))
+        } else {
+            Err(TwoFACodeError::Invalid)
+        }
+    }
+}
+
+impl fmt::Display for RecoveryCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, group) in self.0.as_bytes().chunks(GROUP).enumerate() {
+            if i > 0 {
+                f.write_str("-")?;
+            }
+            // the alphabet is ASCII
+            f.write_str(std::str::from_utf8(group).map_err(|_| fmt::Error)?)?;
+        }
+        Ok(())
+    }
+}
+
+// Keep codes out of logs
+impl fmt::Debug for RecoveryCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RecoveryCode(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn test_display_parses_back() {
+        let code = RecoveryCode::default();
+        let shown = code.to_string();
+        assert_eq!(shown.len(), 19);
+        assert_eq!(shown.parse::<RecoveryCode>(), Ok(code));
+    }
+
+    #[test]
+    fn test_parse_ignores_dashes_spaces_and_case() {
+        let code: RecoveryCode = "abcd-efgh-jkmn-pqrs".parse().unwrap();
+        assert_eq!(
+            "ABCD EFGH JKMN PQRS".parse::<RecoveryCode>(),
+            Ok(code.clone())
+        );
+        assert_eq!(
+            "abcdefghjkmnpqrs".parse::<RecoveryCode>().unwrap().hash(),
+            code.hash()
+        );
+    }
+
+    #[test]
+    fn test_2fa_codes_are_not_recovery_codes() {
+        assert!("123456".parse::<RecoveryCode>().is_err());
+        assert!("abcd-efgh-jkmn-pqr0".parse::<RecoveryCode>().is_err());
+    }
+
+    #[quickcheck]
+    fn prop_default_must_be_valid() -> bool {
+        RecoveryCode::default()
+            .to_string()
+            .parse::<RecoveryCode>()
+            .is_ok()
+    }
+}
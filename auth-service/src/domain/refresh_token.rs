@@ -0,0 +1,43 @@
+/// An opaque, long-lived token that can be exchanged exactly once at
+/// `/refresh` for a new access token and a new refresh token.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RefreshToken(String);
+
+impl From<&str> for RefreshToken {
+    fn from(value: &str) -> Self {
+        RefreshToken(value.to_string())
+    }
+}
+
+impl From<String> for RefreshToken {
+    fn from(value: String) -> Self {
+        RefreshToken(value)
+    }
+}
+
+/// Generate a new token from 32 random bytes, hex-encoded.
+impl Default for RefreshToken {
+    fn default() -> Self {
+        let bytes: [u8; 32] = rand::random();
+        RefreshToken(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+}
+
+impl AsRef<str> for RefreshToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_tokens_are_random() {
+        let token = RefreshToken::default();
+        assert_eq!(token.as_ref().len(), 64);
+        assert!(token.as_ref().chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(token, RefreshToken::default());
+    }
+}
@@ -1,31 +1,60 @@
-use super::{Email, Password};
+use super::{Email, HashedPassword, Password, TotpSecret, TwoFAMethod, UserId};
 use crate::{routes::SignupRequest, AuthApiError};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct User {
+    /// Never changes, unlike `email` might.
+    pub id: UserId,
     pub email: Email,
-    pub password: Password,
+    pub password: HashedPassword,
     pub requires_2fa: bool,
-}
-
-impl TryFrom<SignupRequest> for User {
-    type Error = AuthApiError;
-    fn try_from(request: SignupRequest) -> Result<Self, Self::Error> {
-        let email = request.email.parse()?;
-        let password = request.password.parse()?;
-        Ok(User::new(email, password, request.requires_2fa))
-    }
+    /// The second factor asked for when `requires_2fa` is set.
+    pub two_fa_method: TwoFAMethod,
+    /// The authenticator app secret, once the user has confirmed enrolling it.
+    pub totp_secret: Option<TotpSecret>,
+    /// A secret handed out for enrollment but not yet confirmed with a code.
+    pub pending_totp_secret: Option<TotpSecret>,
+    /// The time step of the last TOTP code accepted, which no code from
+    /// that step or before can be used after.
+    pub totp_last_step: Option<u64>,
+    /// Whether the user has shown they own `email`, by following the link
+    /// emailed to them at signup.
+    pub verified: bool,
 }
 
 impl User {
-    pub fn new(email: Email, password: Password, requires_2fa: bool) -> Self {
+    /// A new user with a fresh ID, who has yet to verify their email address.
+    pub fn new(email: Email, password: HashedPassword, requires_2fa: bool) -> Self {
         User {
+            id: UserId::default(),
             email,
             password,
             requires_2fa,
+            two_fa_method: TwoFAMethod::Email,
+            totp_secret: None,
+            pending_totp_secret: None,
+            totp_last_step: None,
+            verified: false,
+        }
+    }
+
+    /// The authenticator secret to check 2FA codes against, if the user
+    /// has chosen TOTP; otherwise their codes are emailed.
+    pub fn active_totp_secret(&self) -> Option<&TotpSecret> {
+        match self.two_fa_method {
+            TwoFAMethod::Totp => self.totp_secret.as_ref(),
+            TwoFAMethod::Email => None,
         }
     }
+
+    /// Validate a signup request and hash its password.
+    pub async fn parse(request: SignupRequest) -> Result<Self, AuthApiError> {
+        let email: Email = request.email.parse()?;
+        let password: Password = request.password.parse()?;
+        let password = HashedPassword::parse(password).await?;
+        Ok(User::new(email, password, request.requires_2fa))
+    }
 }
 
 #[cfg(test)]
@@ -34,10 +63,17 @@ mod tests {
     use quickcheck_macros::quickcheck;
     use std::str::FromStr;
 
+    fn parse_blocking(request: SignupRequest) -> Result<User, AuthApiError> {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("Failed to build runtime")
+            .block_on(User::parse(request))
+    }
+
     #[quickcheck]
     fn prop_users_must_have_valid_email(email: String) -> bool {
         Email::from_str(&email).is_ok()
-            || User::try_from(SignupRequest {
+            || parse_blocking(SignupRequest {
                 email,
                 password: "password".to_string(),
                 requires_2fa: false,
@@ -48,11 +84,29 @@ mod tests {
     #[quickcheck]
     fn prop_users_must_have_valid_password(password: String) -> bool {
         Password::from_str(&password).is_ok()
-            || User::try_from(SignupRequest {
+            || parse_blocking(SignupRequest {
                 email: "valid@email.com".to_string(),
                 password,
                 requires_2fa: false,
             })
             .is_err()
     }
+
+    #[tokio::test]
+    async fn test_parse_hashes_password() {
+        let user = User::parse(SignupRequest {
+            email: "valid@email.com".to_string(),
+            password: "password123".to_string(),
+            requires_2fa: false,
+        })
+        .await
+        .expect("valid signup request");
+        assert_ne!(user.password.as_ref(), "password123");
+        assert!(!user.verified);
+        assert!(user
+            .password
+            .verify_raw_password(&"password123".parse().unwrap())
+            .await
+            .is_ok());
+    }
 }
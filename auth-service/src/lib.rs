@@ -1,21 +1,40 @@
-use crate::{domain::AuthApiError, routes::*};
-use app_state::AppState;
+use crate::{
+    domain::AuthApiError,
+    routes::*,
+    utils::{
+        constants::REQUEST_ID_HEADER,
+        metrics::track_metrics,
+        rate_limit::rate_limit,
+        telemetry::{drop_malformed_request_id, make_request_span},
+    },
+};
+use app_state::{AppState, BannedTokenStoreType};
 use axum::{
+    extract::connect_info::{ConnectInfo, IntoMakeServiceWithConnectInfo},
+    http::HeaderName,
+    middleware::{self, AddExtension},
     response::{IntoResponse, Response},
-    routing::post,
+    routing::{delete, get, post},
     serve::Serve,
     Json, Router,
 };
 use reqwest::{Method, StatusCode};
 use serde::{Deserialize, Serialize};
-use std::error::Error;
-use tokio::net::TcpListener;
-use tower_http::{cors::CorsLayer, services::ServeDir};
+use std::{error::Error, net::SocketAddr, time::Duration};
+use tokio::{net::TcpListener, task::JoinHandle};
+use tower_http::{
+    cors::CorsLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    services::ServeDir,
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::Level;
 
 pub mod app_state;
 pub mod domain;
 pub mod routes;
 pub mod services;
+pub mod settings;
 pub mod utils;
 
 // This struct represents an API error that will be serialized to JSON.
@@ -26,7 +45,7 @@ pub struct ErrorResponse {
 
 impl IntoResponse for AuthApiError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
+        let (status, error_message) = match &self {
             AuthApiError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthApiError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
             AuthApiError::IncorrectCredentials => (StatusCode::UNAUTHORIZED, "Unauthorized"),
@@ -36,52 +55,160 @@ impl IntoResponse for AuthApiError {
             AuthApiError::MissingToken => (StatusCode::BAD_REQUEST, "Missing token"),
             AuthApiError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token"),
             AuthApiError::InvalidTwoFaCode => (StatusCode::UNAUTHORIZED, "Invalid 2FA code"),
+            AuthApiError::TooManyRequests => (StatusCode::TOO_MANY_REQUESTS, "Too many requests"),
+            AuthApiError::AccountLocked => (StatusCode::LOCKED, "Account locked"),
+            AuthApiError::EmailNotVerified => (StatusCode::FORBIDDEN, "Email not verified"),
+            AuthApiError::TotpNotEnrolled => {
+                (StatusCode::BAD_REQUEST, "Authenticator app not enrolled")
+            }
         };
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
         });
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        // for `track_metrics` to count
+        response.extensions_mut().insert(self);
+        response
     }
 }
 
 // This struct encapsulates our application-related logic.
 pub struct Application {
-    server: Serve<TcpListener, Router, Router>,
+    server: Serve<
+        TcpListener,
+        IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
+    banned_token_sweeper: JoinHandle<()>,
+    store_metrics_reporter: JoinHandle<()>,
     // address is exposed as a public field
     // so we have access to it in tests.
     pub address: String,
 }
 
 impl Application {
-    pub async fn build(app_state: AppState, address: &str) -> Result<Self, Box<dyn Error>> {
-        let ip = std::option_env!("DROPLET_IP").unwrap_or("localhost");
-        let allowed_origins = [(format!("http://{}", ip)).parse()?];
+    pub async fn build(app_state: AppState) -> Result<Self, Box<dyn Error>> {
+        let settings = app_state.settings.clone();
 
         let cors = CorsLayer::new()
-            .allow_origin(allowed_origins)
-            .allow_methods([Method::GET, Method::POST])
+            .allow_origin(settings.allowed_origins.clone())
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
             .allow_credentials(true);
+        let request_id_header = HeaderName::from_static(REQUEST_ID_HEADER);
 
-        let router = Router::new()
-            .fallback_service(ServeDir::new("assets"))
+        // Routes that take a password, a 2FA code or a reset token, which are
+        // worth guessing, or that send email
+        let rate_limited = Router::new()
             .route("/signup", post(signup))
             .route("/login", post(login))
             .route("/verify-2fa", post(verify_2fa))
+            .route("/password-reset/request", post(request_password_reset))
+            .route("/password-reset/confirm", post(confirm_password_reset))
+            .route("/verify-email/resend", post(resend_verification_email))
+            .route("/change-password", post(change_password))
+            .route("/account", delete(delete_account))
+            .route("/2fa/totp/enroll", post(enroll_totp))
+            .route("/2fa/totp/confirm", post(confirm_totp))
+            .route("/2fa/method", post(set_two_fa_method))
+            .route("/2fa/recovery-codes", post(regenerate_recovery_codes))
+            .route_layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                rate_limit,
+            ));
+
+        let router = Router::new()
+            .fallback_service(ServeDir::new(&settings.assets_dir))
+            .merge(rate_limited)
             .route("/logout", post(logout))
             .route("/verify-token", post(verify_token))
-            .with_state(app_state)
-            .layer(cors);
+            .route("/introspect", post(introspect))
+            .route("/verify-email", get(verify_email))
+            .route("/refresh", post(refresh))
+            .route("/.well-known/jwks.json", get(jwks))
+            .route("/debug/banned-tokens", get(banned_token_stats))
+            .route("/admin/unlock-account", post(unlock_account))
+            .route("/metrics", get(metrics))
+            .route_layer(middleware::from_fn_with_state(
+                app_state.metrics.clone(),
+                track_metrics,
+            ))
+            .with_state(app_state.clone())
+            .layer(cors)
+            // Each layer wraps the ones above it, so requests are given an
+            // ID (unless the caller sent a well-formed one) before they are
+            // traced.
+            .layer(PropagateRequestIdLayer::new(request_id_header.clone()))
+            .layer(
+                TraceLayer::new_for_http()
+                    .make_span_with(make_request_span)
+                    .on_response(DefaultOnResponse::new().level(Level::INFO)),
+            )
+            .layer(SetRequestIdLayer::new(request_id_header, MakeRequestUuid))
+            .layer(middleware::from_fn(drop_malformed_request_id));
 
-        let listener = tokio::net::TcpListener::bind(address).await?;
+        let listener = tokio::net::TcpListener::bind(&settings.listen_address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        // Rate limiting needs the client's address
+        let server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
+
+        let store_metrics_reporter =
+            spawn_store_metrics_reporter(app_state.clone(), settings.store_metrics_interval);
+        let banned_token_sweeper = spawn_banned_token_sweeper(
+            app_state.banned_token_store,
+            settings.banned_token_sweep_interval,
+        );
 
         // Create a new Application instance and return it
-        Ok(Self { server, address })
+        Ok(Self {
+            server,
+            banned_token_sweeper,
+            store_metrics_reporter,
+            address,
+        })
     }
 
     pub async fn run(self) -> Result<(), std::io::Error> {
-        println!("listening on {}", &self.address);
-        self.server.await
+        tracing::info!(address = %self.address, "listening");
+        let result = self.server.await;
+        self.banned_token_sweeper.abort();
+        self.store_metrics_reporter.abort();
+        result
     }
 }
+
+// Periodically drop tokens that have expired from the banned token store,
+// so that it doesn't grow with every logout for the life of the process.
+fn spawn_banned_token_sweeper(store: BannedTokenStoreType, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = store.read().await.prune_expired().await {
+                tracing::error!(error = ?e, "Failed to prune banned tokens");
+            }
+        }
+    })
+}
+
+// Periodically count the banned token and 2FA code stores for `/metrics`,
+// rather than on every scrape, as counting a Redis store scans its keys. If
+// a store can't be reached, its gauge keeps its last value.
+fn spawn_store_metrics_reporter(state: AppState, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match state.banned_token_store.read().await.size().await {
+                Ok(size) => state.metrics.set_banned_tokens(size),
+                Err(e) => tracing::warn!(error = ?e, "Failed to count banned tokens"),
+            }
+            match state.two_fa_code_store.read().await.size().await {
+                Ok(size) => state.metrics.set_two_fa_codes(size),
+                Err(e) => tracing::warn!(error = ?e, "Failed to count 2FA codes"),
+            }
+        }
+    })
+}
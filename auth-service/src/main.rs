@@ -1,9 +1,100 @@
-use auth_service::{app_state::AppState, Application};
+use auth_service::{
+    app_state::AppState,
+    services::{
+        RedisBannedTokenStore, RedisFailedLoginStore, RedisRateLimiter, RedisRefreshTokenStore,
+        RedisTwoFACodeStore, SmtpEmailClient, SqlUserStore, WebhookAccountEventHook,
+    },
+    settings::Settings,
+    utils::{jwt_key::JwtKeyring, telemetry::init_tracing},
+    Application,
+};
+use redis::aio::ConnectionManager;
+use std::sync::Arc;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::RwLock,
+};
 
 #[tokio::main]
 async fn main() {
-    let app_state = AppState::default();
-    let app = Application::build(app_state, "0.0.0.0:3000")
+    dotenvy::dotenv().ok();
+    let settings = Settings::load().unwrap_or_else(|e| e.exit());
+    init_tracing(settings.log_format);
+    let jwt_keyring = JwtKeyring::from_source(&settings.jwt_key).unwrap_or_else(|e| {
+        eprintln!(
+            "error: failed to load JWT keys ({:?}): {}",
+            settings.jwt_key, e
+        );
+        std::process::exit(2)
+    });
+    let mut app_state = AppState::new(settings.clone(), jwt_keyring);
+    // Persist users in PostgreSQL or SQLite when a database is configured;
+    // otherwise the default in-memory store is used.
+    if let Some(url) = &settings.database_url {
+        let user_store = SqlUserStore::connect(url)
+            .await
+            .expect("Failed to connect to user database");
+        app_state.user_store = Arc::new(RwLock::new(user_store));
+    }
+    // Share banned tokens, 2FA codes, refresh tokens, failed logins and rate limits
+    // between replicas through Redis when it is configured; otherwise they are kept
+    // in process memory.
+    if let Some(url) = &settings.redis_url {
+        let client = redis::Client::open(url.as_str()).expect("Invalid REDIS_URL");
+        let conn = ConnectionManager::new(client)
+            .await
+            .expect("Failed to connect to Redis");
+        app_state.banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(
+            conn.clone(),
+            settings.token_ttl,
+            settings.token_policy.leeway,
+        )));
+        app_state.two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(
+            conn.clone(),
+            settings.two_fa_code_ttl,
+            settings.max_two_fa_attempts,
+        )));
+        app_state.refresh_token_store = Arc::new(RwLock::new(RedisRefreshTokenStore::new(
+            conn.clone(),
+            settings.refresh_token_ttl,
+        )));
+        app_state.failed_login_store = Arc::new(RwLock::new(RedisFailedLoginStore::new(
+            conn.clone(),
+            settings.lockout,
+        )));
+        app_state.rate_limiter = Arc::new(RedisRateLimiter::new(conn));
+    }
+    // Deliver email over SMTP when a relay is configured; otherwise
+    // the default client prints messages to stdout.
+    if let Some(smtp) = &settings.smtp {
+        let email_client = SmtpEmailClient::new(&smtp.url, &smtp.sender)
+            .expect("Failed to build SMTP email client");
+        app_state.email_client = Arc::new(email_client);
+    }
+    // Post account events to a webhook when one is configured; otherwise
+    // the default hook only logs them.
+    if let Some(webhook) = &settings.account_event_webhook {
+        let hook = WebhookAccountEventHook::new(&webhook.url, &webhook.secret)
+            .expect("Failed to build account event webhook");
+        app_state.account_event_hook = Arc::new(hook);
+    }
+    // Reload the JWT keyring on SIGHUP, so that keys can be rotated
+    // without a restart (and without logging everyone out)
+    let mut hangup = signal(SignalKind::hangup()).expect("Failed to listen for SIGHUP");
+    let jwt_keyring = app_state.jwt_keyring.clone();
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            match JwtKeyring::from_source(&settings.jwt_key) {
+                Ok(keyring) => {
+                    let kid = keyring.signing_key().kid().to_owned();
+                    jwt_keyring.replace(keyring);
+                    tracing::info!(kid, "Reloaded JWT keyring");
+                }
+                Err(e) => tracing::error!(error = %e, "Failed to reload JWT keyring"),
+            }
+        }
+    });
+    let app = Application::build(app_state)
         .await
         .expect("Failed to build application");
     app.run().await.expect("Failed to run application");
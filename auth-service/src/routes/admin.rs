@@ -0,0 +1,47 @@
+use axum::{
+    extract::State,
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthApiError, Email},
+    settings::BearerToken,
+};
+
+/// Lift the lockout on an account and forget its failed logins.
+pub async fn unlock_account(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<UnlockAccountRequest>,
+) -> Result<StatusCode, AuthApiError> {
+    authorize_bearer(&headers, state.settings.admin_token.as_ref())?;
+    let email: Email = request.email.parse()?;
+    state.failed_login_store.write().await.reset(&email).await?;
+    tracing::info!("Account unlocked by an admin");
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+pub struct UnlockAccountRequest {
+    pub email: String,
+}
+
+/// Check that the request has `Authorization: Bearer <expected>`. When no
+/// token is configured, everyone is refused.
+pub(crate) fn authorize_bearer(
+    headers: &HeaderMap,
+    expected: Option<&BearerToken>,
+) -> Result<(), AuthApiError> {
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(AuthApiError::MissingToken)?;
+    match expected {
+        Some(expected) if expected.matches(token) => Ok(()),
+        _ => Err(AuthApiError::InvalidToken),
+    }
+}
@@ -0,0 +1,58 @@
+use axum::{extract::State, Json};
+use axum_extra::extract::CookieJar;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::{add_session_cookies, authenticate, check_password, token_user_error};
+use crate::{
+    app_state::AppState,
+    domain::{AuthApiError, AuthMethod, HashedPassword, Password},
+};
+
+/// Change the logged-in user's password, given their current one. Every
+/// other session of theirs ends; this one carries on with new tokens.
+pub async fn change_password(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<(CookieJar, StatusCode), AuthApiError> {
+    let (token, user) = authenticate(&state, &jar).await?;
+
+    let current_password: Password = request.current_password.parse()?;
+    let new_password: Password = request.new_password.parse()?;
+    check_password(&state, &user, &current_password).await?;
+    let new_password = HashedPassword::parse(new_password).await?;
+    state
+        .user_store
+        .write()
+        .await
+        .update_password(&user.email, new_password)
+        .await
+        .map_err(token_user_error)?;
+
+    let banned_token_store = state.banned_token_store.write().await;
+    banned_token_store.ban_user(&user.id).await?;
+    // the ban doesn't cover tokens from the millisecond it was made in
+    banned_token_store.ban(token).await?;
+    drop(banned_token_store);
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .revoke_user(&user.id)
+        .await?;
+
+    // the new session rests on the password just given
+    let jar =
+        add_session_cookies(&state, &user, Uuid::new_v4(), &[AuthMethod::Password], jar).await?;
+    Ok((jar, StatusCode::OK))
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
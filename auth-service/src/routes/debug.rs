@@ -0,0 +1,27 @@
+use axum::{extract::State, http::HeaderMap, Json};
+use serde::{Deserialize, Serialize};
+
+use super::authorize_bearer;
+use crate::{app_state::AppState, domain::AuthApiError};
+
+/// Report how many tokens the banned token store currently holds. Like the
+/// admin routes, this is refused unless `ADMIN_TOKEN` is presented.
+pub async fn banned_token_stats(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<BannedTokenStatsResponse>, AuthApiError> {
+    authorize_bearer(&headers, state.settings.admin_token.as_ref())?;
+    let size = state
+        .banned_token_store
+        .read()
+        .await
+        .size()
+        .await
+        .map_err(AuthApiError::from)?;
+    Ok(Json(BannedTokenStatsResponse { size }))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BannedTokenStatsResponse {
+    pub size: usize,
+}
@@ -0,0 +1,63 @@
+use axum::{extract::State, Json};
+use axum_extra::extract::CookieJar;
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use super::{authenticate, check_password, token_user_error};
+use crate::{
+    app_state::AppState,
+    domain::{AccountEvent, AuthApiError, Password, TwoFACodeStoreError},
+    utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+};
+
+/// Delete the logged-in user's account, given their password. Their
+/// sessions end, and a `deleted` account event is sent so that other
+/// services can clean up after them.
+pub async fn delete_account(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<DeleteAccountRequest>,
+) -> Result<(CookieJar, StatusCode), AuthApiError> {
+    let (token, user) = authenticate(&state, &jar).await?;
+
+    let password: Password = request.password.parse()?;
+    check_password(&state, &user, &password).await?;
+    let email = user.email;
+    state
+        .user_store
+        .write()
+        .await
+        .delete_user(&email)
+        .await
+        .map_err(token_user_error)?;
+
+    match state.two_fa_code_store.write().await.remove(&email).await {
+        Ok(()) | Err(TwoFACodeStoreError::EmailNotFound) => {}
+        Err(e) => return Err(AuthApiError::from(e)),
+    }
+    // so that signing up again with the address starts afresh
+    state.failed_login_store.write().await.reset(&email).await?;
+    let banned_token_store = state.banned_token_store.write().await;
+    banned_token_store.ban_user(&user.id).await?;
+    // the ban doesn't cover tokens from the millisecond it was made in
+    banned_token_store.ban(token).await?;
+    drop(banned_token_store);
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .revoke_user(&user.id)
+        .await?;
+
+    let event = AccountEvent::Deleted { user_id: user.id };
+    if let Err(e) = state.account_event_hook.notify(&event).await {
+        tracing::error!(error = ?e, "Failed to send account event");
+    }
+    let jar = jar.remove(JWT_COOKIE_NAME).remove(REFRESH_COOKIE_NAME);
+    Ok((jar, StatusCode::OK))
+}
+
+#[derive(Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: String,
+}
@@ -0,0 +1,78 @@
+use axum::{extract::State, http::HeaderMap, Json};
+use serde::{Deserialize, Serialize};
+
+use super::authorize_bearer;
+use crate::{
+    app_state::AppState,
+    domain::{AuthApiError, AuthMethod, Token},
+    utils::auth::validate_token,
+};
+
+/// Tell another service whether a token is active and, if it is, who it
+/// was issued to and how they logged in, after RFC 7662. Callers present
+/// `INTROSPECTION_TOKEN` as a bearer token.
+pub async fn introspect(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<IntrospectRequest>,
+) -> Result<Json<IntrospectResponse>, AuthApiError> {
+    authorize_bearer(&headers, state.settings.introspection_token.as_ref())?;
+
+    if state
+        .banned_token_store
+        .read()
+        .await
+        .is_banned(&request.token)
+        .await?
+    {
+        return Ok(Json(IntrospectResponse::default()));
+    }
+    // the reason a token is inactive is none of the caller's business
+    let Ok(claims) = validate_token(
+        &request.token,
+        &state.jwt_keyring.current(),
+        &state.settings.token_policy,
+    ) else {
+        return Ok(Json(IntrospectResponse::default()));
+    };
+    Ok(Json(IntrospectResponse {
+        active: true,
+        sub: Some(claims.sub),
+        iss: Some(claims.iss),
+        aud: Some(claims.aud),
+        exp: Some(claims.exp),
+        nbf: Some(claims.nbf),
+        iat: Some(claims.iat),
+        jti: Some(claims.jti),
+        amr: Some(claims.amr),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct IntrospectRequest {
+    pub token: Token,
+}
+
+/// Only `active` is given for inactive tokens. There are no scopes or
+/// roles to report yet.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IntrospectResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    /// How the user logged in, e.g. `["pwd", "otp"]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amr: Option<Vec<AuthMethod>>,
+}
@@ -0,0 +1,11 @@
+use axum::{extract::State, Json};
+use jsonwebtoken::jwk::JwkSet;
+
+use crate::app_state::AppState;
+
+/// Publish the public keys tokens are signed with, including older keys
+/// whose tokens are still accepted, so that other services can verify
+/// them without a shared secret.
+pub async fn jwks(State(state): State<AppState>) -> Json<JwkSet> {
+    Json(state.jwt_keyring.current().jwks())
+}
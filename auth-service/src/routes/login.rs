@@ -6,11 +6,16 @@ use axum::{
 use axum_extra::extract::CookieJar;
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
+use super::add_session_cookies;
 use crate::{
     app_state::AppState,
-    domain::{AuthApiError, Email, LoginAttemptId, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    domain::{
+        AccountEvent, AuthApiError, AuthMethod, Email, LoginAttemptId, Password, TwoFACode,
+        TwoFAMethod, User, UserStoreError,
+    },
+    utils::auth::PasswordHashError,
 };
 
 #[axum::debug_handler]
@@ -19,50 +24,175 @@ pub async fn login(
     jar: CookieJar,
     Json(request): Json<LoginRequest>,
 ) -> Result<(CookieJar, LoginResponse), AuthApiError> {
-    let user = state
+    let email: Email = request.email.parse()?;
+    let password: Password = request.password.parse()?;
+    // Locked accounts are refused before the password is checked, so that
+    // guessing on regardless gets an attacker nowhere.
+    if state
+        .failed_login_store
+        .read()
+        .await
+        .locked_for(&email)
+        .await?
+        .is_some()
+    {
+        return Err(AuthApiError::AccountLocked);
+    }
+    let result = state
         .user_store
         .read()
         .await
-        .validate_user(&request.email.parse()?, &request.password.parse()?)
+        .validate_user(&email, &password)
+        .await;
+    let user = match result {
+        Ok(user) => user,
+        Err(UserStoreError::IncorrectCredentials) => {
+            return Err(record_failed_login(&state, &email).await)
+        }
+        Err(e) => return Err(AuthApiError::from(e)),
+    };
+    state
+        .failed_login_store
+        .write()
         .await
-        .map_err(AuthApiError::from)?;
+        .reset(&user.email)
+        .await?;
+    // only checked once the password is right, so as not to tell anyone
+    // but the user whether the address is verified
+    if state.settings.require_email_verification && !user.verified {
+        return Err(AuthApiError::EmailNotVerified);
+    }
 
     if user.requires_2fa {
-        handle_2fa(State(state), &user.email, jar).await
+        handle_2fa(State(state), &user, jar).await
     } else {
-        handle_non_2fa(&user.email, jar).await
+        handle_non_2fa(&state, &user, jar).await
+    }
+}
+
+/// Check the password of a logged-in user who is asked for it again, e.g.
+/// to change it. Locked accounts are refused and wrong passwords count
+/// against the account just as at login, so that a session is no way
+/// around the lockout.
+pub(crate) async fn check_password(
+    state: &AppState,
+    user: &User,
+    password: &Password,
+) -> Result<(), AuthApiError> {
+    if state
+        .failed_login_store
+        .read()
+        .await
+        .locked_for(&user.email)
+        .await?
+        .is_some()
+    {
+        return Err(AuthApiError::AccountLocked);
     }
+    match user.password.verify_raw_password(password).await {
+        Ok(()) => {}
+        Err(PasswordHashError::IncorrectPassword) => {
+            return Err(record_failed_login(state, &user.email).await)
+        }
+        Err(e) => return Err(AuthApiError::from(e)),
+    }
+    state
+        .failed_login_store
+        .write()
+        .await
+        .reset(&user.email)
+        .await?;
+    Ok(())
+}
+
+// Count a wrong password against the account, locking it (and saying so)
+// if it has had too many.
+async fn record_failed_login(state: &AppState, email: &Email) -> AuthApiError {
+    let result = state
+        .failed_login_store
+        .write()
+        .await
+        .record_failure(email)
+        .await;
+    let lockout = match result {
+        Ok(Some(lockout)) => lockout,
+        Ok(None) => return AuthApiError::IncorrectCredentials,
+        Err(e) => return AuthApiError::from(e),
+    };
+    tracing::warn!(
+        locked_for_seconds = lockout.as_secs(),
+        "Account locked after repeated failed logins"
+    );
+    // unknown addresses are locked too, but there is no one to tell
+    if let Ok(user) = state.user_store.read().await.get_user(email).await {
+        let event = AccountEvent::Locked {
+            user_id: user.id,
+            locked_for_seconds: lockout.as_secs(),
+        };
+        if let Err(e) = state.account_event_hook.notify(&event).await {
+            tracing::error!(error = ?e, "Failed to send account event");
+        }
+    }
+    AuthApiError::AccountLocked
 }
 
 async fn handle_2fa(
     State(state): State<AppState>,
-    email: &Email,
+    user: &User,
     jar: CookieJar,
 ) -> Result<(CookieJar, LoginResponse), AuthApiError> {
+    let email = &user.email;
     let login_attempt_id = LoginAttemptId::default();
+    // With TOTP the code comes from the user's app, and this one is never
+    // sent; the attempt is stored all the same, to tie `/verify-2fa` to a
+    // login that got the password right.
     let two_fa_code = TwoFACode::default();
     state
         .two_fa_code_store
         .write()
         .await
-        .add(email.clone(), login_attempt_id, two_fa_code)
+        .add(email.clone(), login_attempt_id.clone(), two_fa_code.clone())
         .await
         .map_err(AuthApiError::from)?;
+    let two_fa_method = if user.active_totp_secret().is_some() {
+        TwoFAMethod::Totp
+    } else {
+        // the store isn't locked while sending, so that a slow mail relay
+        // holds up only this login
+        if let Err(error) = state
+            .email_client
+            .send_email(email, "Your 2FA code", two_fa_code.as_ref())
+            .await
+        {
+            // don't leave behind a code the user never received, unless
+            // another login has replaced it in the meantime
+            let mut two_fa_code_store = state.two_fa_code_store.write().await;
+            if let Ok((stored_id, _)) = two_fa_code_store.get(email).await {
+                if stored_id == login_attempt_id {
+                    two_fa_code_store.remove(email).await.ok();
+                }
+            }
+            return Err(AuthApiError::from(error));
+        }
+        TwoFAMethod::Email
+    };
     Ok((
         jar,
         LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
             message: String::from("2FA required"),
-            login_attempt_id: String::from("123456"),
+            login_attempt_id: login_attempt_id.as_ref().to_owned(),
+            two_fa_method,
         }),
     ))
 }
 
 async fn handle_non_2fa(
-    email: &Email,
+    state: &AppState,
+    user: &User,
     jar: CookieJar,
 ) -> Result<(CookieJar, LoginResponse), AuthApiError> {
-    let auth_cookie = generate_auth_cookie(email).map_err(AuthApiError::from)?;
-    let updated_jar = jar.add(auth_cookie);
+    let updated_jar =
+        add_session_cookies(state, user, Uuid::new_v4(), &[AuthMethod::Password], jar).await?;
     Ok((updated_jar, LoginResponse::RegularAuth))
 }
 
@@ -84,6 +214,9 @@ pub struct TwoFactorAuthResponse {
     pub message: String,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: String,
+    /// Where the user should look for their code
+    #[serde(rename = "2FAMethod")]
+    pub two_fa_method: TwoFAMethod,
 }
 
 impl IntoResponse for LoginResponse {
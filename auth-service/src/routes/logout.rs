@@ -4,8 +4,11 @@ use reqwest::StatusCode;
 
 use crate::{
     app_state::AppState,
-    domain::{AuthApiError, Token},
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    domain::{AuthApiError, RefreshToken, RefreshTokenStoreError, Token},
+    utils::{
+        auth::validate_token,
+        constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    },
 };
 
 pub async fn logout(
@@ -14,12 +17,34 @@ pub async fn logout(
 ) -> Result<(CookieJar, StatusCode), AuthApiError> {
     let cookie = jar.get(JWT_COOKIE_NAME).ok_or(AuthApiError::MissingToken)?;
     let token = Token::from(cookie.value());
-    let _claims = validate_token(&token)
-        .await
-        .map_err(|_| AuthApiError::InvalidToken)?;
-    // remove JWT cookie from the CookieJar
-    let jar = jar.remove(JWT_COOKIE_NAME);
+    let _claims = validate_token(
+        &token,
+        &state.jwt_keyring.current(),
+        &state.settings.token_policy,
+    )
+    .map_err(|_| AuthApiError::InvalidToken)?;
+    // end the refresh token family too, so the session can't be renewed
+    if let Some(cookie) = jar.get(REFRESH_COOKIE_NAME) {
+        let refresh_token = RefreshToken::from(cookie.value());
+        let mut refresh_token_store = state.refresh_token_store.write().await;
+        match refresh_token_store.consume(&refresh_token).await {
+            Ok(record) => refresh_token_store.revoke_family(&record.family_id).await?,
+            Err(RefreshTokenStoreError::TokenReused(family_id)) => {
+                refresh_token_store.revoke_family(&family_id).await?
+            }
+            Err(RefreshTokenStoreError::TokenNotFound) => {}
+            Err(error) => return Err(AuthApiError::from(error)),
+        }
+    }
+    // remove JWT and refresh token cookies from the CookieJar
+    let jar = jar.remove(JWT_COOKIE_NAME).remove(REFRESH_COOKIE_NAME);
     // add token to the banned list
-    state.banned_token_store.write().await.ban(token).await;
+    state
+        .banned_token_store
+        .write()
+        .await
+        .ban(token)
+        .await
+        .map_err(AuthApiError::from)?;
     Ok((jar, StatusCode::OK))
 }
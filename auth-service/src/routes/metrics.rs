@@ -0,0 +1,25 @@
+use axum::{
+    extract::State,
+    http::{header::CONTENT_TYPE, HeaderMap},
+    response::IntoResponse,
+};
+
+use super::authorize_bearer;
+use crate::{app_state::AppState, domain::AuthApiError};
+
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Report request, error and store metrics for Prometheus to scrape, which
+/// presents `METRICS_TOKEN` as a bearer token. Store sizes are as last
+/// counted, every `STORE_METRICS_INTERVAL_SECONDS`.
+pub async fn metrics(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AuthApiError> {
+    authorize_bearer(&headers, state.settings.metrics_token.as_ref())?;
+    let body = state
+        .metrics
+        .encode()
+        .map_err(|_| AuthApiError::UnexpectedError)?;
+    Ok(([(CONTENT_TYPE, OPENMETRICS_CONTENT_TYPE)], body))
+}
@@ -1,11 +1,37 @@
+mod admin;
+mod change_password;
+mod debug;
+mod delete_account;
+mod introspect;
+mod jwks;
 mod login;
 mod logout;
+mod metrics;
+mod password_reset;
+mod recovery_codes;
+mod refresh;
 mod signup;
+mod totp;
+mod two_fa_method;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
 
+pub use admin::*;
+pub use change_password::*;
+pub use debug::*;
+pub use delete_account::*;
+pub use introspect::*;
+pub use jwks::*;
 pub use login::*;
 pub use logout::*;
+pub use metrics::*;
+pub use password_reset::*;
+pub use recovery_codes::*;
+pub use refresh::*;
 pub use signup::*;
+pub use totp::*;
+pub use two_fa_method::*;
 pub use verify_2fa::*;
+pub use verify_email::*;
 pub use verify_token::*;
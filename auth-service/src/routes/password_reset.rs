@@ -0,0 +1,142 @@
+use axum::{extract::State, response::IntoResponse, Json};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use tracing::Instrument;
+
+use super::token_user_error;
+use crate::{
+    app_state::AppState,
+    domain::{
+        AccountEvent, AuthApiError, BannedTokenResult, Email, HashedPassword, Password, Token,
+        UserStoreError,
+    },
+    utils::{
+        auth::{generate_email_token, validate_email_token},
+        constants::PASSWORD_RESET_AUDIENCE,
+    },
+};
+
+/// Email a password reset token to the user, if there is one. The response
+/// is the same either way, and the email is sent after responding, so that
+/// neither what is returned nor how long it takes says whether the address
+/// is registered.
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordResetRequest>,
+) -> Result<impl IntoResponse, AuthApiError> {
+    let email: Email = request.email.parse()?;
+    tokio::spawn(send_password_reset_email(state, email).in_current_span());
+    let response = Json(PasswordResetResponse {
+        message: "If the email is registered, a password reset token has been sent".to_string(),
+    });
+    Ok((StatusCode::ACCEPTED, response))
+}
+
+async fn send_password_reset_email(state: AppState, email: Email) {
+    match state.user_store.read().await.get_user(&email).await {
+        Ok(_) => {}
+        Err(UserStoreError::UserNotFound) => return,
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to look up user for password reset");
+            return;
+        }
+    }
+    let ttl = state.settings.password_reset_token_ttl;
+    let keyring = state.jwt_keyring.current();
+    let token = match generate_email_token(&email, PASSWORD_RESET_AUDIENCE, &keyring, ttl) {
+        Ok(token) => token,
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to generate password reset token");
+            return;
+        }
+    };
+    let content = format!(
+        "Use this token within {} minutes to reset your password: {}",
+        ttl.as_secs().div_ceil(60),
+        token
+    );
+    if let Err(e) = state
+        .email_client
+        .send_email(&email, "Reset your password", &content)
+        .await
+    {
+        tracing::error!(error = ?e, "Failed to send password reset email");
+    }
+}
+
+/// Set a new password with an emailed reset token, which is then used up,
+/// and end every session of the user.
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<ConfirmPasswordResetRequest>,
+) -> Result<StatusCode, AuthApiError> {
+    let claims = validate_email_token(
+        &request.token,
+        PASSWORD_RESET_AUDIENCE,
+        &state.jwt_keyring.current(),
+    )?;
+    let email: Email = claims.sub.parse().map_err(|_| AuthApiError::InvalidToken)?;
+    // check the new password before using up the token on it
+    let password: Password = request.password.parse()?;
+    let password = HashedPassword::parse(password).await?;
+
+    // Banning the token is what uses it up, and only one request can be
+    // the first to ban it.
+    let result = state
+        .banned_token_store
+        .write()
+        .await
+        .ban(request.token)
+        .await?;
+    if result == BannedTokenResult::TokenAlreadyBanned {
+        return Err(AuthApiError::InvalidToken);
+    }
+
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(token_user_error)?;
+    state
+        .user_store
+        .write()
+        .await
+        .update_password(&user.email, password)
+        .await?;
+    state
+        .banned_token_store
+        .write()
+        .await
+        .ban_user(&user.id)
+        .await?;
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .revoke_user(&user.id)
+        .await?;
+
+    let event = AccountEvent::PasswordReset { user_id: user.id };
+    if let Err(e) = state.account_event_hook.notify(&event).await {
+        tracing::error!(error = ?e, "Failed to send account event");
+    }
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Serialize)]
+pub struct PasswordResetResponse {
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmPasswordResetRequest {
+    pub token: Token,
+    pub password: String,
+}
@@ -0,0 +1,69 @@
+use axum::{extract::State, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use super::{authenticate, check_password, token_user_error};
+use crate::{
+    app_state::AppState,
+    domain::{AccountEvent, AuthApiError, Password, RecoveryCode, UserId, UserStoreError},
+    utils::constants::RECOVERY_CODE_COUNT,
+};
+
+/// Replace the logged-in user's recovery codes with a new set, e.g. once
+/// they have used some or lost the old ones. The codes stand in for the
+/// second factor, so this takes the user's current password too.
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<RegenerateRecoveryCodesRequest>,
+) -> Result<Json<RecoveryCodesResponse>, AuthApiError> {
+    let (_, user) = authenticate(&state, &jar).await?;
+    let current_password: Password = request.current_password.parse()?;
+    check_password(&state, &user, &current_password).await?;
+    let recovery_codes = issue_recovery_codes(&state, &user.id)
+        .await
+        .map_err(token_user_error)?;
+
+    let event = AccountEvent::RecoveryCodesRegenerated { user_id: user.id };
+    if let Err(e) = state.account_event_hook.notify(&event).await {
+        tracing::error!(error = ?e, "Failed to send account event");
+    }
+    Ok(Json(RecoveryCodesResponse {
+        recovery_codes: Some(recovery_codes),
+    }))
+}
+
+/// Give the user a new set of recovery codes, returned for showing to them
+/// this once; only their hashes are kept.
+pub(crate) async fn issue_recovery_codes(
+    state: &AppState,
+    id: &UserId,
+) -> Result<Vec<String>, UserStoreError> {
+    let codes: Vec<RecoveryCode> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| RecoveryCode::default())
+        .collect();
+    state
+        .user_store
+        .write()
+        .await
+        .set_recovery_codes(id, &codes)
+        .await?;
+    Ok(codes.iter().map(RecoveryCode::to_string).collect())
+}
+
+#[derive(Deserialize)]
+pub struct RegenerateRecoveryCodesRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+}
+
+/// Sent when 2FA is turned on or the codes are regenerated.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    #[serde(
+        rename = "recoveryCodes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub recovery_codes: Option<Vec<String>>,
+}
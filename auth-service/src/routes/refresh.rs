@@ -0,0 +1,92 @@
+use axum::extract::State;
+use axum_extra::extract::CookieJar;
+use reqwest::StatusCode;
+use uuid::Uuid;
+
+use super::token_user_error;
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthApiError, AuthMethod, RefreshToken, RefreshTokenRecord, RefreshTokenStoreError, User,
+    },
+    utils::{
+        auth::{create_refresh_cookie, generate_auth_cookie},
+        constants::REFRESH_COOKIE_NAME,
+    },
+};
+
+pub async fn refresh(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<(CookieJar, StatusCode), AuthApiError> {
+    let cookie = jar
+        .get(REFRESH_COOKIE_NAME)
+        .ok_or(AuthApiError::MissingToken)?;
+    let token = RefreshToken::from(cookie.value());
+
+    let mut refresh_token_store = state.refresh_token_store.write().await;
+    let record = match refresh_token_store.consume(&token).await {
+        Ok(record) => record,
+        Err(RefreshTokenStoreError::TokenReused(family_id)) => {
+            // refresh tokens are single-use, so someone else holds a copy
+            // of this one; log out every session descended from it
+            refresh_token_store
+                .revoke_family(&family_id)
+                .await
+                .map_err(AuthApiError::from)?;
+            return Err(AuthApiError::InvalidToken);
+        }
+        Err(error) => return Err(AuthApiError::from(error)),
+    };
+    drop(refresh_token_store);
+
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user_by_id(&record.user_id)
+        .await
+        .map_err(token_user_error)?;
+    let jar = add_session_cookies(&state, &user, record.family_id, &record.amr, jar).await?;
+    Ok((jar, StatusCode::OK))
+}
+
+/// Add a new access token cookie and a new refresh token cookie for `user`
+/// to `jar`.
+/// Pass a fresh `family_id` and the ways the user just authenticated when
+/// starting a session, or those of the refresh token being rotated.
+pub(crate) async fn add_session_cookies(
+    state: &AppState,
+    user: &User,
+    family_id: Uuid,
+    amr: &[AuthMethod],
+    jar: CookieJar,
+) -> Result<CookieJar, AuthApiError> {
+    let auth_cookie = generate_auth_cookie(
+        &user.id,
+        amr,
+        &state.jwt_keyring.current(),
+        &state.settings.token_policy,
+        state.settings.token_ttl,
+    )
+    .map_err(AuthApiError::from)?;
+    let refresh_token = RefreshToken::default();
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .add(
+            refresh_token.clone(),
+            RefreshTokenRecord {
+                user_id: user.id,
+                family_id,
+                amr: amr.to_vec(),
+            },
+        )
+        .await
+        .map_err(AuthApiError::from)?;
+    Ok(jar.add(auth_cookie).add(create_refresh_cookie(
+        &refresh_token,
+        state.settings.refresh_token_ttl,
+    )))
+}
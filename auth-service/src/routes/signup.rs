@@ -2,6 +2,7 @@ use axum::{extract::State, response::IntoResponse, Json};
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 
+use super::{issue_recovery_codes, send_verification_email};
 use crate::{
     app_state::AppState,
     domain::{AuthApiError, User},
@@ -11,15 +12,37 @@ pub async fn signup(
     State(state): State<AppState>,
     Json(request): Json<SignupRequest>,
 ) -> Result<impl IntoResponse, AuthApiError> {
+    let user = User::parse(request).await?;
+    let id = user.id;
+    let email = user.email.clone();
+    let requires_2fa = user.requires_2fa;
     state
         .user_store
         .write()
         .await
-        .add_user(User::try_from(request)?)
+        .add_user(user)
         .await
         .map_err(AuthApiError::from)?;
+    // the account exists either way, so a failure here isn't the client's;
+    // they can ask for the link again at `/verify-email/resend`
+    if let Err(e) = send_verification_email(&state, &email).await {
+        tracing::error!(error = ?e, "Failed to send verification email");
+    }
+    let recovery_codes = if requires_2fa {
+        match issue_recovery_codes(&state, &id).await {
+            Ok(codes) => Some(codes),
+            // they can ask for new ones once logged in
+            Err(e) => {
+                tracing::error!(error = ?e, "Failed to issue recovery codes");
+                None
+            }
+        }
+    } else {
+        None
+    };
     let response = Json(SignupResponse {
         message: "User created successfully!".to_string(),
+        recovery_codes,
     });
     Ok((StatusCode::CREATED, response))
 }
@@ -35,4 +58,7 @@ pub struct SignupRequest {
 #[derive(Serialize)]
 pub struct SignupResponse {
     pub message: String,
+    /// Shown only here, when signing up with 2FA
+    #[serde(rename = "recoveryCodes", skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
 }
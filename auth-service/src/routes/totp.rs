@@ -0,0 +1,117 @@
+use axum::{extract::State, Json};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::{ImageFormat, Luma};
+use qrcode::QrCode;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+use super::{
+    authenticate, check_password, issue_recovery_codes, token_user_error, RecoveryCodesResponse,
+};
+use crate::{
+    app_state::AppState,
+    domain::{AuthApiError, Password, TotpSecret, TwoFACode},
+};
+
+/// Start enrolling an authenticator app for the logged-in user. The new
+/// secret only takes over from their current 2FA once `confirm_totp` has
+/// seen a code made from it; enrolling again replaces it. Nothing changes
+/// until then, so the session alone is enough to start.
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<Json<EnrollTotpResponse>, AuthApiError> {
+    let (_, user) = authenticate(&state, &jar).await?;
+    let secret = TotpSecret::default();
+    let otpauth_uri = secret.otpauth_uri(&state.settings.totp_issuer, &user.email);
+    let qr_code_png = render_qr_code(&otpauth_uri)?;
+    state
+        .user_store
+        .write()
+        .await
+        .set_pending_totp_secret(&user.email, secret.clone())
+        .await
+        .map_err(token_user_error)?;
+    Ok(Json(EnrollTotpResponse {
+        secret: secret.as_ref().to_owned(),
+        otpauth_uri,
+        qr_code_png: STANDARD.encode(qr_code_png),
+    }))
+}
+
+/// Finish enrolling with the first code from the authenticator app, which
+/// then becomes the user's second factor. Once 2FA is on, this also takes
+/// the user's current password, as the session alone would let whoever
+/// holds it take over their second factor. Recovery codes come back if this
+/// turns 2FA on.
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<ConfirmTotpRequest>,
+) -> Result<Json<RecoveryCodesResponse>, AuthApiError> {
+    let (_, user) = authenticate(&state, &jar).await?;
+    let code: TwoFACode = request.two_fa_code.parse()?;
+    let secret = user
+        .pending_totp_secret
+        .clone()
+        .ok_or(AuthApiError::TotpNotEnrolled)?;
+    if user.requires_2fa {
+        let current_password: Password = request
+            .current_password
+            .ok_or(AuthApiError::InvalidCredentials)?
+            .parse()?;
+        check_password(&state, &user, &current_password).await?;
+    }
+    let step = secret.verify(&code).ok_or(AuthApiError::InvalidTwoFaCode)?;
+    state
+        .user_store
+        .write()
+        .await
+        .enable_totp(&user.email, secret, step)
+        .await
+        .map_err(token_user_error)?;
+    if user.requires_2fa {
+        return Ok(Json(RecoveryCodesResponse::default()));
+    }
+    let recovery_codes = issue_recovery_codes(&state, &user.id)
+        .await
+        .map_err(token_user_error)?;
+    Ok(Json(RecoveryCodesResponse {
+        recovery_codes: Some(recovery_codes),
+    }))
+}
+
+// A PNG of the QR code authenticator apps scan to pick up `uri`
+fn render_qr_code(uri: &str) -> Result<Vec<u8>, AuthApiError> {
+    let image = QrCode::new(uri.as_bytes())
+        .map_err(|_| AuthApiError::UnexpectedError)?
+        .render::<Luma<u8>>()
+        .min_dimensions(200, 200)
+        .build();
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|_| AuthApiError::UnexpectedError)?;
+    Ok(png)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnrollTotpResponse {
+    /// Base32, for typing into an app that can't scan the QR code
+    pub secret: String,
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+    /// Base64 encoded
+    #[serde(rename = "qrCodePng")]
+    pub qr_code_png: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmTotpRequest {
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: String,
+    /// Only needed once 2FA is on
+    #[serde(rename = "currentPassword")]
+    pub current_password: Option<String>,
+}
@@ -0,0 +1,45 @@
+use axum::{extract::State, Json};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+
+use super::{authenticate, issue_recovery_codes, token_user_error, RecoveryCodesResponse};
+use crate::{
+    app_state::AppState,
+    domain::{AuthApiError, TwoFAMethod},
+};
+
+/// Turn 2FA on for the logged-in user with the method they pick. TOTP is
+/// only available once an authenticator app has been enrolled. Recovery
+/// codes come back if 2FA was off.
+pub async fn set_two_fa_method(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<SetTwoFAMethodRequest>,
+) -> Result<Json<RecoveryCodesResponse>, AuthApiError> {
+    let (_, user) = authenticate(&state, &jar).await?;
+    if request.method == TwoFAMethod::Totp && user.totp_secret.is_none() {
+        return Err(AuthApiError::TotpNotEnrolled);
+    }
+    state
+        .user_store
+        .write()
+        .await
+        .set_two_fa_method(&user.email, request.method)
+        .await
+        .map_err(token_user_error)?;
+    if user.requires_2fa {
+        return Ok(Json(RecoveryCodesResponse::default()));
+    }
+    let recovery_codes = issue_recovery_codes(&state, &user.id)
+        .await
+        .map_err(token_user_error)?;
+    Ok(Json(RecoveryCodesResponse {
+        recovery_codes: Some(recovery_codes),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct SetTwoFAMethodRequest {
+    #[serde(rename = "2FAMethod")]
+    pub method: TwoFAMethod,
+}
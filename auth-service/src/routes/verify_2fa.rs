@@ -1,6 +1,135 @@
-use axum::response::IntoResponse;
+use axum::{extract::State, Json};
+use axum_extra::extract::CookieJar;
 use reqwest::StatusCode;
+use serde::Deserialize;
+use uuid::Uuid;
 
-pub async fn verify_2fa() -> impl IntoResponse {
-    StatusCode::OK.into_response()
+use super::add_session_cookies;
+use crate::{
+    app_state::AppState,
+    domain::{
+        AccountEvent, AuthApiError, AuthMethod, Email, LoginAttemptId, RecoveryCode, TwoFACode,
+        TwoFACodeStoreError, UserStoreError,
+    },
+    utils::auth::TwoFACodeError,
+};
+
+pub async fn verify_2fa(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<Verify2FARequest>,
+) -> Result<(CookieJar, StatusCode), AuthApiError> {
+    let email: Email = request.email.parse()?;
+    let login_attempt_id: LoginAttemptId = request.login_attempt_id.parse()?;
+    let second_factor = SecondFactor::parse(&request.two_fa_code)?;
+
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthApiError::InvalidTwoFaCode)?;
+
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+    let (expected_attempt_id, expected_code) = two_fa_code_store
+        .get(&email)
+        .await
+        .map_err(|_| AuthApiError::InvalidTwoFaCode)?;
+    // Whatever can be checked without the user store is checked here;
+    // recovery codes and TOTP steps are only checked by using them up,
+    // once the lock is released.
+    let mut totp_step = None;
+    let code_matches = login_attempt_id == expected_attempt_id
+        && match &second_factor {
+            SecondFactor::Code(code) => match user.active_totp_secret() {
+                Some(secret) => {
+                    totp_step = secret.verify(code);
+                    totp_step.is_some()
+                }
+                None => *code == expected_code,
+            },
+            SecondFactor::RecoveryCode(_) => true,
+        };
+    if !code_matches {
+        // too many wrong guesses invalidate the code
+        return match two_fa_code_store.record_failed_attempt(&email).await {
+            Err(TwoFACodeStoreError::UnexpectedError) => Err(AuthApiError::UnexpectedError),
+            _ => Err(AuthApiError::InvalidTwoFaCode),
+        };
+    }
+    // codes are single-use, and taking this one first lets only one request
+    // through per login attempt; a recovery code or TOTP step turned down
+    // below ends the attempt too
+    two_fa_code_store
+        .remove(&email)
+        .await
+        .map_err(AuthApiError::from)?;
+    drop(two_fa_code_store);
+
+    let user_store = state.user_store.read().await;
+    let result = match (&second_factor, totp_step) {
+        (SecondFactor::RecoveryCode(code), _) => {
+            user_store.use_recovery_code(&user.id, code).await.map(Some)
+        }
+        // authenticator codes stay current for a while, so each is used up
+        // like an emailed one
+        (SecondFactor::Code(_), Some(step)) => {
+            user_store.use_totp_step(&email, step).await.map(|()| None)
+        }
+        (SecondFactor::Code(_), None) => Ok(None),
+    };
+    drop(user_store);
+    let recovery_codes_left = match result {
+        Ok(left) => left,
+        Err(UserStoreError::RecoveryCodeNotFound | UserStoreError::TotpStepUsed) => {
+            return Err(AuthApiError::InvalidTwoFaCode)
+        }
+        Err(e) => return Err(AuthApiError::from(e)),
+    };
+
+    if let Some(remaining_codes) = recovery_codes_left {
+        tracing::warn!(remaining_codes, "Logged in with a recovery code");
+        let event = AccountEvent::RecoveryCodeUsed {
+            user_id: user.id,
+            remaining_codes,
+        };
+        if let Err(e) = state.account_event_hook.notify(&event).await {
+            tracing::error!(error = ?e, "Failed to send account event");
+        }
+    }
+
+    let second_method = match second_factor {
+        SecondFactor::RecoveryCode(_) => AuthMethod::RecoveryCode,
+        SecondFactor::Code(_) if user.active_totp_secret().is_some() => AuthMethod::Totp,
+        SecondFactor::Code(_) => AuthMethod::EmailCode,
+    };
+    let amr = [AuthMethod::Password, second_method];
+    let jar = add_session_cookies(&state, &user, Uuid::new_v4(), &amr, jar).await?;
+    Ok((jar, StatusCode::OK))
+}
+
+/// What can be given at `/verify-2fa`: a 2FA code, or a recovery code in
+/// its place.
+enum SecondFactor {
+    Code(TwoFACode),
+    RecoveryCode(RecoveryCode),
+}
+
+impl SecondFactor {
+    fn parse(s: &str) -> Result<Self, TwoFACodeError> {
+        match s.parse() {
+            Ok(code) => Ok(Self::Code(code)),
+            Err(_) => s.parse().map(Self::RecoveryCode),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Verify2FARequest {
+    pub email: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: String,
 }
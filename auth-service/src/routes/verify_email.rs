@@ -0,0 +1,111 @@
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    Json,
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use tracing::Instrument;
+
+use super::token_user_error;
+use crate::{
+    app_state::AppState,
+    domain::{AuthApiError, Email, Token, UserStoreError},
+    utils::{
+        auth::{generate_email_token, validate_email_token},
+        constants::EMAIL_VERIFICATION_AUDIENCE,
+    },
+};
+
+/// Mark the user's email address as verified. This is where the link
+/// emailed at signup leads, so the token comes in the query string.
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Query(request): Query<VerifyEmailRequest>,
+) -> Result<(StatusCode, &'static str), AuthApiError> {
+    let claims = validate_email_token(
+        &request.token,
+        EMAIL_VERIFICATION_AUDIENCE,
+        &state.jwt_keyring.current(),
+    )?;
+    let email: Email = claims.sub.parse().map_err(|_| AuthApiError::InvalidToken)?;
+    state
+        .user_store
+        .write()
+        .await
+        .mark_verified(&email)
+        .await
+        .map_err(token_user_error)?;
+    Ok((StatusCode::OK, "Email verified"))
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: Token,
+}
+
+/// Email a new verification link to the user, if there is one and they
+/// haven't verified their address yet, e.g. because the one sent at signup
+/// never arrived or has expired. As with password resets, the response is
+/// the same either way and the email is sent after responding.
+pub async fn resend_verification_email(
+    State(state): State<AppState>,
+    Json(request): Json<ResendVerificationEmailRequest>,
+) -> Result<impl IntoResponse, AuthApiError> {
+    let email: Email = request.email.parse()?;
+    tokio::spawn(resend(state, email).in_current_span());
+    let response = Json(ResendVerificationEmailResponse {
+        message: "If the email is registered and unverified, a verification link has been sent"
+            .to_string(),
+    });
+    Ok((StatusCode::ACCEPTED, response))
+}
+
+async fn resend(state: AppState, email: Email) {
+    match state.user_store.read().await.get_user(&email).await {
+        Ok(user) if !user.verified => {}
+        Ok(_) | Err(UserStoreError::UserNotFound) => return,
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to look up user for email verification");
+            return;
+        }
+    }
+    if let Err(e) = send_verification_email(&state, &email).await {
+        tracing::error!(error = ?e, "Failed to send verification email");
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ResendVerificationEmailRequest {
+    pub email: String,
+}
+
+#[derive(Serialize)]
+pub struct ResendVerificationEmailResponse {
+    pub message: String,
+}
+
+/// Email `email` a link to `/verify-email`.
+pub(crate) async fn send_verification_email(
+    state: &AppState,
+    email: &Email,
+) -> Result<(), AuthApiError> {
+    let ttl = state.settings.email_verification_token_ttl;
+    let token = generate_email_token(
+        email,
+        EMAIL_VERIFICATION_AUDIENCE,
+        &state.jwt_keyring.current(),
+        ttl,
+    )?;
+    let content = format!(
+        "Follow this link within {} hours to verify your email address: {}/verify-email?token={}",
+        ttl.as_secs().div_ceil(60 * 60),
+        state.settings.public_url,
+        token
+    );
+    state
+        .email_client
+        .send_email(email, "Verify your email address", &content)
+        .await?;
+    Ok(())
+}
@@ -1,9 +1,10 @@
 use crate::{
     app_state::AppState,
-    domain::{AuthApiError, Token},
-    utils::auth::validate_token,
+    domain::{AuthApiError, Token, User, UserId, UserStoreError},
+    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
 };
 use axum::{extract::State, Json};
+use axum_extra::extract::CookieJar;
 use reqwest::StatusCode;
 use serde::Deserialize;
 
@@ -17,10 +18,15 @@ pub async fn verify_token(
         .await
         .is_banned(&request.token)
         .await
+        .map_err(AuthApiError::from)?
     {
         return Err(AuthApiError::InvalidToken);
     }
-    validate_token(&request.token).await?;
+    validate_token(
+        &request.token,
+        &state.jwt_keyring.current(),
+        &state.settings.token_policy,
+    )?;
     Ok(StatusCode::OK)
 }
 
@@ -28,3 +34,46 @@ pub async fn verify_token(
 pub struct VerifyTokenRequest {
     pub token: Token,
 }
+
+/// Check the JWT cookie as `verify_token` checks a token, for routes that
+/// act on the logged-in user. Returns the token and the user it was issued
+/// to.
+pub(crate) async fn authenticate(
+    state: &AppState,
+    jar: &CookieJar,
+) -> Result<(Token, User), AuthApiError> {
+    let cookie = jar.get(JWT_COOKIE_NAME).ok_or(AuthApiError::MissingToken)?;
+    let token = Token::from(cookie.value());
+    if state
+        .banned_token_store
+        .read()
+        .await
+        .is_banned(&token)
+        .await?
+    {
+        return Err(AuthApiError::InvalidToken);
+    }
+    let claims = validate_token(
+        &token,
+        &state.jwt_keyring.current(),
+        &state.settings.token_policy,
+    )?;
+    let id: UserId = claims.sub.parse().map_err(|_| AuthApiError::InvalidToken)?;
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user_by_id(&id)
+        .await
+        .map_err(token_user_error)?;
+    Ok((token, user))
+}
+
+/// The error for when the user a token was issued to can't be looked up:
+/// if they have been deleted since, it is the token that is no good.
+pub(crate) fn token_user_error(error: UserStoreError) -> AuthApiError {
+    match error {
+        UserStoreError::UserNotFound => AuthApiError::InvalidToken,
+        e => AuthApiError::from(e),
+    }
+}
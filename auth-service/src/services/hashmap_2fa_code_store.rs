@@ -1,23 +1,48 @@
-use crate::domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+use crate::{
+    domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+    utils::constants::{MAX_TWO_FA_ATTEMPTS, TWO_FA_CODE_TTL_SECONDS},
+};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::{sync::RwLock, time::Instant};
 
-type TwoFACodeStoreType = Arc<RwLock<HashMap<Email, (LoginAttemptId, TwoFACode)>>>;
+#[derive(Debug, Clone)]
+struct TwoFACodeRecord {
+    login_attempt_id: LoginAttemptId,
+    code: TwoFACode,
+    created_at: Instant,
+    failed_attempts: u32,
+}
+
+type TwoFACodeStoreType = Arc<RwLock<HashMap<Email, TwoFACodeRecord>>>;
 
 #[derive(Debug)]
 pub struct HashMapTwoFACodeStore {
     codes: TwoFACodeStoreType,
+    ttl: Duration,
+    max_attempts: u32,
 }
 
-impl Default for HashMapTwoFACodeStore {
-    fn default() -> Self {
+impl HashMapTwoFACodeStore {
+    pub fn new(ttl: Duration, max_attempts: u32) -> Self {
         Self {
             codes: Arc::new(RwLock::new(HashMap::new())),
+            ttl,
+            max_attempts,
         }
     }
 }
 
+impl Default for HashMapTwoFACodeStore {
+    fn default() -> Self {
+        Self::new(
+            Duration::from_secs(TWO_FA_CODE_TTL_SECONDS),
+            MAX_TWO_FA_ATTEMPTS,
+        )
+    }
+}
+
 #[async_trait::async_trait]
 impl TwoFACodeStore for HashMapTwoFACodeStore {
     async fn add(
@@ -27,7 +52,15 @@ impl TwoFACodeStore for HashMapTwoFACodeStore {
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
         let mut codes = self.codes.write().await;
-        codes.insert(email, (login_attempt_id, code));
+        codes.insert(
+            email,
+            TwoFACodeRecord {
+                login_attempt_id,
+                code,
+                created_at: Instant::now(),
+                failed_attempts: 0,
+            },
+        );
         Ok(())
     }
 
@@ -40,11 +73,35 @@ impl TwoFACodeStore for HashMapTwoFACodeStore {
     }
 
     async fn get(&self, email: &Email) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
+        let mut codes = self.codes.write().await;
+        let record = codes.get(email).ok_or(TwoFACodeStoreError::EmailNotFound)?;
+        if record.created_at.elapsed() >= self.ttl {
+            codes.remove(email);
+            return Err(TwoFACodeStoreError::CodeExpired);
+        }
+        Ok((record.login_attempt_id.clone(), record.code.clone()))
+    }
+
+    async fn record_failed_attempt(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        let mut codes = self.codes.write().await;
+        let record = codes
+            .get_mut(email)
+            .ok_or(TwoFACodeStoreError::EmailNotFound)?;
+        record.failed_attempts += 1;
+        if record.failed_attempts >= self.max_attempts {
+            codes.remove(email);
+            return Err(TwoFACodeStoreError::TooManyAttempts);
+        }
+        Ok(())
+    }
+
+    // expired codes linger until someone tries them, so skip those
+    async fn size(&self) -> Result<usize, TwoFACodeStoreError> {
         let codes = self.codes.read().await;
-        codes
-            .get(email)
-            .cloned()
-            .ok_or(TwoFACodeStoreError::EmailNotFound)
+        Ok(codes
+            .values()
+            .filter(|record| record.created_at.elapsed() < self.ttl)
+            .count())
     }
 }
 
@@ -115,6 +172,85 @@ mod tests {
         assert!(store.remove(&email).await.is_ok());
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn test_get_expired_code_fails() {
+        let email: Email = "test@example.com".parse().expect("valid email");
+        let mut store = HashMapTwoFACodeStore::new(Duration::from_secs(60), 3);
+        store
+            .add(email.clone(), Default::default(), Default::default())
+            .await
+            .unwrap();
+        tokio::time::advance(Duration::from_secs(59)).await;
+        assert!(store.get(&email).await.is_ok());
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(
+            TwoFACodeStoreError::CodeExpired,
+            store
+                .get(&email)
+                .await
+                .expect_err("Code should have expired")
+        );
+        // expired codes are dropped from the store
+        assert_eq!(
+            TwoFACodeStoreError::EmailNotFound,
+            store.get(&email).await.expect_err("Code should be removed")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_code_is_invalidated_after_max_attempts() {
+        let email: Email = "test@example.com".parse().expect("valid email");
+        let mut store = HashMapTwoFACodeStore::new(Duration::from_secs(60), 3);
+        store
+            .add(email.clone(), Default::default(), Default::default())
+            .await
+            .unwrap();
+        assert!(store.record_failed_attempt(&email).await.is_ok());
+        assert!(store.record_failed_attempt(&email).await.is_ok());
+        assert!(store.get(&email).await.is_ok());
+        assert_eq!(
+            TwoFACodeStoreError::TooManyAttempts,
+            store
+                .record_failed_attempt(&email)
+                .await
+                .expect_err("Code should be invalidated")
+        );
+        assert_eq!(
+            TwoFACodeStoreError::EmailNotFound,
+            store.get(&email).await.expect_err("Code should be removed")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_new_code_resets_failed_attempts() {
+        let email: Email = "test@example.com".parse().expect("valid email");
+        let mut store = HashMapTwoFACodeStore::new(Duration::from_secs(60), 2);
+        store
+            .add(email.clone(), Default::default(), Default::default())
+            .await
+            .unwrap();
+        assert!(store.record_failed_attempt(&email).await.is_ok());
+        store
+            .add(email.clone(), Default::default(), Default::default())
+            .await
+            .unwrap();
+        assert!(store.record_failed_attempt(&email).await.is_ok());
+        assert!(store.get(&email).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_record_failed_attempt_by_nonexistent_email_fails() {
+        let email: Email = "nope@example.com".parse().expect("valid email");
+        let mut store = get_test_fixture(vec![]).await;
+        assert_eq!(
+            TwoFACodeStoreError::EmailNotFound,
+            store
+                .record_failed_attempt(&email)
+                .await
+                .expect_err("Test user should not exist in fixture")
+        );
+    }
+
     #[tokio::test]
     async fn test_remove_by_nonexistent_email_fails() {
         let email: Email = "nope@example.com".parse().expect("valid email");
@@ -127,4 +263,30 @@ mod tests {
                 .expect_err("Test user should not exist in fixture")
         );
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_size_skips_expired_codes() {
+        let mut store = HashMapTwoFACodeStore::new(Duration::from_secs(60), 3);
+        assert_eq!(store.size().await.unwrap(), 0);
+        store
+            .add(
+                "first@example.com".parse().unwrap(),
+                Default::default(),
+                Default::default(),
+            )
+            .await
+            .unwrap();
+        tokio::time::advance(Duration::from_secs(30)).await;
+        store
+            .add(
+                "second@example.com".parse().unwrap(),
+                Default::default(),
+                Default::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(store.size().await.unwrap(), 2);
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_eq!(store.size().await.unwrap(), 1);
+    }
 }
@@ -0,0 +1,204 @@
+use crate::domain::{Email, FailedLoginStore, FailedLoginStoreError, LockoutPolicy};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::{sync::RwLock, time::Instant};
+
+#[derive(Debug, Clone)]
+struct FailedLogins {
+    // since the last lockout
+    failed_attempts: u32,
+    lockouts: u32,
+    locked_until: Option<Instant>,
+    last_failed_at: Instant,
+}
+
+#[derive(Debug)]
+struct Records {
+    by_email: HashMap<Email, FailedLogins>,
+    last_pruned: Instant,
+}
+
+type FailedLoginStoreType = Arc<RwLock<Records>>;
+
+#[derive(Debug)]
+pub struct HashMapFailedLoginStore {
+    failed_logins: FailedLoginStoreType,
+    policy: LockoutPolicy,
+}
+
+impl HashMapFailedLoginStore {
+    pub fn new(policy: LockoutPolicy) -> Self {
+        Self {
+            failed_logins: Arc::new(RwLock::new(Records {
+                by_email: HashMap::new(),
+                last_pruned: Instant::now(),
+            })),
+            policy,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl FailedLoginStore for HashMapFailedLoginStore {
+    async fn locked_for(&self, email: &Email) -> Result<Option<Duration>, FailedLoginStoreError> {
+        let failed_logins = self.failed_logins.read().await;
+        let now = Instant::now();
+        Ok(failed_logins
+            .by_email
+            .get(email)
+            .and_then(|record| record.locked_until)
+            .filter(|locked_until| *locked_until > now)
+            .map(|locked_until| locked_until - now))
+    }
+
+    async fn record_failure(
+        &mut self,
+        email: &Email,
+    ) -> Result<Option<Duration>, FailedLoginStoreError> {
+        let mut failed_logins = self.failed_logins.write().await;
+        let now = Instant::now();
+        // Records whose failures would be forgotten anyway, and whose
+        // lockout is over, no longer count towards anything; drop them now
+        // and then so the map doesn't grow with every email ever mistyped.
+        if now.duration_since(failed_logins.last_pruned) >= self.policy.forget_after {
+            let forget_after = self.policy.forget_after;
+            failed_logins.by_email.retain(|_, record| {
+                now.duration_since(record.last_failed_at) < forget_after
+                    || record
+                        .locked_until
+                        .is_some_and(|locked_until| locked_until > now)
+            });
+            failed_logins.last_pruned = now;
+        }
+        let record = failed_logins
+            .by_email
+            .entry(email.clone())
+            .or_insert_with(|| FailedLogins {
+                failed_attempts: 0,
+                lockouts: 0,
+                locked_until: None,
+                last_failed_at: now,
+            });
+        if now.duration_since(record.last_failed_at) >= self.policy.forget_after {
+            record.failed_attempts = 0;
+            record.lockouts = 0;
+        }
+        record.last_failed_at = now;
+        record.failed_attempts += 1;
+        if record.failed_attempts < self.policy.max_failed_attempts {
+            return Ok(None);
+        }
+        record.failed_attempts = 0;
+        record.lockouts += 1;
+        let lockout = self.policy.lockout(record.lockouts);
+        record.locked_until = Some(now + lockout);
+        Ok(Some(lockout))
+    }
+
+    async fn reset(&mut self, email: &Email) -> Result<(), FailedLoginStoreError> {
+        self.failed_logins.write().await.by_email.remove(email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_test_fixture() -> HashMapFailedLoginStore {
+        HashMapFailedLoginStore::new(LockoutPolicy {
+            max_failed_attempts: 3,
+            base_lockout: Duration::from_secs(60),
+            max_lockout: Duration::from_secs(600),
+            forget_after: Duration::from_secs(3600),
+        })
+    }
+
+    fn email() -> Email {
+        "test@example.com".parse().expect("valid email")
+    }
+
+    async fn fail(store: &mut HashMapFailedLoginStore, times: u32) -> Option<Duration> {
+        let mut lockout = None;
+        for _ in 0..times {
+            lockout = store.record_failure(&email()).await.unwrap();
+        }
+        lockout
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_locks_after_max_failed_attempts() {
+        let mut store = get_test_fixture();
+        assert_eq!(fail(&mut store, 2).await, None);
+        assert_eq!(store.locked_for(&email()).await.unwrap(), None);
+        assert_eq!(fail(&mut store, 1).await, Some(Duration::from_secs(60)));
+        tokio::time::advance(Duration::from_secs(20)).await;
+        assert_eq!(
+            store.locked_for(&email()).await.unwrap(),
+            Some(Duration::from_secs(40))
+        );
+        tokio::time::advance(Duration::from_secs(40)).await;
+        assert_eq!(store.locked_for(&email()).await.unwrap(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_lockouts_back_off_exponentially() {
+        let mut store = get_test_fixture();
+        assert_eq!(fail(&mut store, 3).await, Some(Duration::from_secs(60)));
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert_eq!(fail(&mut store, 3).await, Some(Duration::from_secs(120)));
+        tokio::time::advance(Duration::from_secs(120)).await;
+        assert_eq!(fail(&mut store, 3).await, Some(Duration::from_secs(240)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failures_are_forgotten() {
+        let mut store = get_test_fixture();
+        fail(&mut store, 3).await;
+        fail(&mut store, 2).await;
+        tokio::time::advance(Duration::from_secs(3600)).await;
+        // a fresh start: neither the 2 failures nor the lockout count
+        assert_eq!(fail(&mut store, 2).await, None);
+        assert_eq!(fail(&mut store, 1).await, Some(Duration::from_secs(60)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reset_lifts_lockout() {
+        let mut store = get_test_fixture();
+        fail(&mut store, 3).await;
+        store.reset(&email()).await.unwrap();
+        assert_eq!(store.locked_for(&email()).await.unwrap(), None);
+        assert_eq!(fail(&mut store, 2).await, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_forgotten_records_are_pruned() {
+        let mut store = get_test_fixture();
+        fail(&mut store, 1).await;
+        tokio::time::advance(Duration::from_secs(3600)).await;
+        let other: Email = "other@example.com".parse().expect("valid email");
+        store.record_failure(&other).await.unwrap();
+        let failed_logins = store.failed_logins.read().await;
+        assert!(!failed_logins.by_email.contains_key(&email()));
+        assert!(failed_logins.by_email.contains_key(&other));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_active_lockouts_are_not_pruned() {
+        let mut store = HashMapFailedLoginStore::new(LockoutPolicy {
+            max_failed_attempts: 1,
+            base_lockout: Duration::from_secs(7200),
+            max_lockout: Duration::from_secs(7200),
+            forget_after: Duration::from_secs(3600),
+        });
+        fail(&mut store, 1).await;
+        tokio::time::advance(Duration::from_secs(3600)).await;
+        let other: Email = "other@example.com".parse().expect("valid email");
+        store.record_failure(&other).await.unwrap();
+        assert_eq!(
+            store.locked_for(&email()).await.unwrap(),
+            Some(Duration::from_secs(3600))
+        );
+    }
+}
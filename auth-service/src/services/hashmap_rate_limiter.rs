@@ -0,0 +1,153 @@
+use crate::domain::{RateLimit, RateLimitDecision, RateLimiter, RateLimiterError};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::{sync::RwLock, time::Instant};
+
+#[derive(Debug, Clone)]
+struct Window {
+    started_at: Instant,
+    previous: u64,
+    current: u64,
+}
+
+#[derive(Debug)]
+struct Windows {
+    by_key: HashMap<String, Window>,
+    last_pruned: Instant,
+}
+
+/// A rate limiter that counts requests in process memory, so each
+/// replica enforces its limits separately.
+#[derive(Debug, Clone)]
+pub struct HashMapRateLimiter {
+    windows: Arc<RwLock<Windows>>,
+}
+
+impl Default for HashMapRateLimiter {
+    fn default() -> Self {
+        Self {
+            windows: Arc::new(RwLock::new(Windows {
+                by_key: HashMap::new(),
+                last_pruned: Instant::now(),
+            })),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimiter for HashMapRateLimiter {
+    async fn hit(
+        &self,
+        key: &str,
+        limit: RateLimit,
+    ) -> Result<RateLimitDecision, RateLimiterError> {
+        let now = Instant::now();
+        let mut windows = self.windows.write().await;
+        // Clients that have gone quiet for two windows no longer count
+        // towards anything; drop them now and then so the map doesn't grow
+        // with every address that ever made a request.
+        if now.duration_since(windows.last_pruned) >= limit.window {
+            windows
+                .by_key
+                .retain(|_, window| now.duration_since(window.started_at) < limit.window * 2);
+            windows.last_pruned = now;
+        }
+        let window = windows
+            .by_key
+            .entry(key.to_owned())
+            .or_insert_with(|| Window {
+                started_at: now,
+                previous: 0,
+                current: 0,
+            });
+        let elapsed = now.duration_since(window.started_at);
+        if elapsed >= limit.window * 2 {
+            *window = Window {
+                started_at: now,
+                previous: 0,
+                current: 0,
+            };
+        } else if elapsed >= limit.window {
+            window.started_at += limit.window;
+            window.previous = window.current;
+            window.current = 0;
+        }
+        window.current += 1;
+        Ok(limit.decide(
+            window.previous,
+            window.current,
+            now.duration_since(window.started_at),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn limit() -> RateLimit {
+        RateLimit::new(2, Duration::from_secs(60))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_limits_requests_over_max() {
+        let limiter = HashMapRateLimiter::default();
+        assert_eq!(
+            limiter.hit("key", limit()).await.unwrap(),
+            RateLimitDecision::Allowed
+        );
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert_eq!(
+            limiter.hit("key", limit()).await.unwrap(),
+            RateLimitDecision::Allowed
+        );
+        assert_eq!(
+            limiter.hit("key", limit()).await.unwrap(),
+            RateLimitDecision::Limited {
+                retry_after: Duration::from_secs(50 + 40)
+            }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keys_are_limited_separately() {
+        let limiter = HashMapRateLimiter::default();
+        limiter.hit("first", limit()).await.unwrap();
+        limiter.hit("first", limit()).await.unwrap();
+        assert_eq!(
+            limiter.hit("second", limit()).await.unwrap(),
+            RateLimitDecision::Allowed
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_requests_are_allowed_again_after_windows_pass() {
+        let limiter = HashMapRateLimiter::default();
+        for _ in 0..3 {
+            limiter.hit("key", limit()).await.unwrap();
+        }
+        // the previous window's 3 hits still weigh 3/4 of the way in
+        tokio::time::advance(Duration::from_secs(75)).await;
+        assert!(matches!(
+            limiter.hit("key", limit()).await.unwrap(),
+            RateLimitDecision::Limited { .. }
+        ));
+        tokio::time::advance(Duration::from_secs(120)).await;
+        assert_eq!(
+            limiter.hit("key", limit()).await.unwrap(),
+            RateLimitDecision::Allowed
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_quiet_clients_are_pruned() {
+        let limiter = HashMapRateLimiter::default();
+        limiter.hit("quiet", limit()).await.unwrap();
+        tokio::time::advance(Duration::from_secs(120)).await;
+        limiter.hit("busy", limit()).await.unwrap();
+        let windows = limiter.windows.read().await;
+        assert!(!windows.by_key.contains_key("quiet"));
+        assert!(windows.by_key.contains_key("busy"));
+    }
+}
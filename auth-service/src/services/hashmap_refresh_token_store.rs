@@ -0,0 +1,203 @@
+use crate::{
+    domain::{RefreshToken, RefreshTokenRecord, RefreshTokenStore, RefreshTokenStoreError, UserId},
+    utils::constants::REFRESH_TOKEN_TTL_SECONDS,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::{sync::RwLock, time::Instant};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+struct RefreshTokenEntry {
+    record: RefreshTokenRecord,
+    issued_at: Instant,
+    used: bool,
+}
+
+type RefreshTokenStoreType = Arc<RwLock<HashMap<RefreshToken, RefreshTokenEntry>>>;
+
+#[derive(Debug)]
+pub struct HashMapRefreshTokenStore {
+    tokens: RefreshTokenStoreType,
+    ttl: Duration,
+}
+
+impl HashMapRefreshTokenStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            tokens: Arc::new(RwLock::new(HashMap::new())),
+            ttl,
+        }
+    }
+}
+
+impl Default for HashMapRefreshTokenStore {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(REFRESH_TOKEN_TTL_SECONDS))
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for HashMapRefreshTokenStore {
+    async fn add(
+        &mut self,
+        token: RefreshToken,
+        record: RefreshTokenRecord,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let mut tokens = self.tokens.write().await;
+        // used tokens are kept around to detect reuse, so drop the expired
+        // ones here rather than letting them pile up
+        tokens.retain(|_, entry| entry.issued_at.elapsed() < self.ttl);
+        tokens.insert(
+            token,
+            RefreshTokenEntry {
+                record,
+                issued_at: Instant::now(),
+                used: false,
+            },
+        );
+        Ok(())
+    }
+
+    async fn consume(
+        &mut self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError> {
+        let mut tokens = self.tokens.write().await;
+        let entry = tokens
+            .get_mut(token)
+            .ok_or(RefreshTokenStoreError::TokenNotFound)?;
+        if entry.issued_at.elapsed() >= self.ttl {
+            tokens.remove(token);
+            return Err(RefreshTokenStoreError::TokenNotFound);
+        }
+        if entry.used {
+            return Err(RefreshTokenStoreError::TokenReused(entry.record.family_id));
+        }
+        entry.used = true;
+        Ok(entry.record.clone())
+    }
+
+    async fn revoke_family(&mut self, family_id: &Uuid) -> Result<(), RefreshTokenStoreError> {
+        let mut tokens = self.tokens.write().await;
+        tokens.retain(|_, entry| entry.record.family_id != *family_id);
+        Ok(())
+    }
+
+    async fn revoke_user(&mut self, user_id: &UserId) -> Result<(), RefreshTokenStoreError> {
+        let mut tokens = self.tokens.write().await;
+        tokens.retain(|_, entry| entry.record.user_id != *user_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::AuthMethod;
+
+    fn get_record() -> RefreshTokenRecord {
+        RefreshTokenRecord {
+            user_id: UserId::default(),
+            family_id: Uuid::new_v4(),
+            amr: vec![AuthMethod::Password],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_consume_returns_record() {
+        let mut store = HashMapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+        let record = get_record();
+        store.add(token.clone(), record.clone()).await.unwrap();
+        assert_eq!(store.consume(&token).await.unwrap(), record);
+    }
+
+    #[tokio::test]
+    async fn test_consume_unknown_token_fails() {
+        let mut store = HashMapRefreshTokenStore::default();
+        assert_eq!(
+            RefreshTokenStoreError::TokenNotFound,
+            store
+                .consume(&RefreshToken::default())
+                .await
+                .expect_err("Token should not exist in store")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_consume_twice_reports_reuse() {
+        let mut store = HashMapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+        let record = get_record();
+        store.add(token.clone(), record.clone()).await.unwrap();
+        store.consume(&token).await.unwrap();
+        assert_eq!(
+            RefreshTokenStoreError::TokenReused(record.family_id),
+            store
+                .consume(&token)
+                .await
+                .expect_err("Token should already be used")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revoke_family_invalidates_all_its_tokens() {
+        let mut store = HashMapRefreshTokenStore::default();
+        let record = get_record();
+        let first = RefreshToken::default();
+        let second = RefreshToken::default();
+        let other = RefreshToken::default();
+        store.add(first.clone(), record.clone()).await.unwrap();
+        store.add(second.clone(), record.clone()).await.unwrap();
+        store.add(other.clone(), get_record()).await.unwrap();
+        store.consume(&first).await.unwrap();
+
+        store.revoke_family(&record.family_id).await.unwrap();
+        assert!(store.consume(&first).await.is_err());
+        assert!(store.consume(&second).await.is_err());
+        assert!(store.consume(&other).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_revoke_user_invalidates_all_their_families() {
+        let mut store = HashMapRefreshTokenStore::default();
+        let record = get_record();
+        let other_login = RefreshTokenRecord {
+            family_id: Uuid::new_v4(),
+            ..record.clone()
+        };
+        let other_user = RefreshTokenRecord {
+            user_id: UserId::default(),
+            family_id: Uuid::new_v4(),
+            ..record.clone()
+        };
+        let first = RefreshToken::default();
+        let second = RefreshToken::default();
+        let other = RefreshToken::default();
+        store.add(first.clone(), record.clone()).await.unwrap();
+        store.add(second.clone(), other_login).await.unwrap();
+        store.add(other.clone(), other_user).await.unwrap();
+
+        store.revoke_user(&record.user_id).await.unwrap();
+        assert!(store.consume(&first).await.is_err());
+        assert!(store.consume(&second).await.is_err());
+        assert!(store.consume(&other).await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_consume_expired_token_fails() {
+        let mut store = HashMapRefreshTokenStore::new(Duration::from_secs(60));
+        let token = RefreshToken::default();
+        store.add(token.clone(), get_record()).await.unwrap();
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert_eq!(
+            RefreshTokenStoreError::TokenNotFound,
+            store
+                .consume(&token)
+                .await
+                .expect_err("Token should have expired")
+        );
+    }
+}
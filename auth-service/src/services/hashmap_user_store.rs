@@ -1,76 +1,193 @@
-use crate::domain::{Email, Password, User, UserStore, UserStoreError};
-use std::collections::HashMap;
+use crate::domain::{
+    Email, HashedPassword, RecoveryCode, TotpSecret, TwoFAMethod, User, UserId, UserStore,
+    UserStoreError,
+};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-type UserStoreType = Arc<RwLock<HashMap<Email, User>>>;
-
-#[derive(Debug)]
-pub struct HashMapUserStore {
-    users: UserStoreType,
+#[derive(Debug, Default)]
+struct Users {
+    by_id: HashMap<UserId, User>,
+    ids_by_email: HashMap<Email, UserId>,
+    // hashes of each user's recovery codes
+    recovery_codes: HashMap<UserId, HashSet<String>>,
 }
 
-impl Default for HashMapUserStore {
-    fn default() -> Self {
-        Self {
-            users: Arc::new(RwLock::new(HashMap::new())),
-        }
+impl Users {
+    fn get(&self, email: &Email) -> Result<&User, UserStoreError> {
+        self.ids_by_email
+            .get(email)
+            .and_then(|id| self.by_id.get(id))
+            .ok_or(UserStoreError::UserNotFound)
+    }
+
+    fn get_mut(&mut self, email: &Email) -> Result<&mut User, UserStoreError> {
+        self.ids_by_email
+            .get(email)
+            .and_then(|id| self.by_id.get_mut(id))
+            .ok_or(UserStoreError::UserNotFound)
     }
 }
 
+#[derive(Debug, Default)]
+pub struct HashMapUserStore {
+    users: Arc<RwLock<Users>>,
+}
+
 #[async_trait::async_trait]
 impl UserStore for HashMapUserStore {
     async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
         let mut users = self.users.write().await;
-        if users.contains_key(&user.email) {
-            Err(UserStoreError::UserAlreadyExists)
-        } else {
-            users.insert(user.email.clone(), user);
-            Ok(())
+        if users.ids_by_email.contains_key(&user.email) {
+            return Err(UserStoreError::UserAlreadyExists);
         }
+        users.ids_by_email.insert(user.email.clone(), user.id);
+        users.by_id.insert(user.id, user);
+        Ok(())
     }
 
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        self.users.read().await.get(email).cloned()
+    }
+
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
         let users = self.users.read().await;
         users
-            .get(email)
+            .by_id
+            .get(id)
             .cloned()
             .ok_or(UserStoreError::UserNotFound)
     }
 
-    async fn validate_user(
+    async fn update_password(
         &self,
         email: &Email,
-        password: &Password,
-    ) -> Result<User, UserStoreError> {
-        let users = self.users.read().await;
-        if let Some(user) = users.get(email) {
-            if user.password == *password {
-                Ok(user.clone())
-            } else {
-                Err(UserStoreError::IncorrectCredentials)
-            }
-        } else {
-            Err(UserStoreError::UserNotFound)
+        password: HashedPassword,
+    ) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        users.get_mut(email)?.password = password;
+        Ok(())
+    }
+
+    async fn mark_verified(&self, email: &Email) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        users.get_mut(email)?.verified = true;
+        Ok(())
+    }
+
+    async fn set_pending_totp_secret(
+        &self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        users.get_mut(email)?.pending_totp_secret = Some(secret);
+        Ok(())
+    }
+
+    async fn enable_totp(
+        &self,
+        email: &Email,
+        secret: TotpSecret,
+        step: u64,
+    ) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        let user = users.get_mut(email)?;
+        user.totp_secret = Some(secret);
+        user.pending_totp_secret = None;
+        user.totp_last_step = Some(step);
+        user.two_fa_method = TwoFAMethod::Totp;
+        user.requires_2fa = true;
+        Ok(())
+    }
+
+    async fn use_totp_step(&self, email: &Email, step: u64) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        let user = users.get_mut(email)?;
+        if user
+            .totp_last_step
+            .is_some_and(|last_step| step <= last_step)
+        {
+            return Err(UserStoreError::TotpStepUsed);
+        }
+        user.totp_last_step = Some(step);
+        Ok(())
+    }
+
+    async fn set_two_fa_method(
+        &self,
+        email: &Email,
+        method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        let user = users.get_mut(email)?;
+        user.two_fa_method = method;
+        user.requires_2fa = true;
+        Ok(())
+    }
+
+    async fn set_recovery_codes(
+        &self,
+        id: &UserId,
+        codes: &[RecoveryCode],
+    ) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        if !users.by_id.contains_key(id) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        let hashes = codes.iter().map(RecoveryCode::hash).collect();
+        users.recovery_codes.insert(*id, hashes);
+        Ok(())
+    }
+
+    async fn use_recovery_code(
+        &self,
+        id: &UserId,
+        code: &RecoveryCode,
+    ) -> Result<usize, UserStoreError> {
+        let mut users = self.users.write().await;
+        let hashes = users
+            .recovery_codes
+            .get_mut(id)
+            .ok_or(UserStoreError::RecoveryCodeNotFound)?;
+        if !hashes.remove(&code.hash()) {
+            return Err(UserStoreError::RecoveryCodeNotFound);
         }
+        Ok(hashes.len())
     }
 
     async fn delete_user(&self, email: &Email) -> Result<User, UserStoreError> {
         let mut users = self.users.write().await;
-        users.remove(email).ok_or(UserStoreError::UserNotFound)
+        let id = users
+            .ids_by_email
+            .remove(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        users.recovery_codes.remove(&id);
+        users
+            .by_id
+            .remove(&id)
+            .ok_or(UserStoreError::UnexpectedError)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::Password;
+
+    async fn hash(password: &str) -> HashedPassword {
+        HashedPassword::parse(password.parse().expect("valid password"))
+            .await
+            .expect("hashable password")
+    }
 
     async fn get_test_fixture() -> HashMapUserStore {
         let store = HashMapUserStore::default();
         store
             .add_user(User::new(
                 "test@example.com".parse().expect("valid email"),
-                "password123".parse().expect("valid password"),
+                hash("password123").await,
                 false,
             ))
             .await
@@ -78,7 +195,7 @@ mod tests {
         store
             .add_user(User::new(
                 "test2@example.com".parse().expect("valid email"),
-                "password234".parse().expect("valid password"),
+                hash("password234").await,
                 true,
             ))
             .await
@@ -91,7 +208,7 @@ mod tests {
         let store = get_test_fixture().await;
         let new_user = User::new(
             "test3@example.com".parse().expect("valid email"),
-            "password345".parse().expect("valid password"),
+            hash("password345").await,
             false,
         );
         assert!(store.add_user(new_user).await.is_ok());
@@ -102,7 +219,7 @@ mod tests {
         let store = get_test_fixture().await;
         let new_user = User::new(
             "test@example.com".parse().expect("valid email"),
-            "password123".parse().expect("valid password"),
+            hash("password123").await,
             false,
         );
         assert_eq!(
@@ -138,13 +255,28 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_get_user_by_id() {
+        let store = get_test_fixture().await;
+        let email: Email = "test@example.com".parse().expect("valid email");
+        let user = store.get_user(&email).await.unwrap();
+        assert_eq!(store.get_user_by_id(&user.id).await, Ok(user));
+        assert_eq!(
+            UserStoreError::UserNotFound,
+            store
+                .get_user_by_id(&UserId::default())
+                .await
+                .expect_err("Test user should not exist in fixture")
+        );
+    }
+
     #[tokio::test]
     async fn test_validate_unknown_user_fails() {
         let store = get_test_fixture().await;
         let email: Email = "nope@example.com".parse().expect("valid email");
         let password: Password = "password123".parse().expect("valid password");
         assert_eq!(
-            UserStoreError::UserNotFound,
+            UserStoreError::IncorrectCredentials,
             store
                 .validate_user(&email, &password)
                 .await
@@ -174,11 +306,196 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_validate_user_with_unusable_hash_is_unexpected() {
+        let store = HashMapUserStore::default();
+        let email: Email = "test@example.com".parse().expect("valid email");
+        // a well-formed hash, but not one Argon2 can check
+        let hash = HashedPassword::parse_password_hash(
+            "$pbkdf2-sha256$i=1000$c2FsdHNhbHQ$aGFzaGhhc2hoYXNo".to_owned(),
+        )
+        .expect("valid PHC string");
+        store
+            .add_user(User::new(email.clone(), hash, false))
+            .await
+            .unwrap();
+        let password: Password = "password123".parse().expect("valid password");
+        assert_eq!(
+            UserStoreError::UnexpectedError,
+            store
+                .validate_user(&email, &password)
+                .await
+                .expect_err("Hash should not be checkable")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_password_replaces_it() {
+        let store = get_test_fixture().await;
+        let email: Email = "test@example.com".parse().expect("valid email");
+        store
+            .update_password(&email, hash("new_password123").await)
+            .await
+            .expect("Test user should already exist in fixture");
+        let old_password: Password = "password123".parse().expect("valid password");
+        let new_password: Password = "new_password123".parse().expect("valid password");
+        assert!(store.validate_user(&email, &old_password).await.is_err());
+        assert!(store.validate_user(&email, &new_password).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_update_password_of_nonexistent_user_fails() {
+        let store = get_test_fixture().await;
+        let email: Email = "nope@example.com".parse().expect("valid email");
+        assert_eq!(
+            UserStoreError::UserNotFound,
+            store
+                .update_password(&email, hash("new_password123").await)
+                .await
+                .expect_err("Test user should not exist in fixture")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mark_verified() {
+        let store = get_test_fixture().await;
+        let email: Email = "test@example.com".parse().expect("valid email");
+        assert!(!store.get_user(&email).await.unwrap().verified);
+        store
+            .mark_verified(&email)
+            .await
+            .expect("Test user should already exist in fixture");
+        assert!(store.get_user(&email).await.unwrap().verified);
+
+        let email: Email = "nope@example.com".parse().expect("valid email");
+        assert_eq!(
+            UserStoreError::UserNotFound,
+            store
+                .mark_verified(&email)
+                .await
+                .expect_err("Test user should not exist in fixture")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_totp_enrollment() {
+        let store = get_test_fixture().await;
+        let email: Email = "test@example.com".parse().expect("valid email");
+        let secret = TotpSecret::default();
+        store
+            .set_pending_totp_secret(&email, secret.clone())
+            .await
+            .expect("Test user should already exist in fixture");
+        let user = store.get_user(&email).await.unwrap();
+        assert_eq!(user.pending_totp_secret, Some(secret.clone()));
+        assert_eq!(user.totp_secret, None);
+        assert!(!user.requires_2fa);
+
+        store
+            .enable_totp(&email, secret.clone(), 100)
+            .await
+            .unwrap();
+        let user = store.get_user(&email).await.unwrap();
+        assert_eq!(user.totp_secret, Some(secret.clone()));
+        assert_eq!(user.pending_totp_secret, None);
+        assert_eq!(user.totp_last_step, Some(100));
+        assert_eq!(user.two_fa_method, TwoFAMethod::Totp);
+        assert!(user.requires_2fa);
+
+        // switching back to email keeps the secret for later
+        store
+            .set_two_fa_method(&email, TwoFAMethod::Email)
+            .await
+            .unwrap();
+        let user = store.get_user(&email).await.unwrap();
+        assert_eq!(user.two_fa_method, TwoFAMethod::Email);
+        assert_eq!(user.totp_secret, Some(secret));
+
+        let email: Email = "nope@example.com".parse().expect("valid email");
+        assert_eq!(
+            UserStoreError::UserNotFound,
+            store
+                .set_two_fa_method(&email, TwoFAMethod::Totp)
+                .await
+                .expect_err("Test user should not exist in fixture")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_totp_steps_are_used_once() {
+        let store = get_test_fixture().await;
+        let email: Email = "test@example.com".parse().expect("valid email");
+        store.use_totp_step(&email, 100).await.unwrap();
+        assert_eq!(
+            store.use_totp_step(&email, 100).await,
+            Err(UserStoreError::TotpStepUsed)
+        );
+        assert_eq!(
+            store.use_totp_step(&email, 99).await,
+            Err(UserStoreError::TotpStepUsed)
+        );
+        store.use_totp_step(&email, 101).await.unwrap();
+        assert_eq!(
+            store.get_user(&email).await.unwrap().totp_last_step,
+            Some(101)
+        );
+
+        let email: Email = "nope@example.com".parse().expect("valid email");
+        assert_eq!(
+            store.use_totp_step(&email, 100).await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recovery_codes() {
+        let store = get_test_fixture().await;
+        let email: Email = "test@example.com".parse().expect("valid email");
+        let id = store.get_user(&email).await.unwrap().id;
+        let codes = [RecoveryCode::default(), RecoveryCode::default()];
+        store
+            .set_recovery_codes(&id, &codes)
+            .await
+            .expect("Test user should already exist in fixture");
+        assert_eq!(store.use_recovery_code(&id, &codes[0]).await, Ok(1));
+        // each code works once
+        assert_eq!(
+            store.use_recovery_code(&id, &codes[0]).await,
+            Err(UserStoreError::RecoveryCodeNotFound)
+        );
+
+        // replacing the codes invalidates the old ones
+        let new_codes = [RecoveryCode::default()];
+        store.set_recovery_codes(&id, &new_codes).await.unwrap();
+        assert_eq!(
+            store.use_recovery_code(&id, &codes[1]).await,
+            Err(UserStoreError::RecoveryCodeNotFound)
+        );
+        assert_eq!(store.use_recovery_code(&id, &new_codes[0]).await, Ok(0));
+
+        assert_eq!(
+            UserStoreError::UserNotFound,
+            store
+                .set_recovery_codes(&UserId::default(), &codes)
+                .await
+                .expect_err("Test user should not exist in fixture")
+        );
+    }
+
     #[tokio::test]
     async fn test_delete_user_by_existing_email_succeeds() {
         let store = get_test_fixture().await;
         let email: Email = "test@example.com".parse().expect("valid email");
-        assert!(store.delete_user(&email).await.is_ok());
+        let user = store
+            .delete_user(&email)
+            .await
+            .expect("Test user should exist");
+        assert_eq!(
+            store.get_user_by_id(&user.id).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        // the address is free to sign up with again
+        store.add_user(user).await.unwrap();
     }
 
     #[tokio::test]
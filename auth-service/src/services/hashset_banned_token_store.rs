@@ -1,103 +1,301 @@
-use crate::domain::{BannedTokenResult, BannedTokenStore, Token};
-use std::collections::HashSet;
+use crate::{
+    domain::{BannedTokenResult, BannedTokenStore, BannedTokenStoreError, Token, UserId},
+    utils::auth::{read_token_id, read_token_issue, token_expires_at},
+};
+use chrono::Utc;
+use std::collections::{hash_map::Entry, HashMap};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
-type BannedTokenStoreType = Arc<RwLock<HashSet<Token>>>;
+// Each banned token's `jti` is stored with the time it would stop being
+// accepted anyway, its `exp` claim plus the leeway, so that it can be
+// dropped then.
+type BannedTokenStoreType = Arc<RwLock<HashMap<String, i64>>>;
+// Banned users are stored with the time their ban was issued, in
+// milliseconds; their tokens issued before then are banned.
+type BannedUserStoreType = Arc<RwLock<HashMap<String, i64>>>;
 
 #[derive(Debug)]
 pub struct HashSetBannedTokenStore {
     tokens: BannedTokenStoreType,
+    users: BannedUserStoreType,
+    token_ttl: Duration,
+    leeway: Duration,
 }
 
-impl Default for HashSetBannedTokenStore {
-    fn default() -> Self {
+impl HashSetBannedTokenStore {
+    /// A store for access tokens that are valid for `token_ttl`, and are
+    /// accepted for up to `leeway` past their expiry for clock skew; a
+    /// ban has to be remembered for as long.
+    pub fn new(token_ttl: Duration, leeway: Duration) -> Self {
         Self {
-            tokens: Arc::new(RwLock::new(HashSet::new())),
+            tokens: Arc::new(RwLock::new(HashMap::new())),
+            users: Arc::new(RwLock::new(HashMap::new())),
+            token_ttl,
+            leeway,
         }
     }
 }
 
-// Just a convenience, for tests
-
 #[async_trait::async_trait]
 impl BannedTokenStore for HashSetBannedTokenStore {
-    async fn ban(&self, token: Token) -> BannedTokenResult {
+    async fn ban(&self, token: Token) -> Result<BannedTokenResult, BannedTokenStoreError> {
+        let jti = read_token_id(&token).ok_or(BannedTokenStoreError::InvalidToken)?;
         let mut tokens = self.tokens.write().await;
-        if tokens.contains(&token) {
-            BannedTokenResult::TokenAlreadyBanned
-        } else {
-            tokens.insert(token);
-            BannedTokenResult::TokenBanned
+        match tokens.entry(jti) {
+            Entry::Occupied(_) => Ok(BannedTokenResult::TokenAlreadyBanned),
+            Entry::Vacant(entry) => {
+                let leeway = self.leeway.as_secs() as i64;
+                entry.insert(token_expires_at(&token, self.token_ttl) + leeway);
+                Ok(BannedTokenResult::TokenBanned)
+            }
+        }
+    }
+
+    async fn is_banned(&self, token: &Token) -> Result<bool, BannedTokenStoreError> {
+        // a token without an ID can't pass validation anyway
+        let Some(jti) = read_token_id(token) else {
+            return Ok(false);
+        };
+        if self.tokens.read().await.contains_key(&jti) {
+            return Ok(true);
         }
+        let Some((sub, issued_at)) = read_token_issue(token) else {
+            return Ok(false);
+        };
+        let users = self.users.read().await;
+        Ok(users
+            .get(&sub)
+            .is_some_and(|banned_at| issued_at < *banned_at))
     }
 
-    async fn is_banned(&self, token: &Token) -> bool {
-        let tokens = self.tokens.read().await;
-        tokens.contains(token)
+    async fn ban_user(&self, id: &UserId) -> Result<(), BannedTokenStoreError> {
+        let mut users = self.users.write().await;
+        users.insert(id.to_string(), Utc::now().timestamp_millis());
+        Ok(())
     }
 
-    async fn unban(&self, token: &Token) -> BannedTokenResult {
+    async fn unban(&self, token: &Token) -> Result<BannedTokenResult, BannedTokenStoreError> {
+        let Some(jti) = read_token_id(token) else {
+            return Ok(BannedTokenResult::TokenNotBanned);
+        };
         let mut tokens = self.tokens.write().await;
-        if tokens.remove(token) {
-            BannedTokenResult::TokenUnbanned
+        if tokens.remove(&jti).is_some() {
+            Ok(BannedTokenResult::TokenUnbanned)
         } else {
-            BannedTokenResult::TokenNotBanned
+            Ok(BannedTokenResult::TokenNotBanned)
         }
     }
+
+    async fn size(&self) -> Result<usize, BannedTokenStoreError> {
+        Ok(self.tokens.read().await.len())
+    }
+
+    async fn prune_expired(&self) -> Result<usize, BannedTokenStoreError> {
+        let now = Utc::now().timestamp();
+        let mut tokens = self.tokens.write().await;
+        let before = tokens.len();
+        tokens.retain(|_, expires_at| *expires_at > now);
+        // and bans of users whose tokens from before have all expired
+        let now_ms = Utc::now().timestamp_millis();
+        let remember_for = (self.token_ttl + self.leeway).as_millis() as i64;
+        let mut users = self.users.write().await;
+        users.retain(|_, banned_at| *banned_at + remember_for > now_ms);
+        Ok(before - tokens.len())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::AuthMethod;
 
-    // Create a prepopulated banned user store, for convenience in testing
-    impl<S: AsRef<str>> FromIterator<S> for HashSetBannedTokenStore {
-        fn from_iter<T: IntoIterator<Item = S>>(iter: T) -> Self {
-            let tokens = Arc::new(RwLock::new(
-                iter.into_iter()
-                    .map(|item| Token::from(item.as_ref()))
-                    .collect::<HashSet<Token>>(),
-            ));
-            Self { tokens }
-        }
+    use crate::utils::{
+        auth::{generate_auth_token, TokenPolicy},
+        jwt_key::{JwtKey, JwtKeyring},
+    };
+
+    const TOKEN_TTL: Duration = Duration::from_secs(600);
+    const LEEWAY: Duration = Duration::from_secs(60);
+
+    fn get_test_fixture() -> HashSetBannedTokenStore {
+        HashSetBannedTokenStore::new(TOKEN_TTL, LEEWAY)
+    }
+
+    fn generate_token(user_id: &UserId) -> Token {
+        let keyring = JwtKeyring::new(JwtKey::from_secret(b"secret"), vec![]);
+        let policy = TokenPolicy {
+            issuer: "http://localhost:3000".to_owned(),
+            audience: "app-service".to_owned(),
+            leeway: Duration::ZERO,
+        };
+        generate_auth_token(
+            user_id,
+            &[AuthMethod::Password],
+            &keyring,
+            &policy,
+            TOKEN_TTL,
+        )
+        .unwrap()
+    }
+
+    // Create a store with a token banned, for convenience in testing
+    async fn store_with_banned_token() -> (HashSetBannedTokenStore, Token) {
+        let store = get_test_fixture();
+        let token = generate_token(&UserId::default());
+        store.ban(token.clone()).await.unwrap();
+        (store, token)
     }
 
     #[tokio::test]
     async fn test_ban() {
-        let store = HashSetBannedTokenStore::from_iter(vec!["bad", "verybad"]);
-        let token = Token::from("superbad");
+        let (store, _) = store_with_banned_token().await;
+        let token = generate_token(&UserId::default());
         assert_eq!(
-            store.ban(token.clone()).await,
+            store.ban(token.clone()).await.unwrap(),
             BannedTokenResult::TokenBanned
         );
-        assert!(store.is_banned(&token).await);
+        assert!(store.is_banned(&token).await.unwrap());
     }
 
     #[tokio::test]
     async fn test_ban_already_banned() {
-        let store = HashSetBannedTokenStore::from_iter(vec!["bad", "verybad"]);
-        let token = Token::from("bad");
+        let (store, token) = store_with_banned_token().await;
         assert_eq!(
-            store.ban(token.clone()).await,
+            store.ban(token.clone()).await.unwrap(),
             BannedTokenResult::TokenAlreadyBanned
         );
-        assert!(store.is_banned(&token).await);
+        assert!(store.is_banned(&token).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_ban_keys_by_jti() {
+        let (store, token) = store_with_banned_token().await;
+        let jti = read_token_id(&token).unwrap();
+        assert!(store.tokens.read().await.contains_key(&jti));
+        // another token of the same user is a different token
+        let other_token = generate_token(&UserId::default());
+        assert!(!store.is_banned(&other_token).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_ban_token_without_jti_fails() {
+        let store = get_test_fixture();
+        assert_eq!(
+            store.ban(Token::from("not-a-jwt")).await,
+            Err(BannedTokenStoreError::InvalidToken)
+        );
+        assert!(!store.is_banned(&Token::from("not-a-jwt")).await.unwrap());
     }
 
     #[tokio::test]
     async fn test_unban() {
-        let store = HashSetBannedTokenStore::from_iter(vec!["bad", "verybad"]);
-        let token = Token::from("bad");
-        assert_eq!(store.unban(&token).await, BannedTokenResult::TokenUnbanned);
-        assert!(!store.is_banned(&token).await);
+        let (store, token) = store_with_banned_token().await;
+        assert_eq!(
+            store.unban(&token).await.unwrap(),
+            BannedTokenResult::TokenUnbanned
+        );
+        assert!(!store.is_banned(&token).await.unwrap());
     }
 
     #[tokio::test]
     async fn test_unban_not_banned() {
-        let store = HashSetBannedTokenStore::from_iter(vec!["bad", "verybad"]);
-        let token = Token::from("superbad");
-        assert_eq!(store.unban(&token).await, BannedTokenResult::TokenNotBanned);
-        assert!(!store.is_banned(&token).await);
+        let (store, _) = store_with_banned_token().await;
+        let token = generate_token(&UserId::default());
+        assert_eq!(
+            store.unban(&token).await.unwrap(),
+            BannedTokenResult::TokenNotBanned
+        );
+        assert!(!store.is_banned(&token).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_ban_remembers_token_expiry() {
+        let (store, token) = store_with_banned_token().await;
+        let tokens = store.tokens.read().await;
+        assert_eq!(
+            tokens.get(&read_token_id(&token).unwrap()).copied(),
+            crate::utils::auth::read_token_expiry(&token).map(|exp| exp as i64 + 60)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ban_outlasts_expiry_by_leeway() {
+        let store = get_test_fixture();
+        let policy = TokenPolicy {
+            issuer: "http://localhost:3000".to_owned(),
+            audience: "app-service".to_owned(),
+            leeway: LEEWAY,
+        };
+        // expired as of now, but still accepted for the leeway
+        let token = generate_auth_token(
+            &UserId::default(),
+            &[AuthMethod::Password],
+            &JwtKeyring::new(JwtKey::from_secret(b"secret"), vec![]),
+            &policy,
+            Duration::ZERO,
+        )
+        .unwrap();
+        store.ban(token.clone()).await.unwrap();
+        assert_eq!(store.prune_expired().await.unwrap(), 0);
+        assert!(store.is_banned(&token).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_ban_user_bans_their_earlier_tokens() {
+        let store = get_test_fixture();
+        let user_id = UserId::default();
+        let token = generate_token(&user_id);
+        let other_token = generate_token(&UserId::default());
+
+        // as if the user was banned a millisecond after the token was issued
+        let (_, issued_at) = read_token_issue(&token).unwrap();
+        store
+            .users
+            .write()
+            .await
+            .insert(user_id.to_string(), issued_at + 1);
+        assert!(store.is_banned(&token).await.unwrap());
+        assert!(!store.is_banned(&other_token).await.unwrap());
+
+        // tokens issued since are not banned
+        store.ban_user(&user_id).await.unwrap();
+        let token = generate_token(&user_id);
+        assert!(!store.is_banned(&token).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_prune_expired_forgets_old_user_bans() {
+        let store = get_test_fixture();
+        let now = Utc::now().timestamp_millis();
+        let mut users = store.users.write().await;
+        users.insert("old@example.com".to_owned(), now - 661_000);
+        // only just past the token lifetime, and so within the leeway
+        users.insert("recent@example.com".to_owned(), now - 601_000);
+        users.insert("new@example.com".to_owned(), now);
+        drop(users);
+        store.prune_expired().await.unwrap();
+        let users = store.users.read().await;
+        assert!(!users.contains_key("old@example.com"));
+        assert!(users.contains_key("recent@example.com"));
+        assert!(users.contains_key("new@example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_prune_expired() {
+        let (store, token) = store_with_banned_token().await;
+        let now = Utc::now().timestamp();
+        store
+            .tokens
+            .write()
+            .await
+            .insert("expired".to_owned(), now - 1);
+        assert_eq!(store.size().await.unwrap(), 2);
+
+        assert_eq!(store.prune_expired().await.unwrap(), 1);
+        assert_eq!(store.size().await.unwrap(), 1);
+        assert!(!store.tokens.read().await.contains_key("expired"));
+        assert!(store.is_banned(&token).await.unwrap());
     }
 }
@@ -0,0 +1,16 @@
+use crate::domain::{AccountEvent, AccountEventHook, AccountEventHookError};
+
+/// Logs account events and sends them nowhere else. The default, for when
+/// nothing downstream needs to hear about them.
+#[derive(Debug, Default)]
+pub struct LogAccountEventHook;
+
+#[async_trait::async_trait]
+impl AccountEventHook for LogAccountEventHook {
+    async fn notify(&self, event: &AccountEvent) -> Result<(), AccountEventHookError> {
+        let event = serde_json::to_string(event)
+            .map_err(|e| AccountEventHookError::DeliveryFailed(e.to_string()))?;
+        tracing::info!(%event, "Account event");
+        Ok(())
+    }
+}
@@ -0,0 +1,24 @@
+use crate::domain::{AccountEvent, AccountEventHook, AccountEventHookError};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Records account events in memory, so tests can inspect what would
+/// have been sent.
+#[derive(Debug, Default)]
+pub struct MockAccountEventHook {
+    events: Arc<RwLock<Vec<AccountEvent>>>,
+}
+
+impl MockAccountEventHook {
+    pub async fn events(&self) -> Vec<AccountEvent> {
+        self.events.read().await.clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl AccountEventHook for MockAccountEventHook {
+    async fn notify(&self, event: &AccountEvent) -> Result<(), AccountEventHookError> {
+        self.events.write().await.push(event.clone());
+        Ok(())
+    }
+}
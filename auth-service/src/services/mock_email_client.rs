@@ -0,0 +1,114 @@
+use crate::domain::{Email, EmailClient, EmailClientError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+/// An email that was "sent" through a [`MockEmailClient`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentEmail {
+    pub recipient: Email,
+    pub subject: String,
+    pub content: String,
+}
+
+/// Records outgoing email in memory instead of delivering it,
+/// so tests can inspect what would have been sent.
+#[derive(Debug, Default)]
+pub struct MockEmailClient {
+    sent: Arc<RwLock<Vec<SentEmail>>>,
+    fail: AtomicBool,
+    delay: Duration,
+}
+
+impl MockEmailClient {
+    /// A client whose every delivery attempt fails.
+    pub fn failing() -> Self {
+        Self {
+            fail: AtomicBool::new(true),
+            ..Default::default()
+        }
+    }
+
+    /// Make deliveries fail from now on, or work again, like a relay that
+    /// goes down and comes back.
+    pub fn set_failing(&self, fail: bool) {
+        self.fail.store(fail, Ordering::Relaxed);
+    }
+
+    /// A client that takes `delay` over every delivery, like a slow relay.
+    pub fn slow(delay: Duration) -> Self {
+        Self {
+            delay,
+            ..Default::default()
+        }
+    }
+
+    pub async fn sent_emails(&self) -> Vec<SentEmail> {
+        self.sent.read().await.clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for MockEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str,
+    ) -> Result<(), EmailClientError> {
+        tokio::time::sleep(self.delay).await;
+        if self.fail.load(Ordering::Relaxed) {
+            return Err(EmailClientError::DeliveryFailed(
+                "mock delivery failure".to_owned(),
+            ));
+        }
+        self.sent.write().await.push(SentEmail {
+            recipient: recipient.clone(),
+            subject: subject.to_owned(),
+            content: content.to_owned(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_email_records_message() {
+        let client = MockEmailClient::default();
+        let recipient: Email = "test@example.com".parse().expect("valid email");
+        client
+            .send_email(&recipient, "Subject", "Content")
+            .await
+            .expect("Failed to send email");
+        assert_eq!(
+            client.sent_emails().await,
+            vec![SentEmail {
+                recipient,
+                subject: "Subject".to_owned(),
+                content: "Content".to_owned(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failing_client_records_nothing() {
+        let client = MockEmailClient::failing();
+        let recipient: Email = "test@example.com".parse().expect("valid email");
+        assert!(client
+            .send_email(&recipient, "Subject", "Content")
+            .await
+            .is_err());
+        assert!(client.sent_emails().await.is_empty());
+
+        client.set_failing(false);
+        assert!(client
+            .send_email(&recipient, "Subject", "Content")
+            .await
+            .is_ok());
+        assert_eq!(client.sent_emails().await.len(), 1);
+    }
+}
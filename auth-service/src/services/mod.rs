@@ -1,8 +1,55 @@
 mod hashmap_2fa_code_store;
 pub use hashmap_2fa_code_store::HashMapTwoFACodeStore;
 
+mod hashmap_failed_login_store;
+pub use hashmap_failed_login_store::HashMapFailedLoginStore;
+
+mod hashmap_rate_limiter;
+pub use hashmap_rate_limiter::HashMapRateLimiter;
+
+mod hashmap_refresh_token_store;
+pub use hashmap_refresh_token_store::HashMapRefreshTokenStore;
+
 mod hashmap_user_store;
 pub use hashmap_user_store::HashMapUserStore;
 
 mod hashset_banned_token_store;
 pub use hashset_banned_token_store::HashSetBannedTokenStore;
+
+mod log_account_event_hook;
+pub use log_account_event_hook::LogAccountEventHook;
+
+mod mock_account_event_hook;
+pub use mock_account_event_hook::MockAccountEventHook;
+
+mod mock_email_client;
+pub use mock_email_client::{MockEmailClient, SentEmail};
+
+mod redis_2fa_code_store;
+pub use redis_2fa_code_store::RedisTwoFACodeStore;
+
+mod redis_banned_token_store;
+pub use redis_banned_token_store::RedisBannedTokenStore;
+
+mod redis_failed_login_store;
+pub use redis_failed_login_store::RedisFailedLoginStore;
+
+mod redis_keys;
+
+mod redis_rate_limiter;
+pub use redis_rate_limiter::RedisRateLimiter;
+
+mod redis_refresh_token_store;
+pub use redis_refresh_token_store::RedisRefreshTokenStore;
+
+mod sql_user_store;
+pub use sql_user_store::SqlUserStore;
+
+mod smtp_email_client;
+pub use smtp_email_client::SmtpEmailClient;
+
+mod stdout_email_client;
+pub use stdout_email_client::StdoutEmailClient;
+
+mod webhook_account_event_hook;
+pub use webhook_account_event_hook::WebhookAccountEventHook;
@@ -0,0 +1,267 @@
+use super::redis_keys::count_keys;
+use crate::domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const TWO_FA_CODE_KEY_PREFIX: &str = "two_fa_code:";
+const TWO_FA_ATTEMPTS_KEY_PREFIX: &str = "two_fa_attempts:";
+
+/// A 2FA code store shared between replicas through Redis. Codes
+/// expire automatically after the configured TTL, and are removed
+/// after `max_attempts` wrong guesses.
+#[derive(Clone)]
+pub struct RedisTwoFACodeStore {
+    conn: ConnectionManager,
+    ttl: Duration,
+    max_attempts: u32,
+}
+
+impl RedisTwoFACodeStore {
+    pub fn new(conn: ConnectionManager, ttl: Duration, max_attempts: u32) -> Self {
+        Self {
+            conn,
+            ttl,
+            max_attempts,
+        }
+    }
+}
+
+impl std::fmt::Debug for RedisTwoFACodeStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisTwoFACodeStore")
+            .field("ttl", &self.ttl)
+            .field("max_attempts", &self.max_attempts)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct TwoFATuple(pub String, pub String);
+
+fn get_key(email: &Email) -> String {
+    format!("{}{}", TWO_FA_CODE_KEY_PREFIX, email.as_ref())
+}
+
+fn get_attempts_key(email: &Email) -> String {
+    format!("{}{}", TWO_FA_ATTEMPTS_KEY_PREFIX, email.as_ref())
+}
+
+impl From<redis::RedisError> for TwoFACodeStoreError {
+    fn from(_error: redis::RedisError) -> Self {
+        TwoFACodeStoreError::UnexpectedError
+    }
+}
+
+#[async_trait::async_trait]
+impl TwoFACodeStore for RedisTwoFACodeStore {
+    async fn add(
+        &mut self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let value = serde_json::to_string(&TwoFATuple(
+            login_attempt_id.as_ref().to_owned(),
+            code.as_ref().to_owned(),
+        ))
+        .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        // a new code starts with a clean slate of attempts
+        let _: () = redis::pipe()
+            .atomic()
+            .set_ex(get_key(&email), value, self.ttl.as_secs().max(1))
+            .del(get_attempts_key(&email))
+            .query_async(&mut self.conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        let (removed, _): (u64, u64) = redis::pipe()
+            .atomic()
+            .del(get_key(email))
+            .del(get_attempts_key(email))
+            .query_async(&mut self.conn)
+            .await?;
+        if removed > 0 {
+            Ok(())
+        } else {
+            Err(TwoFACodeStoreError::EmailNotFound)
+        }
+    }
+
+    async fn get(&self, email: &Email) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
+        let value: Option<String> = self.conn.clone().get(get_key(email)).await?;
+        let TwoFATuple(login_attempt_id, code) =
+            serde_json::from_str(&value.ok_or(TwoFACodeStoreError::EmailNotFound)?)
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        Ok((
+            login_attempt_id
+                .parse()
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?,
+            code.parse()
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?,
+        ))
+    }
+
+    async fn record_failed_attempt(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        let exists: bool = self.conn.exists(get_key(email)).await?;
+        if !exists {
+            return Err(TwoFACodeStoreError::EmailNotFound);
+        }
+        // the counter never needs to outlive the code it belongs to
+        let (failed_attempts, _): (u32, bool) = redis::pipe()
+            .atomic()
+            .incr(get_attempts_key(email), 1)
+            .expire(get_attempts_key(email), self.ttl.as_secs().max(1) as i64)
+            .query_async(&mut self.conn)
+            .await?;
+        if failed_attempts >= self.max_attempts {
+            self.remove(email).await?;
+            return Err(TwoFACodeStoreError::TooManyAttempts);
+        }
+        Ok(())
+    }
+
+    async fn size(&self) -> Result<usize, TwoFACodeStoreError> {
+        Ok(count_keys(&mut self.conn.clone(), TWO_FA_CODE_KEY_PREFIX).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // These tests need a running redis-server, e.g. `docker run -p 6379:6379 redis`.
+    // Run them with `cargo test -- --ignored`.
+    async fn get_test_fixture(ttl: Duration) -> RedisTwoFACodeStore {
+        let url = std::env::var("REDIS_URL").unwrap_or("redis://127.0.0.1:6379".to_owned());
+        let client = redis::Client::open(url).expect("Invalid Redis URL");
+        let conn = ConnectionManager::new(client)
+            .await
+            .expect("Failed to connect to Redis");
+        RedisTwoFACodeStore::new(conn, ttl, 3)
+    }
+
+    fn get_random_email() -> Email {
+        format!("{}@example.com", uuid::Uuid::new_v4())
+            .parse()
+            .expect("valid email")
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_get_code_by_existing_email_succeeds() {
+        let mut store = get_test_fixture(Duration::from_secs(60)).await;
+        let email = get_random_email();
+        let attempt_id = LoginAttemptId::default();
+        let code: TwoFACode = "123456".parse().expect("valid 2FA code");
+        store
+            .add(email.clone(), attempt_id.clone(), code.clone())
+            .await
+            .unwrap();
+        let record = store
+            .get(&email)
+            .await
+            .expect("Failed to find existing code in store");
+        assert_eq!(record.0, attempt_id);
+        assert_eq!(record.1, code);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_get_by_nonexistent_email_fails() {
+        let store = get_test_fixture(Duration::from_secs(60)).await;
+        assert_eq!(
+            TwoFACodeStoreError::EmailNotFound,
+            store
+                .get(&get_random_email())
+                .await
+                .expect_err("Email should not exist in store")
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_remove_by_existing_email_succeeds() {
+        let mut store = get_test_fixture(Duration::from_secs(60)).await;
+        let email = get_random_email();
+        store
+            .add(email.clone(), Default::default(), Default::default())
+            .await
+            .unwrap();
+        assert!(store.remove(&email).await.is_ok());
+        assert!(store.get(&email).await.is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_remove_by_nonexistent_email_fails() {
+        let mut store = get_test_fixture(Duration::from_secs(60)).await;
+        assert_eq!(
+            TwoFACodeStoreError::EmailNotFound,
+            store
+                .remove(&get_random_email())
+                .await
+                .expect_err("Email should not exist in store")
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_code_is_invalidated_after_max_attempts() {
+        let mut store = get_test_fixture(Duration::from_secs(60)).await;
+        let email = get_random_email();
+        store
+            .add(email.clone(), Default::default(), Default::default())
+            .await
+            .unwrap();
+        assert!(store.record_failed_attempt(&email).await.is_ok());
+        assert!(store.record_failed_attempt(&email).await.is_ok());
+        assert_eq!(
+            TwoFACodeStoreError::TooManyAttempts,
+            store
+                .record_failed_attempt(&email)
+                .await
+                .expect_err("Code should be invalidated")
+        );
+        assert_eq!(
+            TwoFACodeStoreError::EmailNotFound,
+            store.get(&email).await.expect_err("Code should be removed")
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_code_expires_after_ttl() {
+        let mut store = get_test_fixture(Duration::from_secs(1)).await;
+        let email = get_random_email();
+        store
+            .add(email.clone(), Default::default(), Default::default())
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(
+            TwoFACodeStoreError::EmailNotFound,
+            store
+                .get(&email)
+                .await
+                .expect_err("Code should have expired")
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_size_counts_pending_codes() {
+        let mut store = get_test_fixture(Duration::from_secs(60)).await;
+        let before = store.size().await.unwrap();
+        for _ in 0..2 {
+            store
+                .add(get_random_email(), Default::default(), Default::default())
+                .await
+                .unwrap();
+        }
+        // other tests may be adding codes concurrently
+        assert!(store.size().await.unwrap() >= before + 2);
+    }
+}
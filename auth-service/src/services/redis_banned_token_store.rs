@@ -0,0 +1,285 @@
+use super::redis_keys::count_keys;
+use crate::{
+    domain::{BannedTokenResult, BannedTokenStore, BannedTokenStoreError, Token, UserId},
+    utils::auth::{read_token_id, read_token_issue, token_expires_at},
+};
+use chrono::Utc;
+use redis::{aio::ConnectionManager, AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use std::time::Duration;
+
+const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
+const BANNED_USER_KEY_PREFIX: &str = "banned_user:";
+
+/// A banned token store shared between replicas through Redis.
+/// Each entry expires as soon as the token itself would stop being
+/// accepted, leeway included, so the store never holds tokens that are
+/// no longer valid anyway. Banned users likewise expire once all their
+/// earlier tokens have.
+#[derive(Clone)]
+pub struct RedisBannedTokenStore {
+    conn: ConnectionManager,
+    token_ttl: Duration,
+    leeway: Duration,
+}
+
+impl RedisBannedTokenStore {
+    pub fn new(conn: ConnectionManager, token_ttl: Duration, leeway: Duration) -> Self {
+        Self {
+            conn,
+            token_ttl,
+            leeway,
+        }
+    }
+}
+
+impl std::fmt::Debug for RedisBannedTokenStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisBannedTokenStore")
+            .field("token_ttl", &self.token_ttl)
+            .field("leeway", &self.leeway)
+            .finish_non_exhaustive()
+    }
+}
+
+fn get_key(jti: &str) -> String {
+    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, jti)
+}
+
+fn get_user_key(sub: &str) -> String {
+    format!("{}{}", BANNED_USER_KEY_PREFIX, sub)
+}
+
+// Until `token` stops being accepted, `leeway` past its expiry
+fn seconds_until_expiry(token: &Token, token_ttl: Duration, leeway: Duration) -> u64 {
+    let accepted_until = token_expires_at(token, token_ttl) + leeway.as_secs() as i64;
+    // Redis rejects an expiry of zero
+    (accepted_until - Utc::now().timestamp()).max(1) as u64
+}
+
+impl From<redis::RedisError> for BannedTokenStoreError {
+    fn from(_error: redis::RedisError) -> Self {
+        BannedTokenStoreError::UnexpectedError
+    }
+}
+
+#[async_trait::async_trait]
+impl BannedTokenStore for RedisBannedTokenStore {
+    async fn ban(&self, token: Token) -> Result<BannedTokenResult, BannedTokenStoreError> {
+        let jti = read_token_id(&token).ok_or(BannedTokenStoreError::InvalidToken)?;
+        let options = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(seconds_until_expiry(
+                &token,
+                self.token_ttl,
+                self.leeway,
+            )));
+        let newly_set: bool = self
+            .conn
+            .clone()
+            .set_options(get_key(&jti), true, options)
+            .await?;
+        Ok(if newly_set {
+            BannedTokenResult::TokenBanned
+        } else {
+            BannedTokenResult::TokenAlreadyBanned
+        })
+    }
+
+    async fn is_banned(&self, token: &Token) -> Result<bool, BannedTokenStoreError> {
+        // a token without an ID can't pass validation anyway
+        let Some(jti) = read_token_id(token) else {
+            return Ok(false);
+        };
+        let mut conn = self.conn.clone();
+        if conn.exists(get_key(&jti)).await? {
+            return Ok(true);
+        }
+        let Some((sub, issued_at)) = read_token_issue(token) else {
+            return Ok(false);
+        };
+        let banned_at: Option<i64> = conn.get(get_user_key(&sub)).await?;
+        Ok(banned_at.is_some_and(|banned_at| issued_at < banned_at))
+    }
+
+    async fn ban_user(&self, id: &UserId) -> Result<(), BannedTokenStoreError> {
+        let _: () = self
+            .conn
+            .clone()
+            .set_ex(
+                get_user_key(&id.to_string()),
+                Utc::now().timestamp_millis(),
+                (self.token_ttl + self.leeway).as_secs().max(1),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn unban(&self, token: &Token) -> Result<BannedTokenResult, BannedTokenStoreError> {
+        let Some(jti) = read_token_id(token) else {
+            return Ok(BannedTokenResult::TokenNotBanned);
+        };
+        let removed: u64 = self.conn.clone().del(get_key(&jti)).await?;
+        Ok(if removed > 0 {
+            BannedTokenResult::TokenUnbanned
+        } else {
+            BannedTokenResult::TokenNotBanned
+        })
+    }
+
+    // Redis expires entries by itself, so there is nothing to prune
+    async fn size(&self) -> Result<usize, BannedTokenStoreError> {
+        Ok(count_keys(&mut self.conn.clone(), BANNED_TOKEN_KEY_PREFIX).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::AuthMethod;
+    use crate::utils::{
+        auth::{generate_auth_token, TokenPolicy},
+        jwt_key::{JwtKey, JwtKeyring},
+    };
+    const TOKEN_TTL_SECONDS: u64 = 600;
+    const LEEWAY_SECONDS: u64 = 60;
+
+    // These tests need a running redis-server, e.g. `docker run -p 6379:6379 redis`.
+    // Run them with `cargo test -- --ignored`.
+    async fn get_test_fixture() -> RedisBannedTokenStore {
+        let url = std::env::var("REDIS_URL").unwrap_or("redis://127.0.0.1:6379".to_owned());
+        let client = redis::Client::open(url).expect("Invalid Redis URL");
+        let conn = ConnectionManager::new(client)
+            .await
+            .expect("Failed to connect to Redis");
+        RedisBannedTokenStore::new(
+            conn,
+            Duration::from_secs(TOKEN_TTL_SECONDS),
+            Duration::from_secs(LEEWAY_SECONDS),
+        )
+    }
+
+    fn get_random_token() -> Token {
+        let keyring = JwtKeyring::new(JwtKey::from_secret(b"secret"), vec![]);
+        let policy = TokenPolicy {
+            issuer: "http://localhost:3000".to_owned(),
+            audience: "app-service".to_owned(),
+            leeway: Duration::ZERO,
+        };
+        generate_auth_token(
+            &UserId::default(),
+            &[AuthMethod::Password],
+            &keyring,
+            &policy,
+            Duration::from_secs(TOKEN_TTL_SECONDS),
+        )
+        .expect("Failed to generate token")
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_ban() {
+        let store = get_test_fixture().await;
+        let token = get_random_token();
+        assert_eq!(
+            store.ban(token.clone()).await.unwrap(),
+            BannedTokenResult::TokenBanned
+        );
+        assert!(store.is_banned(&token).await.unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_ban_already_banned() {
+        let store = get_test_fixture().await;
+        let token = get_random_token();
+        store.ban(token.clone()).await.unwrap();
+        assert_eq!(
+            store.ban(token.clone()).await.unwrap(),
+            BannedTokenResult::TokenAlreadyBanned
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_ban_expires_with_token() {
+        let store = get_test_fixture().await;
+        let token = get_random_token();
+        store.ban(token.clone()).await.unwrap();
+        let jti = read_token_id(&token).unwrap();
+        let ttl: i64 = store.conn.clone().ttl(get_key(&jti)).await.unwrap();
+        assert!(
+            ttl > TOKEN_TTL_SECONDS as i64 && ttl <= (TOKEN_TTL_SECONDS + LEEWAY_SECONDS) as i64
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_unban() {
+        let store = get_test_fixture().await;
+        let token = get_random_token();
+        store.ban(token.clone()).await.unwrap();
+        assert_eq!(
+            store.unban(&token).await.unwrap(),
+            BannedTokenResult::TokenUnbanned
+        );
+        assert!(!store.is_banned(&token).await.unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_unban_not_banned() {
+        let store = get_test_fixture().await;
+        let token = get_random_token();
+        assert_eq!(
+            store.unban(&token).await.unwrap(),
+            BannedTokenResult::TokenNotBanned
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_size_counts_banned_tokens() {
+        let store = get_test_fixture().await;
+        let before = store.size().await.unwrap();
+        store.ban(get_random_token()).await.unwrap();
+        store.ban(get_random_token()).await.unwrap();
+        // other tests may be banning tokens concurrently
+        assert!(store.size().await.unwrap() >= before + 2);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_ban_user_bans_their_earlier_tokens() {
+        let store = get_test_fixture().await;
+        let token = get_random_token();
+        let other_token = get_random_token();
+        let (sub, issued_at) = read_token_issue(&token).unwrap();
+        // as if the user was banned a millisecond after the token was issued
+        let _: () = store
+            .conn
+            .clone()
+            .set_ex(get_user_key(&sub), issued_at + 1, 60)
+            .await
+            .unwrap();
+        assert!(store.is_banned(&token).await.unwrap());
+        assert!(!store.is_banned(&other_token).await.unwrap());
+
+        store.ban_user(&sub.parse().unwrap()).await.unwrap();
+        let ttl: i64 = store.conn.clone().ttl(get_user_key(&sub)).await.unwrap();
+        assert!(
+            ttl > TOKEN_TTL_SECONDS as i64 && ttl <= (TOKEN_TTL_SECONDS + LEEWAY_SECONDS) as i64
+        );
+    }
+
+    #[test]
+    fn test_seconds_until_expiry_matches_token() {
+        let seconds = seconds_until_expiry(
+            &get_random_token(),
+            Duration::from_secs(TOKEN_TTL_SECONDS),
+            Duration::from_secs(LEEWAY_SECONDS),
+        );
+        // the token is still accepted for the leeway past its expiry
+        assert!(seconds > TOKEN_TTL_SECONDS + LEEWAY_SECONDS - 5);
+        assert!(seconds <= TOKEN_TTL_SECONDS + LEEWAY_SECONDS);
+    }
+}
@@ -0,0 +1,180 @@
+use crate::domain::{Email, FailedLoginStore, FailedLoginStoreError, LockoutPolicy};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use std::time::Duration;
+
+const FAILED_LOGINS_KEY_PREFIX: &str = "failed_logins:";
+const LOCKOUTS_KEY_PREFIX: &str = "lockouts:";
+const LOCKED_KEY_PREFIX: &str = "locked:";
+
+/// A failed login store shared between replicas through Redis. A lockout
+/// is a key that expires when the lockout ends; the counters behind it
+/// expire once `forget_after` has passed without a failed login.
+#[derive(Clone)]
+pub struct RedisFailedLoginStore {
+    conn: ConnectionManager,
+    policy: LockoutPolicy,
+}
+
+impl RedisFailedLoginStore {
+    pub fn new(conn: ConnectionManager, policy: LockoutPolicy) -> Self {
+        Self { conn, policy }
+    }
+}
+
+impl std::fmt::Debug for RedisFailedLoginStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisFailedLoginStore")
+            .field("policy", &self.policy)
+            .finish_non_exhaustive()
+    }
+}
+
+fn get_key(prefix: &str, email: &Email) -> String {
+    format!("{}{}", prefix, email.as_ref())
+}
+
+impl From<redis::RedisError> for FailedLoginStoreError {
+    fn from(_error: redis::RedisError) -> Self {
+        FailedLoginStoreError::UnexpectedError
+    }
+}
+
+#[async_trait::async_trait]
+impl FailedLoginStore for RedisFailedLoginStore {
+    async fn locked_for(&self, email: &Email) -> Result<Option<Duration>, FailedLoginStoreError> {
+        // -2 if the key doesn't exist
+        let ttl: i64 = self
+            .conn
+            .clone()
+            .ttl(get_key(LOCKED_KEY_PREFIX, email))
+            .await?;
+        Ok((ttl > 0).then(|| Duration::from_secs(ttl as u64)))
+    }
+
+    async fn record_failure(
+        &mut self,
+        email: &Email,
+    ) -> Result<Option<Duration>, FailedLoginStoreError> {
+        let failed_logins_key = get_key(FAILED_LOGINS_KEY_PREFIX, email);
+        let lockouts_key = get_key(LOCKOUTS_KEY_PREFIX, email);
+        let forget_after = self.policy.forget_after.as_secs().max(1) as i64;
+        let (failed_attempts, _, _): (u32, bool, bool) = redis::pipe()
+            .atomic()
+            .incr(&failed_logins_key, 1)
+            .expire(&failed_logins_key, forget_after)
+            .expire(&lockouts_key, forget_after)
+            .query_async(&mut self.conn)
+            .await?;
+        // Only the failure that reaches the limit locks the account, even
+        // if others come in before the counter is cleared
+        if failed_attempts != self.policy.max_failed_attempts {
+            return Ok(None);
+        }
+        let (_, lockouts, _): (u64, u32, bool) = redis::pipe()
+            .atomic()
+            .del(&failed_logins_key)
+            .incr(&lockouts_key, 1)
+            .expire(&lockouts_key, forget_after)
+            .query_async(&mut self.conn)
+            .await?;
+        let lockout = self.policy.lockout(lockouts);
+        let _: () = self
+            .conn
+            .set_ex(
+                get_key(LOCKED_KEY_PREFIX, email),
+                lockouts,
+                lockout.as_secs().max(1),
+            )
+            .await?;
+        Ok(Some(lockout))
+    }
+
+    async fn reset(&mut self, email: &Email) -> Result<(), FailedLoginStoreError> {
+        let _: u64 = self
+            .conn
+            .del(&[
+                get_key(FAILED_LOGINS_KEY_PREFIX, email),
+                get_key(LOCKOUTS_KEY_PREFIX, email),
+                get_key(LOCKED_KEY_PREFIX, email),
+            ])
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // These tests need a running redis-server, e.g. `docker run -p 6379:6379 redis`.
+    // Run them with `cargo test -- --ignored`.
+    async fn get_test_fixture(base_lockout: Duration) -> RedisFailedLoginStore {
+        let url = std::env::var("REDIS_URL").unwrap_or("redis://127.0.0.1:6379".to_owned());
+        let client = redis::Client::open(url).expect("Invalid Redis URL");
+        let conn = ConnectionManager::new(client)
+            .await
+            .expect("Failed to connect to Redis");
+        RedisFailedLoginStore::new(
+            conn,
+            LockoutPolicy {
+                max_failed_attempts: 3,
+                base_lockout,
+                max_lockout: Duration::from_secs(600),
+                forget_after: Duration::from_secs(3600),
+            },
+        )
+    }
+
+    fn get_random_email() -> Email {
+        format!("{}@example.com", uuid::Uuid::new_v4())
+            .parse()
+            .expect("valid email")
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_locks_after_max_failed_attempts() {
+        let mut store = get_test_fixture(Duration::from_secs(60)).await;
+        let email = get_random_email();
+        assert_eq!(store.record_failure(&email).await.unwrap(), None);
+        assert_eq!(store.record_failure(&email).await.unwrap(), None);
+        assert_eq!(store.locked_for(&email).await.unwrap(), None);
+        assert_eq!(
+            store.record_failure(&email).await.unwrap(),
+            Some(Duration::from_secs(60))
+        );
+        let locked_for = store.locked_for(&email).await.unwrap().expect("locked");
+        assert!(locked_for > Duration::from_secs(55));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_lockouts_back_off_exponentially() {
+        let mut store = get_test_fixture(Duration::from_secs(1)).await;
+        let email = get_random_email();
+        for _ in 0..3 {
+            store.record_failure(&email).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(store.locked_for(&email).await.unwrap(), None);
+        store.record_failure(&email).await.unwrap();
+        store.record_failure(&email).await.unwrap();
+        assert_eq!(
+            store.record_failure(&email).await.unwrap(),
+            Some(Duration::from_secs(2))
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_reset_lifts_lockout() {
+        let mut store = get_test_fixture(Duration::from_secs(60)).await;
+        let email = get_random_email();
+        for _ in 0..3 {
+            store.record_failure(&email).await.unwrap();
+        }
+        store.reset(&email).await.unwrap();
+        assert_eq!(store.locked_for(&email).await.unwrap(), None);
+        assert_eq!(store.record_failure(&email).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,25 @@
+use redis::{aio::ConnectionManager, RedisResult};
+
+/// Count the keys starting with `prefix`. Redis can't do this directly,
+/// so it means walking the keyspace with SCAN, which unlike KEYS doesn't
+/// block the server while it does so.
+pub(crate) async fn count_keys(conn: &mut ConnectionManager, prefix: &str) -> RedisResult<usize> {
+    let pattern = format!("{}*", prefix);
+    let mut cursor: u64 = 0;
+    let mut count = 0;
+    loop {
+        let (next_cursor, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(&pattern)
+            .arg("COUNT")
+            .arg(1000)
+            .query_async(conn)
+            .await?;
+        count += keys.len();
+        if next_cursor == 0 {
+            return Ok(count);
+        }
+        cursor = next_cursor;
+    }
+}
@@ -0,0 +1,121 @@
+use crate::domain::{RateLimit, RateLimitDecision, RateLimiter, RateLimiterError};
+use redis::aio::ConnectionManager;
+use std::time::Duration;
+
+const RATE_LIMIT_KEY_PREFIX: &str = "rate_limit:";
+
+/// A rate limiter that counts requests in Redis, so that limits hold
+/// across replicas. Windows are aligned to the Unix epoch, so every
+/// replica agrees on which one a request falls in; each window's counter
+/// expires once it can no longer affect a decision.
+#[derive(Clone)]
+pub struct RedisRateLimiter {
+    conn: ConnectionManager,
+}
+
+impl RedisRateLimiter {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+impl std::fmt::Debug for RedisRateLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisRateLimiter").finish_non_exhaustive()
+    }
+}
+
+fn get_key(key: &str, window_index: u64) -> String {
+    format!("{}{}:{}", RATE_LIMIT_KEY_PREFIX, key, window_index)
+}
+
+impl From<redis::RedisError> for RateLimiterError {
+    fn from(_error: redis::RedisError) -> Self {
+        RateLimiterError::UnexpectedError
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimiter for RedisRateLimiter {
+    async fn hit(
+        &self,
+        key: &str,
+        limit: RateLimit,
+    ) -> Result<RateLimitDecision, RateLimiterError> {
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        let window_millis = (limit.window.as_millis() as u64).max(1);
+        let window_index = now / window_millis;
+        let (current, _, previous): (u64, bool, Option<u64>) = redis::pipe()
+            .atomic()
+            .incr(get_key(key, window_index), 1)
+            .expire(
+                get_key(key, window_index),
+                (limit.window.as_secs() * 2).max(1) as i64,
+            )
+            .get(get_key(key, window_index.saturating_sub(1)))
+            .query_async(&mut self.conn.clone())
+            .await?;
+        Ok(limit.decide(
+            previous.unwrap_or(0),
+            current,
+            Duration::from_millis(now % window_millis),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // These tests need a running redis-server, e.g. `docker run -p 6379:6379 redis`.
+    // Run them with `cargo test -- --ignored`.
+    async fn get_test_fixture() -> RedisRateLimiter {
+        let url = std::env::var("REDIS_URL").unwrap_or("redis://127.0.0.1:6379".to_owned());
+        let client = redis::Client::open(url).expect("Invalid Redis URL");
+        let conn = ConnectionManager::new(client)
+            .await
+            .expect("Failed to connect to Redis");
+        RedisRateLimiter::new(conn)
+    }
+
+    fn get_random_key() -> String {
+        format!("ip:{}", uuid::Uuid::new_v4())
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_limits_requests_over_max() {
+        let limiter = get_test_fixture().await;
+        // a long window, so the test can't straddle two of them
+        let limit = RateLimit::new(2, Duration::from_secs(24 * 60 * 60));
+        let key = get_random_key();
+        for _ in 0..2 {
+            assert_eq!(
+                limiter.hit(&key, limit).await.unwrap(),
+                RateLimitDecision::Allowed
+            );
+        }
+        assert!(matches!(
+            limiter.hit(&key, limit).await.unwrap(),
+            RateLimitDecision::Limited { retry_after } if retry_after > Duration::ZERO
+        ));
+        assert_eq!(
+            limiter.hit(&get_random_key(), limit).await.unwrap(),
+            RateLimitDecision::Allowed
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_counters_expire() {
+        let limiter = get_test_fixture().await;
+        let limit = RateLimit::new(1, Duration::from_secs(1));
+        let key = get_random_key();
+        limiter.hit(&key, limit).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        assert_eq!(
+            limiter.hit(&key, limit).await.unwrap(),
+            RateLimitDecision::Allowed
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use crate::domain::{
+    AuthMethod, RefreshToken, RefreshTokenRecord, RefreshTokenStore, RefreshTokenStoreError, UserId,
+};
+use redis::{aio::ConnectionManager, AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use uuid::Uuid;
+
+const REFRESH_TOKEN_KEY_PREFIX: &str = "refresh_token:";
+const USED_REFRESH_TOKEN_KEY_PREFIX: &str = "refresh_token_used:";
+const REVOKED_FAMILY_KEY_PREFIX: &str = "refresh_token_revoked_family:";
+const USER_FAMILIES_KEY_PREFIX: &str = "refresh_token_user_families:";
+
+/// A refresh token store shared between replicas through Redis. Rather
+/// than tracking every member of a family, revoking a family leaves a
+/// marker behind that outlives any token it could have issued. Each
+/// user's families are kept in a set, so that they can all be revoked.
+#[derive(Clone)]
+pub struct RedisRefreshTokenStore {
+    conn: ConnectionManager,
+    ttl: Duration,
+}
+
+impl RedisRefreshTokenStore {
+    pub fn new(conn: ConnectionManager, ttl: Duration) -> Self {
+        Self { conn, ttl }
+    }
+
+    fn ttl_seconds(&self) -> u64 {
+        self.ttl.as_secs().max(1)
+    }
+}
+
+impl std::fmt::Debug for RedisRefreshTokenStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisRefreshTokenStore")
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+// (user ID, family ID, amr). Records from before `amr` was kept have no
+// third element.
+#[derive(Serialize, Deserialize)]
+struct RefreshTokenTuple(
+    pub String,
+    pub String,
+    #[serde(default)] pub Vec<AuthMethod>,
+);
+
+fn get_key(token: &RefreshToken) -> String {
+    format!("{}{}", REFRESH_TOKEN_KEY_PREFIX, token.as_ref())
+}
+
+fn get_used_key(token: &RefreshToken) -> String {
+    format!("{}{}", USED_REFRESH_TOKEN_KEY_PREFIX, token.as_ref())
+}
+
+fn get_revoked_family_key(family_id: &Uuid) -> String {
+    format!("{}{}", REVOKED_FAMILY_KEY_PREFIX, family_id)
+}
+
+fn get_user_families_key(user_id: &UserId) -> String {
+    format!("{}{}", USER_FAMILIES_KEY_PREFIX, user_id)
+}
+
+impl From<redis::RedisError> for RefreshTokenStoreError {
+    fn from(_error: redis::RedisError) -> Self {
+        RefreshTokenStoreError::UnexpectedError
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for RedisRefreshTokenStore {
+    async fn add(
+        &mut self,
+        token: RefreshToken,
+        record: RefreshTokenRecord,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let value = serde_json::to_string(&RefreshTokenTuple(
+            record.user_id.to_string(),
+            record.family_id.to_string(),
+            record.amr.clone(),
+        ))
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+        let ttl = self.ttl_seconds();
+        let families_key = get_user_families_key(&record.user_id);
+        // the set lives as long as the newest token, and so outlives the rest
+        let _: () = redis::pipe()
+            .atomic()
+            .set_ex(get_key(&token), value, ttl)
+            .sadd(&families_key, record.family_id.to_string())
+            .expire(&families_key, ttl as i64)
+            .query_async(&mut self.conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn consume(
+        &mut self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError> {
+        let value: Option<String> = self.conn.get(get_key(token)).await?;
+        let RefreshTokenTuple(user_id, family_id, amr) =
+            serde_json::from_str(&value.ok_or(RefreshTokenStoreError::TokenNotFound)?)
+                .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+        let record = RefreshTokenRecord {
+            // records from before user IDs name an email address instead;
+            // their users log in again
+            user_id: user_id
+                .parse()
+                .map_err(|_| RefreshTokenStoreError::TokenNotFound)?,
+            family_id: family_id
+                .parse()
+                .map_err(|_| RefreshTokenStoreError::UnexpectedError)?,
+            amr,
+        };
+        let revoked: bool = self
+            .conn
+            .exists(get_revoked_family_key(&record.family_id))
+            .await?;
+        if revoked {
+            return Err(RefreshTokenStoreError::TokenNotFound);
+        }
+        // only the first caller to set the marker gets to use the token
+        let options = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(self.ttl_seconds()));
+        let first_use: bool = self
+            .conn
+            .set_options(get_used_key(token), true, options)
+            .await?;
+        if !first_use {
+            return Err(RefreshTokenStoreError::TokenReused(record.family_id));
+        }
+        Ok(record)
+    }
+
+    async fn revoke_family(&mut self, family_id: &Uuid) -> Result<(), RefreshTokenStoreError> {
+        let ttl = self.ttl_seconds();
+        let _: () = self
+            .conn
+            .set_ex(get_revoked_family_key(family_id), true, ttl)
+            .await?;
+        Ok(())
+    }
+
+    async fn revoke_user(&mut self, user_id: &UserId) -> Result<(), RefreshTokenStoreError> {
+        let families_key = get_user_families_key(user_id);
+        let family_ids: Vec<String> = self.conn.smembers(&families_key).await?;
+        let ttl = self.ttl_seconds();
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for family_id in family_ids.iter().filter_map(|id| id.parse::<Uuid>().ok()) {
+            pipe.set_ex(get_revoked_family_key(&family_id), true, ttl);
+        }
+        pipe.del(&families_key);
+        let _: () = pipe.query_async(&mut self.conn).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // These tests need a running redis-server, e.g. `docker run -p 6379:6379 redis`.
+    // Run them with `cargo test -- --ignored`.
+    async fn get_test_fixture(ttl: Duration) -> RedisRefreshTokenStore {
+        let url = std::env::var("REDIS_URL").unwrap_or("redis://127.0.0.1:6379".to_owned());
+        let client = redis::Client::open(url).expect("Invalid Redis URL");
+        let conn = ConnectionManager::new(client)
+            .await
+            .expect("Failed to connect to Redis");
+        RedisRefreshTokenStore::new(conn, ttl)
+    }
+
+    fn get_record() -> RefreshTokenRecord {
+        RefreshTokenRecord {
+            user_id: UserId::default(),
+            family_id: Uuid::new_v4(),
+            amr: vec![AuthMethod::Password, AuthMethod::Totp],
+        }
+    }
+
+    #[test]
+    fn test_records_without_amr_still_parse() {
+        let RefreshTokenTuple(_, _, amr) =
+            serde_json::from_str(r#"["test@example.com", "family"]"#).unwrap();
+        assert!(amr.is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_consume_returns_record() {
+        let mut store = get_test_fixture(Duration::from_secs(60)).await;
+        let token = RefreshToken::default();
+        let record = get_record();
+        store.add(token.clone(), record.clone()).await.unwrap();
+        assert_eq!(store.consume(&token).await.unwrap(), record);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_consume_unknown_token_fails() {
+        let mut store = get_test_fixture(Duration::from_secs(60)).await;
+        assert_eq!(
+            RefreshTokenStoreError::TokenNotFound,
+            store
+                .consume(&RefreshToken::default())
+                .await
+                .expect_err("Token should not exist in store")
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_consume_twice_reports_reuse() {
+        let mut store = get_test_fixture(Duration::from_secs(60)).await;
+        let token = RefreshToken::default();
+        let record = get_record();
+        store.add(token.clone(), record.clone()).await.unwrap();
+        store.consume(&token).await.unwrap();
+        assert_eq!(
+            RefreshTokenStoreError::TokenReused(record.family_id),
+            store
+                .consume(&token)
+                .await
+                .expect_err("Token should already be used")
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_revoke_family_invalidates_all_its_tokens() {
+        let mut store = get_test_fixture(Duration::from_secs(60)).await;
+        let record = get_record();
+        let first = RefreshToken::default();
+        let second = RefreshToken::default();
+        let other = RefreshToken::default();
+        store.add(first.clone(), record.clone()).await.unwrap();
+        store.add(second.clone(), record.clone()).await.unwrap();
+        store.add(other.clone(), get_record()).await.unwrap();
+        store.consume(&first).await.unwrap();
+
+        store.revoke_family(&record.family_id).await.unwrap();
+        assert!(store.consume(&first).await.is_err());
+        assert!(store.consume(&second).await.is_err());
+        assert!(store.consume(&other).await.is_ok());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_revoke_user_invalidates_all_their_families() {
+        let mut store = get_test_fixture(Duration::from_secs(60)).await;
+        let record = get_record();
+        let other_login = RefreshTokenRecord {
+            family_id: Uuid::new_v4(),
+            ..record.clone()
+        };
+        let first = RefreshToken::default();
+        let second = RefreshToken::default();
+        let other = RefreshToken::default();
+        store.add(first.clone(), record.clone()).await.unwrap();
+        store.add(second.clone(), other_login).await.unwrap();
+        store.add(other.clone(), get_record()).await.unwrap();
+
+        store.revoke_user(&record.user_id).await.unwrap();
+        assert!(store.consume(&first).await.is_err());
+        assert!(store.consume(&second).await.is_err());
+        assert!(store.consume(&other).await.is_ok());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a local redis-server"]
+    async fn test_consume_expired_token_fails() {
+        let mut store = get_test_fixture(Duration::from_secs(1)).await;
+        let token = RefreshToken::default();
+        store.add(token.clone(), get_record()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(
+            RefreshTokenStoreError::TokenNotFound,
+            store
+                .consume(&token)
+                .await
+                .expect_err("Token should have expired")
+        );
+    }
+}
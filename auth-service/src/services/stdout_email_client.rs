@@ -0,0 +1,24 @@
+use crate::domain::{Email, EmailClient, EmailClientError};
+
+/// Prints outgoing email to stdout. Useful for local development,
+/// where there is no mail server to deliver to.
+#[derive(Debug, Default)]
+pub struct StdoutEmailClient;
+
+#[async_trait::async_trait]
+impl EmailClient for StdoutEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str,
+    ) -> Result<(), EmailClientError> {
+        println!(
+            "Sending email to {}\nSubject: {}\n\n{}",
+            recipient.as_ref(),
+            subject,
+            content
+        );
+        Ok(())
+    }
+}
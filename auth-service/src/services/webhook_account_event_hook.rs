@@ -0,0 +1,194 @@
+use crate::domain::{AccountEvent, AccountEventHook, AccountEventHookError};
+use crate::utils::constants::{
+    ACCOUNT_EVENT_SIGNATURE_HEADER, ACCOUNT_EVENT_WEBHOOK_ATTEMPTS,
+    ACCOUNT_EVENT_WEBHOOK_RETRY_SECONDS, ACCOUNT_EVENT_WEBHOOK_TIMEOUT_SECONDS,
+};
+use aws_lc_rs::hmac;
+use reqwest::header::CONTENT_TYPE;
+use std::fmt;
+use std::time::Duration;
+use tracing::Instrument;
+
+/// Posts account events as JSON to a webhook. Each body is signed with
+/// the shared secret (see `ACCOUNT_EVENT_SIGNATURE_HEADER`) so the
+/// receiver can tell it came from here.
+///
+/// Delivery happens in the background so a slow receiver doesn't hold up
+/// the request the event came from; failed attempts are retried with
+/// backoff, and an event that still can't be delivered is logged.
+#[derive(Clone)]
+pub struct WebhookAccountEventHook {
+    client: reqwest::Client,
+    url: String,
+    key: hmac::Key,
+    retry_delay: Duration,
+}
+
+impl WebhookAccountEventHook {
+    pub fn new(url: &str, secret: &str) -> Result<Self, AccountEventHookError> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(ACCOUNT_EVENT_WEBHOOK_TIMEOUT_SECONDS))
+            .build()
+            .map_err(|e| AccountEventHookError::DeliveryFailed(e.to_string()))?;
+        Ok(Self {
+            client,
+            url: url.to_owned(),
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()),
+            retry_delay: Duration::from_secs(ACCOUNT_EVENT_WEBHOOK_RETRY_SECONDS),
+        })
+    }
+
+    fn sign(&self, body: &[u8]) -> String {
+        let tag = hmac::sign(&self.key, body);
+        let hex: String = tag.as_ref().iter().map(|b| format!("{b:02x}")).collect();
+        format!("sha256={hex}")
+    }
+
+    async fn deliver(self, body: Vec<u8>) {
+        let signature = self.sign(&body);
+        let mut delay = self.retry_delay;
+        for attempt in 1..=ACCOUNT_EVENT_WEBHOOK_ATTEMPTS {
+            let result = self
+                .client
+                .post(&self.url)
+                .header(CONTENT_TYPE, "application/json")
+                .header(ACCOUNT_EVENT_SIGNATURE_HEADER, &signature)
+                .body(body.clone())
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match result {
+                Ok(_) => return,
+                Err(e) if attempt < ACCOUNT_EVENT_WEBHOOK_ATTEMPTS => {
+                    tracing::warn!(error = %e, attempt, "Failed to post account event, retrying");
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, attempt, "Failed to post account event, giving up");
+                }
+            }
+        }
+    }
+}
+
+// Hand-written so the signing key stays out of logs.
+impl fmt::Debug for WebhookAccountEventHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebhookAccountEventHook")
+            .field("url", &self.url)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait::async_trait]
+impl AccountEventHook for WebhookAccountEventHook {
+    async fn notify(&self, event: &AccountEvent) -> Result<(), AccountEventHookError> {
+        let body = serde_json::to_vec(event)
+            .map_err(|e| AccountEventHookError::DeliveryFailed(e.to_string()))?;
+        tokio::spawn(self.clone().deliver(body).in_current_span());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::UserId;
+    use axum::{body::Bytes, extract::State, http::HeaderMap, http::StatusCode, Router};
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct Receiver {
+        requests: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+    }
+
+    /// Starts a webhook receiver that refuses the first `failures` requests,
+    /// returning its URL and everything it is sent.
+    async fn receiver(failures: usize) -> (String, Receiver) {
+        let receiver = Receiver::default();
+        let app = Router::new()
+            .route(
+                "/hook",
+                axum::routing::post(
+                    move |State(receiver): State<Receiver>, headers: HeaderMap, body: Bytes| async move {
+                        let mut requests = receiver.requests.lock().await;
+                        requests.push((headers, body));
+                        if requests.len() <= failures {
+                            StatusCode::INTERNAL_SERVER_ERROR
+                        } else {
+                            StatusCode::NO_CONTENT
+                        }
+                    },
+                ),
+            )
+            .with_state(receiver.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{addr}/hook"), receiver)
+    }
+
+    async fn wait_for_requests(receiver: &Receiver, count: usize) -> Vec<(HeaderMap, Bytes)> {
+        for _ in 0..100 {
+            let requests = receiver.requests.lock().await;
+            if requests.len() >= count {
+                return requests.clone();
+            }
+            drop(requests);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("webhook was not called {count} times");
+    }
+
+    fn hook(url: &str) -> WebhookAccountEventHook {
+        WebhookAccountEventHook {
+            retry_delay: Duration::ZERO,
+            ..WebhookAccountEventHook::new(url, "shared-secret").unwrap()
+        }
+    }
+
+    fn event() -> AccountEvent {
+        AccountEvent::Deleted {
+            user_id: UserId::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_posts_signed_event() {
+        let (url, receiver) = receiver(0).await;
+        let event = event();
+        hook(&url).notify(&event).await.unwrap();
+
+        let requests = wait_for_requests(&receiver, 1).await;
+        let (headers, body) = &requests[0];
+        assert_eq!(body.as_ref(), serde_json::to_vec(&event).unwrap());
+        assert_eq!(headers[CONTENT_TYPE], "application/json");
+
+        let signature = headers[ACCOUNT_EVENT_SIGNATURE_HEADER].to_str().unwrap();
+        let tag = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, b"shared-secret"), body);
+        let hex: String = tag.as_ref().iter().map(|b| format!("{b:02x}")).collect();
+        assert_eq!(signature, format!("sha256={hex}"));
+    }
+
+    #[tokio::test]
+    async fn test_retries_failed_delivery() {
+        let (url, receiver) = receiver(1).await;
+        hook(&url).notify(&event()).await.unwrap();
+
+        let requests = wait_for_requests(&receiver, 2).await;
+        assert_eq!(requests[0].1, requests[1].1);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_last_attempt() {
+        let (url, receiver) = receiver(usize::MAX).await;
+        hook(&url).notify(&event()).await.unwrap();
+
+        let attempts = ACCOUNT_EVENT_WEBHOOK_ATTEMPTS as usize;
+        wait_for_requests(&receiver, attempts).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(receiver.requests.lock().await.len(), attempts);
+    }
+}
@@ -0,0 +1,986 @@
+use crate::{
+    domain::{Email, LockoutPolicy, RateLimit},
+    utils::auth::TokenPolicy,
+    utils::constants::{
+        BANNED_TOKEN_SWEEP_INTERVAL_SECONDS, EMAIL_VERIFICATION_AUDIENCE,
+        EMAIL_VERIFICATION_TOKEN_TTL_SECONDS, FAILED_LOGIN_MEMORY_SECONDS, JWT_LEEWAY_SECONDS,
+        LOCKOUT_SECONDS, MAX_FAILED_LOGINS, MAX_LOCKOUT_SECONDS, MAX_TWO_FA_ATTEMPTS,
+        PASSWORD_RESET_AUDIENCE, PASSWORD_RESET_TOKEN_TTL_SECONDS, RATE_LIMIT_PER_EMAIL,
+        RATE_LIMIT_PER_IP, RATE_LIMIT_WINDOW_SECONDS, REFRESH_TOKEN_TTL_SECONDS,
+        STORE_METRICS_INTERVAL_SECONDS, TWO_FA_CODE_TTL_SECONDS,
+    },
+};
+use axum::http::HeaderValue;
+use clap::{Parser, ValueEnum};
+use jsonwebtoken::Algorithm;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    ffi::OsString,
+    fmt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const DEFAULT_LISTEN_ADDRESS: &str = "0.0.0.0:3000";
+const DEFAULT_ALLOWED_ORIGIN: &str = "http://localhost";
+const DEFAULT_ASSETS_DIR: &str = "assets";
+// Only ever read through `Settings::token_ttl`, so that nothing works to a
+// different access token lifetime than the one configured
+const DEFAULT_TOKEN_TTL_SECONDS: u64 = 600;
+const DEFAULT_PUBLIC_URL: &str = "http://localhost:3000";
+const DEFAULT_TOTP_ISSUER: &str = "Auth Service";
+const DEFAULT_JWT_AUDIENCE: &str = "app-service";
+
+/// Everything that can differ between deployments, checked once at startup.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub listen_address: String,
+    pub allowed_origins: Vec<HeaderValue>,
+    pub assets_dir: PathBuf,
+    /// Where users reach this service, for links in the email it sends.
+    /// Has no trailing slash.
+    pub public_url: String,
+    /// The name authenticator apps list this service's codes under.
+    pub totp_issuer: String,
+    pub database_url: Option<String>,
+    pub redis_url: Option<String>,
+    pub smtp: Option<SmtpSettings>,
+    /// Where account events are posted; without it they are only logged.
+    pub account_event_webhook: Option<WebhookSettings>,
+    pub jwt_key: JwtKeySource,
+    pub token_ttl: Duration,
+    /// The `iss` and `aud` of access tokens, and the clock skew allowed
+    /// when checking them.
+    pub token_policy: TokenPolicy,
+    pub refresh_token_ttl: Duration,
+    pub two_fa_code_ttl: Duration,
+    pub max_two_fa_attempts: u32,
+    pub password_reset_token_ttl: Duration,
+    pub email_verification_token_ttl: Duration,
+    /// Refuse logins until the user has verified their email address.
+    pub require_email_verification: bool,
+    pub banned_token_sweep_interval: Duration,
+    /// How often the store sizes `/metrics` reports are counted, rather
+    /// than on every scrape.
+    pub store_metrics_interval: Duration,
+    pub log_format: LogFormat,
+    /// Applies to signup, login, 2FA and password reset requests from each client IP.
+    pub ip_rate_limit: RateLimit,
+    /// Applies to signup, login, 2FA and password reset requests for each email address.
+    pub email_rate_limit: RateLimit,
+    pub lockout: LockoutPolicy,
+    /// Unlocks the admin routes; without it they are always refused.
+    pub admin_token: Option<BearerToken>,
+    /// Lets other services ask `/introspect` about tokens; without it the
+    /// route is always refused.
+    pub introspection_token: Option<BearerToken>,
+    /// Lets Prometheus scrape `/metrics`; without it the route is always
+    /// refused.
+    pub metrics_token: Option<BearerToken>,
+}
+
+/// How log lines are written to stdout.
+#[derive(Debug, Default, Clone, Copy, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpSettings {
+    pub url: String,
+    pub sender: Email,
+}
+
+#[derive(Clone)]
+pub struct WebhookSettings {
+    pub url: String,
+    /// Signs each body, so the receiver can tell it came from here.
+    pub secret: String,
+}
+
+// Keep the secret out of logs
+impl fmt::Debug for WebhookSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebhookSettings")
+            .field("url", &self.url)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Where the keys that tokens are signed with come from.
+#[derive(Clone)]
+pub enum JwtKeySource {
+    /// A JSON keyring file; see `JwtKeyring::from_file`.
+    KeyringFile(PathBuf),
+    /// A PEM-encoded private key.
+    PrivateKeyFile { path: PathBuf, algorithm: Algorithm },
+    /// A shared HS256 secret.
+    Secret(String),
+}
+
+// Keep the secret out of logs
+impl fmt::Debug for JwtKeySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JwtKeySource::KeyringFile(path) => f.debug_tuple("KeyringFile").field(path).finish(),
+            JwtKeySource::PrivateKeyFile { path, algorithm } => f
+                .debug_struct("PrivateKeyFile")
+                .field("path", path)
+                .field("algorithm", algorithm)
+                .finish(),
+            JwtKeySource::Secret(_) => f.write_str("Secret(..)"),
+        }
+    }
+}
+
+/// A secret that callers of a route present as `Authorization: Bearer`.
+#[derive(Clone)]
+pub struct BearerToken(String);
+
+impl BearerToken {
+    /// Check `candidate` against the token. Digests are compared rather
+    /// than the tokens themselves, so that how long the comparison takes
+    /// says nothing about how much of the token was guessed right.
+    pub fn matches(&self, candidate: &str) -> bool {
+        Sha256::digest(self.0.as_bytes()) == Sha256::digest(candidate.as_bytes())
+    }
+}
+
+// Keep the token out of logs
+impl fmt::Debug for BearerToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BearerToken(..)")
+    }
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    InvalidArguments(clap::Error),
+    ReadFailed(PathBuf, std::io::Error),
+    InvalidFile(PathBuf, toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::InvalidArguments(e) => write!(f, "{}", e),
+            SettingsError::ReadFailed(path, e) => {
+                write!(f, "failed to read config file {}: {}", path.display(), e)
+            }
+            SettingsError::InvalidFile(path, e) => {
+                write!(f, "invalid config file {}: {}", path.display(), e)
+            }
+            SettingsError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl SettingsError {
+    /// Print the error and exit, the way clap does for bad arguments
+    /// (which includes `--help`, so that exits successfully).
+    pub fn exit(&self) -> ! {
+        match self {
+            SettingsError::InvalidArguments(e) => e.exit(),
+            other => {
+                eprintln!("error: {}", other);
+                std::process::exit(2)
+            }
+        }
+    }
+}
+
+/// Settings as given on the command line, in the environment or in the
+/// config file. Everything is optional so that the sources can be layered.
+/// TOML keys are the field names, e.g. `token_ttl_seconds = 300`.
+#[derive(Debug, Default, Parser, Deserialize)]
+#[command(name = "auth-service", about = "Authentication service")]
+#[serde(default, deny_unknown_fields)]
+struct RawSettings {
+    /// TOML file to read settings from; flags and environment variables override it
+    #[arg(long, env = "AUTH_CONFIG_FILE")]
+    #[serde(skip)]
+    config: Option<PathBuf>,
+    /// Address to listen on [default: 0.0.0.0:3000]
+    #[arg(long, env = "LISTEN_ADDRESS")]
+    listen_address: Option<String>,
+    /// Comma-separated origins allowed to make credentialed requests [default: http://localhost]
+    #[arg(long, env = "ALLOWED_ORIGINS", value_delimiter = ',')]
+    allowed_origins: Option<Vec<String>>,
+    /// Directory of static files to serve [default: assets]
+    #[arg(long, env = "ASSETS_DIR")]
+    assets_dir: Option<PathBuf>,
+    /// URL users reach this service at, for links in email [default: http://localhost:3000]
+    #[arg(long, env = "PUBLIC_URL")]
+    public_url: Option<String>,
+    /// Name authenticator apps show for this service [default: Auth Service]
+    #[arg(long, env = "TOTP_ISSUER")]
+    totp_issuer: Option<String>,
+    /// PostgreSQL or SQLite URL to persist users in, instead of memory
+    #[arg(long, env = "DATABASE_URL", hide_env_values = true)]
+    database_url: Option<String>,
+    /// Redis URL to share banned tokens, 2FA codes, refresh tokens, failed logins and rate limits through
+    #[arg(long, env = "REDIS_URL")]
+    redis_url: Option<String>,
+    /// SMTP relay to send email through, instead of printing it
+    #[arg(long, env = "SMTP_URL", hide_env_values = true)]
+    smtp_url: Option<String>,
+    /// Address email is sent from; required with --smtp-url
+    #[arg(long, env = "EMAIL_SENDER")]
+    email_sender: Option<String>,
+    /// HTTP(S) URL to post account events to, instead of only logging them
+    #[arg(long, env = "ACCOUNT_EVENT_WEBHOOK_URL")]
+    account_event_webhook_url: Option<String>,
+    /// Secret to sign account event webhooks with; required with --account-event-webhook-url
+    #[arg(long, env = "ACCOUNT_EVENT_WEBHOOK_SECRET", hide_env_values = true)]
+    account_event_webhook_secret: Option<String>,
+    /// Shared secret to sign tokens with (HS256)
+    #[arg(long, env = "JWT_SECRET", hide_env_values = true)]
+    jwt_secret: Option<String>,
+    /// PEM private key to sign tokens with, instead of --jwt-secret
+    #[arg(long, env = "JWT_PRIVATE_KEY_FILE")]
+    jwt_private_key_file: Option<PathBuf>,
+    /// Algorithm of --jwt-private-key-file: RS256 or EdDSA [default: RS256]
+    #[arg(long, env = "JWT_ALGORITHM")]
+    jwt_algorithm: Option<String>,
+    /// JSON keyring to sign and verify tokens with, instead of a single key
+    #[arg(long, env = "JWT_KEYRING_FILE")]
+    jwt_keyring_file: Option<PathBuf>,
+    /// How long access tokens are valid [default: 600]
+    #[arg(long, env = "TOKEN_TTL_SECONDS")]
+    token_ttl_seconds: Option<u64>,
+    /// `iss` claim of access tokens [default: PUBLIC_URL]
+    #[arg(long, env = "JWT_ISSUER")]
+    jwt_issuer: Option<String>,
+    /// `aud` claim of access tokens, naming the services they are for [default: app-service]
+    #[arg(long, env = "JWT_AUDIENCE")]
+    jwt_audience: Option<String>,
+    /// Clock skew allowed when checking access tokens' times [default: 60]
+    #[arg(long, env = "JWT_LEEWAY_SECONDS")]
+    jwt_leeway_seconds: Option<u64>,
+    /// How long refresh tokens are valid [default: 1209600]
+    #[arg(long, env = "REFRESH_TOKEN_TTL_SECONDS")]
+    refresh_token_ttl_seconds: Option<u64>,
+    /// How long emailed 2FA codes are valid [default: 600]
+    #[arg(long, env = "TWO_FA_CODE_TTL_SECONDS")]
+    two_fa_code_ttl_seconds: Option<u64>,
+    /// Wrong guesses a 2FA code survives [default: 5]
+    #[arg(long, env = "MAX_TWO_FA_ATTEMPTS")]
+    max_two_fa_attempts: Option<u32>,
+    /// How long emailed password reset tokens are valid [default: 900]
+    #[arg(long, env = "PASSWORD_RESET_TOKEN_TTL_SECONDS")]
+    password_reset_token_ttl_seconds: Option<u64>,
+    /// How long the link emailed at signup verifies the address for [default: 86400]
+    #[arg(long, env = "EMAIL_VERIFICATION_TOKEN_TTL_SECONDS")]
+    email_verification_token_ttl_seconds: Option<u64>,
+    /// Refuse logins until the user has verified their email address [default: false]
+    #[arg(long, env = "REQUIRE_EMAIL_VERIFICATION", num_args = 0..=1, default_missing_value = "true")]
+    require_email_verification: Option<bool>,
+    /// How often expired tokens are swept from the in-memory banned token store [default: 60]
+    #[arg(long, env = "BANNED_TOKEN_SWEEP_INTERVAL_SECONDS")]
+    banned_token_sweep_interval_seconds: Option<u64>,
+    /// How often the banned token and 2FA code stores are counted for /metrics [default: 30]
+    #[arg(long, env = "STORE_METRICS_INTERVAL_SECONDS")]
+    store_metrics_interval_seconds: Option<u64>,
+    /// Log format; what gets logged is set with RUST_LOG [default: text]
+    #[arg(long, env = "LOG_FORMAT", value_enum)]
+    log_format: Option<LogFormat>,
+    /// Signup, login, 2FA and password reset requests allowed per client IP per window [default: 30]
+    #[arg(long, env = "RATE_LIMIT_PER_IP")]
+    rate_limit_per_ip: Option<u32>,
+    /// Signup, login, 2FA and password reset requests allowed per email address per window [default: 10]
+    #[arg(long, env = "RATE_LIMIT_PER_EMAIL")]
+    rate_limit_per_email: Option<u32>,
+    /// Window the rate limits apply to [default: 60]
+    #[arg(long, env = "RATE_LIMIT_WINDOW_SECONDS")]
+    rate_limit_window_seconds: Option<u64>,
+    /// Failed logins in a row that lock an account [default: 5]
+    #[arg(long, env = "MAX_FAILED_LOGINS")]
+    max_failed_logins: Option<u32>,
+    /// How long an account's first lockout lasts; each one after it doubles [default: 60]
+    #[arg(long, env = "LOCKOUT_SECONDS")]
+    lockout_seconds: Option<u64>,
+    /// The longest an account is locked out for [default: 3600]
+    #[arg(long, env = "MAX_LOCKOUT_SECONDS")]
+    max_lockout_seconds: Option<u64>,
+    /// Bearer token for the admin routes, which are refused without one
+    #[arg(long, env = "ADMIN_TOKEN", hide_env_values = true)]
+    admin_token: Option<String>,
+    /// Bearer token for services calling /introspect, which is refused without one
+    #[arg(long, env = "INTROSPECTION_TOKEN", hide_env_values = true)]
+    introspection_token: Option<String>,
+    /// Bearer token for scraping /metrics, which is refused without one
+    #[arg(long, env = "METRICS_TOKEN", hide_env_values = true)]
+    metrics_token: Option<String>,
+}
+
+impl Settings {
+    /// Load settings from the process's arguments; see [`Settings::load_from`].
+    pub fn load() -> Result<Self, SettingsError> {
+        Self::load_from(std::env::args_os())
+    }
+
+    /// Load settings from command line flags, falling back to environment
+    /// variables, then to the `--config` TOML file, then to defaults.
+    pub fn load_from<I, T>(args: I) -> Result<Self, SettingsError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let raw = RawSettings::try_parse_from(args).map_err(SettingsError::InvalidArguments)?;
+        let file = match &raw.config {
+            Some(path) => RawSettings::from_file(path)?,
+            None => RawSettings::default(),
+        };
+        raw.or(file).validate()
+    }
+}
+
+impl RawSettings {
+    fn from_file(path: &Path) -> Result<Self, SettingsError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| SettingsError::ReadFailed(path.to_owned(), e))?;
+        toml::from_str(&contents).map_err(|e| SettingsError::InvalidFile(path.to_owned(), e))
+    }
+
+    // Fill in whatever isn't set here from `fallback`
+    fn or(self, fallback: RawSettings) -> RawSettings {
+        RawSettings {
+            config: self.config,
+            listen_address: self.listen_address.or(fallback.listen_address),
+            allowed_origins: self.allowed_origins.or(fallback.allowed_origins),
+            assets_dir: self.assets_dir.or(fallback.assets_dir),
+            public_url: self.public_url.or(fallback.public_url),
+            totp_issuer: self.totp_issuer.or(fallback.totp_issuer),
+            database_url: self.database_url.or(fallback.database_url),
+            redis_url: self.redis_url.or(fallback.redis_url),
+            smtp_url: self.smtp_url.or(fallback.smtp_url),
+            email_sender: self.email_sender.or(fallback.email_sender),
+            account_event_webhook_url: self
+                .account_event_webhook_url
+                .or(fallback.account_event_webhook_url),
+            account_event_webhook_secret: self
+                .account_event_webhook_secret
+                .or(fallback.account_event_webhook_secret),
+            jwt_secret: self.jwt_secret.or(fallback.jwt_secret),
+            jwt_private_key_file: self.jwt_private_key_file.or(fallback.jwt_private_key_file),
+            jwt_algorithm: self.jwt_algorithm.or(fallback.jwt_algorithm),
+            jwt_keyring_file: self.jwt_keyring_file.or(fallback.jwt_keyring_file),
+            token_ttl_seconds: self.token_ttl_seconds.or(fallback.token_ttl_seconds),
+            jwt_issuer: self.jwt_issuer.or(fallback.jwt_issuer),
+            jwt_audience: self.jwt_audience.or(fallback.jwt_audience),
+            jwt_leeway_seconds: self.jwt_leeway_seconds.or(fallback.jwt_leeway_seconds),
+            refresh_token_ttl_seconds: self
+                .refresh_token_ttl_seconds
+                .or(fallback.refresh_token_ttl_seconds),
+            two_fa_code_ttl_seconds: self
+                .two_fa_code_ttl_seconds
+                .or(fallback.two_fa_code_ttl_seconds),
+            max_two_fa_attempts: self.max_two_fa_attempts.or(fallback.max_two_fa_attempts),
+            password_reset_token_ttl_seconds: self
+                .password_reset_token_ttl_seconds
+                .or(fallback.password_reset_token_ttl_seconds),
+            email_verification_token_ttl_seconds: self
+                .email_verification_token_ttl_seconds
+                .or(fallback.email_verification_token_ttl_seconds),
+            require_email_verification: self
+                .require_email_verification
+                .or(fallback.require_email_verification),
+            banned_token_sweep_interval_seconds: self
+                .banned_token_sweep_interval_seconds
+                .or(fallback.banned_token_sweep_interval_seconds),
+            store_metrics_interval_seconds: self
+                .store_metrics_interval_seconds
+                .or(fallback.store_metrics_interval_seconds),
+            log_format: self.log_format.or(fallback.log_format),
+            rate_limit_per_ip: self.rate_limit_per_ip.or(fallback.rate_limit_per_ip),
+            rate_limit_per_email: self.rate_limit_per_email.or(fallback.rate_limit_per_email),
+            rate_limit_window_seconds: self
+                .rate_limit_window_seconds
+                .or(fallback.rate_limit_window_seconds),
+            max_failed_logins: self.max_failed_logins.or(fallback.max_failed_logins),
+            lockout_seconds: self.lockout_seconds.or(fallback.lockout_seconds),
+            max_lockout_seconds: self.max_lockout_seconds.or(fallback.max_lockout_seconds),
+            admin_token: self.admin_token.or(fallback.admin_token),
+            introspection_token: self.introspection_token.or(fallback.introspection_token),
+            metrics_token: self.metrics_token.or(fallback.metrics_token),
+        }
+    }
+
+    fn validate(self) -> Result<Settings, SettingsError> {
+        let allowed_origins = self
+            .allowed_origins
+            .unwrap_or_else(|| vec![DEFAULT_ALLOWED_ORIGIN.to_owned()])
+            .iter()
+            .map(|origin| parse_origin(origin))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let public_url = non_empty(self.public_url)
+            .unwrap_or_else(|| DEFAULT_PUBLIC_URL.to_owned())
+            .trim_end_matches('/')
+            .to_owned();
+        if !(public_url.starts_with("http://") || public_url.starts_with("https://")) {
+            return Err(SettingsError::Invalid(format!(
+                "PUBLIC_URL must be an http:// or https:// URL, not {:?}",
+                public_url
+            )));
+        }
+
+        let totp_issuer =
+            non_empty(self.totp_issuer).unwrap_or_else(|| DEFAULT_TOTP_ISSUER.to_owned());
+        // the otpauth URI separates the issuer from the account with a colon
+        if totp_issuer.contains(':') {
+            return Err(SettingsError::Invalid(format!(
+                "TOTP_ISSUER must not contain ':', not {:?}",
+                totp_issuer
+            )));
+        }
+
+        let jwt_audience =
+            non_empty(self.jwt_audience).unwrap_or_else(|| DEFAULT_JWT_AUDIENCE.to_owned());
+        // emailed tokens are told apart from access tokens by their audience
+        if [PASSWORD_RESET_AUDIENCE, EMAIL_VERIFICATION_AUDIENCE].contains(&jwt_audience.as_str()) {
+            return Err(SettingsError::Invalid(format!(
+                "JWT_AUDIENCE must not be {:?}, which emailed tokens are for",
+                jwt_audience
+            )));
+        }
+        let token_policy = TokenPolicy {
+            issuer: non_empty(self.jwt_issuer).unwrap_or_else(|| public_url.clone()),
+            audience: jwt_audience,
+            leeway: Duration::from_secs(self.jwt_leeway_seconds.unwrap_or(JWT_LEEWAY_SECONDS)),
+        };
+
+        let smtp = match (non_empty(self.smtp_url), non_empty(self.email_sender)) {
+            (Some(url), Some(sender)) => Some(SmtpSettings {
+                url,
+                sender: sender.parse().map_err(|_| {
+                    SettingsError::Invalid(format!(
+                        "EMAIL_SENDER must be a valid email address, not {:?}",
+                        sender
+                    ))
+                })?,
+            }),
+            (Some(_), None) => {
+                return Err(SettingsError::Invalid(
+                    "EMAIL_SENDER must be set when SMTP_URL is set".to_owned(),
+                ))
+            }
+            (None, _) => None,
+        };
+
+        let account_event_webhook = match (
+            non_empty(self.account_event_webhook_url),
+            non_empty(self.account_event_webhook_secret),
+        ) {
+            (Some(url), _) if !url.starts_with("http://") && !url.starts_with("https://") => {
+                return Err(SettingsError::Invalid(format!(
+                    "ACCOUNT_EVENT_WEBHOOK_URL must be an http:// or https:// URL, not {:?}",
+                    url
+                )))
+            }
+            (Some(url), Some(secret)) => Some(WebhookSettings { url, secret }),
+            (Some(_), None) => return Err(SettingsError::Invalid(
+                "ACCOUNT_EVENT_WEBHOOK_SECRET must be set when ACCOUNT_EVENT_WEBHOOK_URL is set"
+                    .to_owned(),
+            )),
+            (None, _) => None,
+        };
+
+        let jwt_key = if let Some(path) = non_empty_path(self.jwt_keyring_file) {
+            JwtKeySource::KeyringFile(path)
+        } else if let Some(path) = non_empty_path(self.jwt_private_key_file) {
+            let algorithm = match non_empty(self.jwt_algorithm) {
+                None => Algorithm::RS256,
+                Some(name) => match name.parse() {
+                    Ok(algorithm @ (Algorithm::RS256 | Algorithm::EdDSA)) => algorithm,
+                    _ => {
+                        return Err(SettingsError::Invalid(format!(
+                            "JWT_ALGORITHM must be RS256 or EdDSA, not {:?}",
+                            name
+                        )))
+                    }
+                },
+            };
+            JwtKeySource::PrivateKeyFile { path, algorithm }
+        } else if let Some(secret) = non_empty(self.jwt_secret) {
+            JwtKeySource::Secret(secret)
+        } else {
+            return Err(SettingsError::Invalid(
+                "no key to sign tokens with: set JWT_SECRET, JWT_PRIVATE_KEY_FILE or JWT_KEYRING_FILE"
+                    .to_owned(),
+            ));
+        };
+
+        let max_two_fa_attempts = at_least_one(
+            "MAX_TWO_FA_ATTEMPTS",
+            self.max_two_fa_attempts,
+            MAX_TWO_FA_ATTEMPTS,
+        )?;
+
+        let rate_limit_window = seconds(
+            "RATE_LIMIT_WINDOW_SECONDS",
+            self.rate_limit_window_seconds,
+            RATE_LIMIT_WINDOW_SECONDS,
+        )?;
+        let ip_rate_limit = RateLimit::new(
+            at_least_one(
+                "RATE_LIMIT_PER_IP",
+                self.rate_limit_per_ip,
+                RATE_LIMIT_PER_IP,
+            )?,
+            rate_limit_window,
+        );
+        let email_rate_limit = RateLimit::new(
+            at_least_one(
+                "RATE_LIMIT_PER_EMAIL",
+                self.rate_limit_per_email,
+                RATE_LIMIT_PER_EMAIL,
+            )?,
+            rate_limit_window,
+        );
+
+        let base_lockout = seconds("LOCKOUT_SECONDS", self.lockout_seconds, LOCKOUT_SECONDS)?;
+        let max_lockout = seconds(
+            "MAX_LOCKOUT_SECONDS",
+            self.max_lockout_seconds,
+            MAX_LOCKOUT_SECONDS,
+        )?;
+        if max_lockout < base_lockout {
+            return Err(SettingsError::Invalid(
+                "MAX_LOCKOUT_SECONDS must be at least LOCKOUT_SECONDS".to_owned(),
+            ));
+        }
+        let lockout = LockoutPolicy {
+            max_failed_attempts: at_least_one(
+                "MAX_FAILED_LOGINS",
+                self.max_failed_logins,
+                MAX_FAILED_LOGINS,
+            )?,
+            base_lockout,
+            max_lockout,
+            // an account must not be forgotten while it is still locked
+            forget_after: max_lockout.max(Duration::from_secs(FAILED_LOGIN_MEMORY_SECONDS)),
+        };
+
+        Ok(Settings {
+            listen_address: non_empty(self.listen_address)
+                .unwrap_or_else(|| DEFAULT_LISTEN_ADDRESS.to_owned()),
+            allowed_origins,
+            assets_dir: non_empty_path(self.assets_dir)
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_ASSETS_DIR)),
+            public_url,
+            totp_issuer,
+            database_url: non_empty(self.database_url),
+            redis_url: non_empty(self.redis_url),
+            smtp,
+            account_event_webhook,
+            jwt_key,
+            token_ttl: seconds(
+                "TOKEN_TTL_SECONDS",
+                self.token_ttl_seconds,
+                DEFAULT_TOKEN_TTL_SECONDS,
+            )?,
+            token_policy,
+            refresh_token_ttl: seconds(
+                "REFRESH_TOKEN_TTL_SECONDS",
+                self.refresh_token_ttl_seconds,
+                REFRESH_TOKEN_TTL_SECONDS,
+            )?,
+            two_fa_code_ttl: seconds(
+                "TWO_FA_CODE_TTL_SECONDS",
+                self.two_fa_code_ttl_seconds,
+                TWO_FA_CODE_TTL_SECONDS,
+            )?,
+            max_two_fa_attempts,
+            password_reset_token_ttl: seconds(
+                "PASSWORD_RESET_TOKEN_TTL_SECONDS",
+                self.password_reset_token_ttl_seconds,
+                PASSWORD_RESET_TOKEN_TTL_SECONDS,
+            )?,
+            email_verification_token_ttl: seconds(
+                "EMAIL_VERIFICATION_TOKEN_TTL_SECONDS",
+                self.email_verification_token_ttl_seconds,
+                EMAIL_VERIFICATION_TOKEN_TTL_SECONDS,
+            )?,
+            require_email_verification: self.require_email_verification.unwrap_or_default(),
+            banned_token_sweep_interval: seconds(
+                "BANNED_TOKEN_SWEEP_INTERVAL_SECONDS",
+                self.banned_token_sweep_interval_seconds,
+                BANNED_TOKEN_SWEEP_INTERVAL_SECONDS,
+            )?,
+            store_metrics_interval: seconds(
+                "STORE_METRICS_INTERVAL_SECONDS",
+                self.store_metrics_interval_seconds,
+                STORE_METRICS_INTERVAL_SECONDS,
+            )?,
+            log_format: self.log_format.unwrap_or_default(),
+            ip_rate_limit,
+            email_rate_limit,
+            lockout,
+            admin_token: non_empty(self.admin_token).map(BearerToken),
+            introspection_token: non_empty(self.introspection_token).map(BearerToken),
+            metrics_token: non_empty(self.metrics_token).map(BearerToken),
+        })
+    }
+}
+
+// Compose files pass unset variables through as empty strings, which
+// should mean the same as not setting them at all.
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|value| !value.is_empty())
+}
+
+fn non_empty_path(value: Option<PathBuf>) -> Option<PathBuf> {
+    value.filter(|value| !value.as_os_str().is_empty())
+}
+
+fn parse_origin(origin: &str) -> Result<HeaderValue, SettingsError> {
+    // browsers reject a wildcard on credentialed requests, and so does tower-http
+    if origin == "*" || !(origin.starts_with("http://") || origin.starts_with("https://")) {
+        return Err(SettingsError::Invalid(format!(
+            "ALLOWED_ORIGINS must be http:// or https:// origins, not {:?}",
+            origin
+        )));
+    }
+    origin.parse().map_err(|_| {
+        SettingsError::Invalid(format!(
+            "ALLOWED_ORIGINS has an invalid origin {:?}",
+            origin
+        ))
+    })
+}
+
+fn seconds(name: &str, value: Option<u64>, default: u64) -> Result<Duration, SettingsError> {
+    match value.unwrap_or(default) {
+        0 => Err(SettingsError::Invalid(format!(
+            "{} must be at least 1 second",
+            name
+        ))),
+        seconds => Ok(Duration::from_secs(seconds)),
+    }
+}
+
+fn at_least_one(name: &str, value: Option<u32>, default: u32) -> Result<u32, SettingsError> {
+    match value.unwrap_or(default) {
+        0 => Err(SettingsError::Invalid(format!(
+            "{} must be at least 1",
+            name
+        ))),
+        value => Ok(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Most of these tests build `RawSettings` directly, since whatever is
+    // set in the test process's environment would leak into `load_from`.
+    fn with_secret() -> RawSettings {
+        RawSettings {
+            jwt_secret: Some("secret".to_owned()),
+            ..Default::default()
+        }
+    }
+
+    fn error_message(raw: RawSettings) -> String {
+        raw.validate()
+            .expect_err("Settings should be invalid")
+            .to_string()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let settings = with_secret().validate().unwrap();
+        assert_eq!(settings.listen_address, DEFAULT_LISTEN_ADDRESS);
+        assert_eq!(settings.allowed_origins, [DEFAULT_ALLOWED_ORIGIN]);
+        assert_eq!(
+            settings.token_ttl,
+            Duration::from_secs(DEFAULT_TOKEN_TTL_SECONDS)
+        );
+        assert_eq!(settings.max_two_fa_attempts, MAX_TWO_FA_ATTEMPTS);
+        assert_eq!(
+            settings.email_rate_limit,
+            RateLimit::new(
+                RATE_LIMIT_PER_EMAIL,
+                Duration::from_secs(RATE_LIMIT_WINDOW_SECONDS)
+            )
+        );
+        assert_eq!(settings.public_url, DEFAULT_PUBLIC_URL);
+        assert_eq!(settings.totp_issuer, DEFAULT_TOTP_ISSUER);
+        assert!(!settings.require_email_verification);
+        assert!(settings.smtp.is_none());
+        assert!(settings.account_event_webhook.is_none());
+        assert!(matches!(settings.jwt_key, JwtKeySource::Secret(_)));
+        assert_eq!(
+            settings.token_policy,
+            TokenPolicy {
+                issuer: DEFAULT_PUBLIC_URL.to_owned(),
+                audience: DEFAULT_JWT_AUDIENCE.to_owned(),
+                leeway: Duration::from_secs(JWT_LEEWAY_SECONDS),
+            }
+        );
+    }
+
+    #[test]
+    fn test_token_issuer_follows_public_url() {
+        let settings = RawSettings {
+            public_url: Some("https://auth.example.com/".to_owned()),
+            jwt_leeway_seconds: Some(0),
+            ..with_secret()
+        }
+        .validate()
+        .unwrap();
+        assert_eq!(settings.token_policy.issuer, "https://auth.example.com");
+        assert_eq!(settings.token_policy.leeway, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_emailed_token_audience_fails() {
+        let message = error_message(RawSettings {
+            jwt_audience: Some(PASSWORD_RESET_AUDIENCE.to_owned()),
+            ..with_secret()
+        });
+        assert!(message.contains("JWT_AUDIENCE"), "{}", message);
+    }
+
+    #[test]
+    fn test_flags_override_config_file() {
+        let file: RawSettings = toml::from_str(
+            r#"
+            listen_address = "127.0.0.1:4000"
+            token_ttl_seconds = 300
+            allowed_origins = ["https://example.com", "https://www.example.com"]
+            "#,
+        )
+        .unwrap();
+        let flags =
+            RawSettings::try_parse_from(["auth-service", "--listen-address", "127.0.0.1:5000"])
+                .unwrap();
+        let settings = flags.or(file).or(with_secret()).validate().unwrap();
+        assert_eq!(settings.listen_address, "127.0.0.1:5000");
+        assert_eq!(settings.token_ttl, Duration::from_secs(300));
+        assert_eq!(settings.allowed_origins.len(), 2);
+    }
+
+    #[test]
+    fn test_comma_separated_origins() {
+        let flags = RawSettings::try_parse_from([
+            "auth-service",
+            "--allowed-origins",
+            "http://localhost:8000,https://example.com",
+        ])
+        .unwrap();
+        let settings = flags.or(with_secret()).validate().unwrap();
+        assert_eq!(
+            settings.allowed_origins,
+            ["http://localhost:8000", "https://example.com"]
+        );
+    }
+
+    #[test]
+    fn test_log_format() {
+        let flags = RawSettings::try_parse_from(["auth-service", "--log-format", "json"]).unwrap();
+        assert_eq!(flags.log_format, Some(LogFormat::Json));
+        let file: RawSettings = toml::from_str(r#"log_format = "text""#).unwrap();
+        assert_eq!(file.log_format, Some(LogFormat::Text));
+        assert!(RawSettings::try_parse_from(["auth-service", "--log-format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn test_require_email_verification() {
+        let flags =
+            RawSettings::try_parse_from(["auth-service", "--require-email-verification"]).unwrap();
+        assert_eq!(flags.require_email_verification, Some(true));
+        let file: RawSettings = toml::from_str("require_email_verification = false").unwrap();
+        assert_eq!(file.require_email_verification, Some(false));
+    }
+
+    #[test]
+    fn test_public_url_trailing_slash_is_dropped() {
+        let settings = RawSettings {
+            public_url: Some("https://auth.example.com/".to_owned()),
+            ..with_secret()
+        }
+        .validate()
+        .unwrap();
+        assert_eq!(settings.public_url, "https://auth.example.com");
+
+        let message = error_message(RawSettings {
+            public_url: Some("auth.example.com".to_owned()),
+            ..with_secret()
+        });
+        assert!(message.contains("PUBLIC_URL"), "{}", message);
+    }
+
+    #[test]
+    fn test_totp_issuer_must_not_contain_colon() {
+        let message = error_message(RawSettings {
+            totp_issuer: Some("Example: Auth".to_owned()),
+            ..with_secret()
+        });
+        assert!(message.contains("TOTP_ISSUER"), "{}", message);
+    }
+
+    #[test]
+    fn test_unknown_config_key_fails() {
+        assert!(toml::from_str::<RawSettings>("token_ttl = 300").is_err());
+    }
+
+    #[test]
+    fn test_missing_config_file_fails() {
+        assert!(matches!(
+            Settings::load_from(["auth-service", "--config", "does-not-exist.toml"]),
+            Err(SettingsError::ReadFailed(..))
+        ));
+    }
+
+    #[test]
+    fn test_non_numeric_ttl_fails() {
+        assert!(matches!(
+            Settings::load_from(["auth-service", "--token-ttl-seconds", "ten"]),
+            Err(SettingsError::InvalidArguments(_))
+        ));
+    }
+
+    #[test]
+    fn test_missing_jwt_key_fails() {
+        let message = error_message(RawSettings {
+            jwt_secret: Some(String::new()),
+            ..Default::default()
+        });
+        assert!(message.contains("JWT_SECRET"), "{}", message);
+    }
+
+    #[test]
+    fn test_keyring_takes_precedence_over_single_key() {
+        let settings = RawSettings {
+            jwt_keyring_file: Some("keyring.json".into()),
+            jwt_private_key_file: Some("key.pem".into()),
+            ..with_secret()
+        }
+        .validate()
+        .unwrap();
+        assert!(matches!(settings.jwt_key, JwtKeySource::KeyringFile(_)));
+    }
+
+    #[test]
+    fn test_unsupported_jwt_algorithm_fails() {
+        let message = error_message(RawSettings {
+            jwt_private_key_file: Some("key.pem".into()),
+            jwt_algorithm: Some("HS512".to_owned()),
+            ..Default::default()
+        });
+        assert!(message.contains("JWT_ALGORITHM"), "{}", message);
+    }
+
+    #[test]
+    fn test_smtp_requires_valid_sender() {
+        let message = error_message(RawSettings {
+            smtp_url: Some("smtp://localhost:25".to_owned()),
+            ..with_secret()
+        });
+        assert!(message.contains("EMAIL_SENDER"), "{}", message);
+
+        let message = error_message(RawSettings {
+            smtp_url: Some("smtp://localhost:25".to_owned()),
+            email_sender: Some("not an email".to_owned()),
+            ..with_secret()
+        });
+        assert!(message.contains("EMAIL_SENDER"), "{}", message);
+    }
+
+    #[test]
+    fn test_webhook_requires_secret() {
+        let message = error_message(RawSettings {
+            account_event_webhook_url: Some("https://example.com/hook".to_owned()),
+            ..with_secret()
+        });
+        assert!(
+            message.contains("ACCOUNT_EVENT_WEBHOOK_SECRET"),
+            "{}",
+            message
+        );
+
+        let settings = RawSettings {
+            account_event_webhook_url: Some("https://example.com/hook".to_owned()),
+            account_event_webhook_secret: Some("shared".to_owned()),
+            ..with_secret()
+        }
+        .validate()
+        .unwrap();
+        let webhook = settings.account_event_webhook.unwrap();
+        assert_eq!(webhook.url, "https://example.com/hook");
+        assert!(!format!("{:?}", webhook).contains("shared"));
+    }
+
+    #[test]
+    fn test_webhook_url_must_be_http() {
+        let message = error_message(RawSettings {
+            account_event_webhook_url: Some("example.com/hook".to_owned()),
+            account_event_webhook_secret: Some("shared".to_owned()),
+            ..with_secret()
+        });
+        assert!(message.contains("ACCOUNT_EVENT_WEBHOOK_URL"), "{}", message);
+    }
+
+    #[test]
+    fn test_zero_ttl_fails() {
+        let message = error_message(RawSettings {
+            two_fa_code_ttl_seconds: Some(0),
+            ..with_secret()
+        });
+        assert!(message.contains("TWO_FA_CODE_TTL_SECONDS"), "{}", message);
+    }
+
+    #[test]
+    fn test_wildcard_origin_fails() {
+        let message = error_message(RawSettings {
+            allowed_origins: Some(vec!["*".to_owned()]),
+            ..with_secret()
+        });
+        assert!(message.contains("ALLOWED_ORIGINS"), "{}", message);
+    }
+
+    #[test]
+    fn test_zero_rate_limit_fails() {
+        let message = error_message(RawSettings {
+            rate_limit_per_ip: Some(0),
+            ..with_secret()
+        });
+        assert!(message.contains("RATE_LIMIT_PER_IP"), "{}", message);
+    }
+
+    #[test]
+    fn test_max_lockout_shorter_than_lockout_fails() {
+        let message = error_message(RawSettings {
+            lockout_seconds: Some(600),
+            max_lockout_seconds: Some(60),
+            ..with_secret()
+        });
+        assert!(message.contains("MAX_LOCKOUT_SECONDS"), "{}", message);
+    }
+
+    #[test]
+    fn test_admin_token_matches_only_itself() {
+        let settings = RawSettings {
+            admin_token: Some("admin-token".to_owned()),
+            ..with_secret()
+        }
+        .validate()
+        .unwrap();
+        let admin_token = settings.admin_token.expect("Admin token should be set");
+        assert!(admin_token.matches("admin-token"));
+        assert!(!admin_token.matches("admin-toke"));
+        assert_eq!(format!("{:?}", admin_token), "BearerToken(..)");
+    }
+}
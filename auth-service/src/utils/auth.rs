@@ -1,15 +1,21 @@
-use crate::domain::{Email, Token};
-use crate::utils::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use crate::domain::{AuthMethod, Email, RefreshToken, Token, UserId};
+use crate::utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME};
+use crate::utils::jwt_key::JwtKeyring;
 use axum_extra::extract::cookie::{Cookie, SameSite};
-use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use uuid::Uuid;
 
-pub const TOKEN_TTL_SECONDS: i64 = 600;
-
-// Create cookie with a new JWT auth token
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>, GenerateTokenError> {
-    let token = generate_auth_token(email)?;
+// Create cookie with a new JWT auth token, valid for `ttl`
+pub fn generate_auth_cookie(
+    user_id: &UserId,
+    amr: &[AuthMethod],
+    keyring: &JwtKeyring,
+    policy: &TokenPolicy,
+    ttl: Duration,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = generate_auth_token(user_id, amr, keyring, policy, ttl)?;
     Ok(create_auth_cookie(token))
 }
 
@@ -22,6 +28,16 @@ pub fn create_auth_cookie(token: Token) -> Cookie<'static> {
         .build()
 }
 
+// Create cookie holding a refresh token, which outlives the browser session
+pub fn create_refresh_cookie(token: &RefreshToken, ttl: Duration) -> Cookie<'static> {
+    Cookie::build((REFRESH_COOKIE_NAME, token.as_ref().to_owned()))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::try_from(ttl).unwrap_or(time::Duration::MAX))
+        .build()
+}
+
 #[derive(Debug)]
 pub enum GenerateTokenError {
     TokenError(jsonwebtoken::errors::Error),
@@ -38,59 +54,233 @@ pub enum TwoFACodeError {
     Invalid,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum PasswordHashError {
+    HashingFailed,
+    InvalidHash,
+    IncorrectPassword,
+}
+
+/// Who access tokens are issued by and for, which they are checked
+/// against when they come back, and how far the clocks of the services
+/// checking them may be off.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenPolicy {
+    pub issuer: String,
+    pub audience: String,
+    pub leeway: Duration,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
+    pub iss: String,
+    pub aud: String,
     pub exp: usize,
+    /// Not valid before; the same as `iat`, as tokens are valid at once.
+    pub nbf: usize,
+    pub iat: usize,
+    /// `iat` in milliseconds, so that a ban of the user can tell apart the
+    /// tokens issued in the same second, before and after it.
+    #[serde(default)]
+    pub iat_ms: i64,
+    /// Random, so that every token is distinct and can be banned by it.
+    pub jti: String,
+    /// How the user logged in.
+    pub amr: Vec<AuthMethod>,
 }
 
-// Create JWT auth token
-pub fn generate_auth_token(email: &Email) -> Result<Token, GenerateTokenError> {
-    let delta =
-        Duration::try_seconds(TOKEN_TTL_SECONDS).ok_or(GenerateTokenError::UnexpectedError)?;
-    // create JWT expiration time
-    let exp = Utc::now()
-        .checked_add_signed(delta)
-        .ok_or(GenerateTokenError::UnexpectedError)?
-        .timestamp();
-    let exp: usize = exp
+// Just what bookkeeping needs, so that it can be read from any of our
+// tokens, access or emailed
+#[derive(Deserialize)]
+struct UnverifiedClaims {
+    sub: Option<String>,
+    exp: Option<usize>,
+    iat: Option<usize>,
+    iat_ms: Option<i64>,
+    jti: Option<String>,
+}
+
+/// Claims of a token emailed to a user, e.g. to reset their password. The
+/// `aud` claim says what the token is for, and keeps it from being accepted
+/// as an access token; the random `jti` makes every token distinct, so that
+/// using one doesn't use up another.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailTokenClaims {
+    pub sub: String,
+    pub exp: usize,
+    pub aud: String,
+    pub jti: String,
+}
+
+// Create JWT auth token for a user who logged in with `amr`, signed with the
+// keyring's current key, issued as `policy` says and valid for `ttl`
+pub fn generate_auth_token(
+    user_id: &UserId,
+    amr: &[AuthMethod],
+    keyring: &JwtKeyring,
+    policy: &TokenPolicy,
+    ttl: Duration,
+) -> Result<Token, GenerateTokenError> {
+    let exp = expires_at(ttl)?;
+    let now = Utc::now();
+    let iat = now
+        .timestamp()
         .try_into()
         .map_err(|_| GenerateTokenError::UnexpectedError)?;
-    let sub = email.as_ref().to_owned();
+    let sub = user_id.to_string();
 
-    let claims = Claims { sub, exp };
+    let claims = Claims {
+        sub,
+        iss: policy.issuer.clone(),
+        aud: policy.audience.clone(),
+        exp,
+        nbf: iat,
+        iat,
+        iat_ms: now.timestamp_millis(),
+        jti: Uuid::new_v4().to_string(),
+        amr: amr.to_vec(),
+    };
 
-    create_token(&claims).map_err(GenerateTokenError::TokenError)
+    create_token(&claims, keyring).map_err(GenerateTokenError::TokenError)
 }
 
-// Check if JWT auth token is valid by decoding it using the JWT secret
-pub async fn validate_token(token: &Token) -> Result<Claims, jsonwebtoken::errors::Error> {
-    decode::<Claims>(
-        token.to_string(),
-        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-        &Validation::default(),
-    )
-    .map(|data| data.claims)
+// Create a token to email to `email` for `audience` (e.g.
+// `PASSWORD_RESET_AUDIENCE`), valid for `ttl`
+pub fn generate_email_token(
+    email: &Email,
+    audience: &str,
+    keyring: &JwtKeyring,
+    ttl: Duration,
+) -> Result<Token, GenerateTokenError> {
+    let claims = EmailTokenClaims {
+        sub: email.as_ref().to_owned(),
+        exp: expires_at(ttl)?,
+        aud: audience.to_owned(),
+        jti: Uuid::new_v4().to_string(),
+    };
+    create_token(&claims, keyring).map_err(GenerateTokenError::TokenError)
+}
+
+// Check an emailed token's signature, expiry and audience. Whether it has
+// been used already is up to the caller.
+pub fn validate_email_token(
+    token: &Token,
+    audience: &str,
+    keyring: &JwtKeyring,
+) -> Result<EmailTokenClaims, jsonwebtoken::errors::Error> {
+    keyring.decode_for_audience::<EmailTokenClaims>(&token.to_string(), audience)
+}
+
+// JWT expiration time `ttl` from now
+fn expires_at(ttl: Duration) -> Result<usize, GenerateTokenError> {
+    let delta = chrono::Duration::from_std(ttl).map_err(|_| GenerateTokenError::UnexpectedError)?;
+    Utc::now()
+        .checked_add_signed(delta)
+        .ok_or(GenerateTokenError::UnexpectedError)?
+        .timestamp()
+        .try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)
 }
 
-// Create JWT auth token by encoding Claims using the JWT secret
-fn create_token(claims: &Claims) -> Result<Token, jsonwebtoken::errors::Error> {
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+// Check if JWT auth token is valid by decoding it with the key named by its `kid`
+// and that it was issued by and for who `policy` says
+pub fn validate_token(
+    token: &Token,
+    keyring: &JwtKeyring,
+    policy: &TokenPolicy,
+) -> Result<Claims, jsonwebtoken::errors::Error> {
+    keyring.decode_from_issuer::<Claims>(
+        &token.to_string(),
+        &policy.issuer,
+        &policy.audience,
+        policy.leeway,
     )
-    .map(Token::from)
+}
+
+fn read_unverified_claims(token: &Token) -> Option<UnverifiedClaims> {
+    jsonwebtoken::dangerous::insecure_decode::<UnverifiedClaims>(token.to_string())
+        .map(|data| data.claims)
+        .ok()
+}
+
+// Read the `exp` claim of a token without checking its signature or
+// whether it has already expired. Only use this for bookkeeping
+// (e.g. how long to remember a banned token), never for authentication.
+pub fn read_token_expiry(token: &Token) -> Option<usize> {
+    read_unverified_claims(token)?.exp
+}
+
+// Read who a token was issued to and when, as a Unix timestamp in
+// milliseconds, without checking its signature. Tokens without `iat_ms`
+// count as issued at the end of their `iat` second. Like
+// `read_token_expiry`, only use this for bookkeeping.
+pub fn read_token_issue(token: &Token) -> Option<(String, i64)> {
+    let claims = read_unverified_claims(token)?;
+    let issued_at = match claims.iat_ms {
+        Some(iat_ms) => iat_ms,
+        None => i64::try_from(claims.iat?).ok()?.checked_mul(1000)? + 999,
+    };
+    Some((claims.sub?, issued_at))
+}
+
+// Read a token's `jti` claim, which names it in the banned token store,
+// without checking its signature. Like `read_token_expiry`, only use this
+// for bookkeeping.
+pub fn read_token_id(token: &Token) -> Option<String> {
+    read_unverified_claims(token)?.jti
+}
+
+// Unix timestamp after which a token can no longer be valid: its `exp`
+// claim or, for tokens we can't read (and so can't ever have been valid),
+// `ttl` from now.
+pub fn token_expires_at(token: &Token, ttl: Duration) -> i64 {
+    read_token_expiry(token)
+        .and_then(|exp| i64::try_from(exp).ok())
+        .unwrap_or_else(|| Utc::now().timestamp() + ttl.as_secs() as i64)
+}
+
+// Create JWT token by encoding claims using the current signing key
+fn create_token<T: Serialize>(
+    claims: &T,
+    keyring: &JwtKeyring,
+) -> Result<Token, jsonwebtoken::errors::Error> {
+    keyring.encode(claims).map(Token::from)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::{
+        constants::{EMAIL_VERIFICATION_AUDIENCE, PASSWORD_RESET_AUDIENCE},
+        jwt_key::JwtKey,
+    };
+
+    const TTL: Duration = Duration::from_secs(600);
+
+    fn keyring() -> JwtKeyring {
+        JwtKeyring::new(JwtKey::from_secret(b"secret"), vec![])
+    }
+
+    fn policy() -> TokenPolicy {
+        TokenPolicy {
+            issuer: "https://auth.example.com".to_owned(),
+            audience: "app".to_owned(),
+            leeway: Duration::ZERO,
+        }
+    }
 
     #[tokio::test]
     async fn test_generate_auth_cookie() {
-        let email = "test@example.com".parse().unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let user_id = UserId::default();
+        let cookie = generate_auth_cookie(
+            &user_id,
+            &[AuthMethod::Password],
+            &keyring(),
+            &policy(),
+            TTL,
+        )
+        .unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -109,19 +299,43 @@ mod tests {
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
     }
 
+    #[tokio::test]
+    async fn test_create_refresh_cookie() {
+        let token = RefreshToken::default();
+        let cookie = create_refresh_cookie(&token, Duration::from_secs(3600));
+        assert_eq!(cookie.name(), REFRESH_COOKIE_NAME);
+        assert_eq!(cookie.value(), token.as_ref());
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.max_age(), Some(time::Duration::hours(1)));
+    }
+
     #[tokio::test]
     async fn test_generate_auth_token() {
-        let email = "test@example.com".parse().unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let user_id = UserId::default();
+        let result = generate_auth_token(
+            &user_id,
+            &[AuthMethod::Password],
+            &keyring(),
+            &policy(),
+            TTL,
+        )
+        .unwrap();
         assert_eq!(result.to_string().split('.').count(), 3);
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
-        let email = "test@example.com".parse().unwrap();
-        let token = generate_auth_token(&email).unwrap();
-        let result = validate_token(&token).await.unwrap();
-        assert_eq!(result.sub, "test@example.com");
+        let user_id = UserId::default();
+        let token = generate_auth_token(
+            &user_id,
+            &[AuthMethod::Password],
+            &keyring(),
+            &policy(),
+            TTL,
+        )
+        .unwrap();
+        let result = validate_token(&token, &keyring(), &policy()).unwrap();
+        assert_eq!(result.sub, user_id.to_string());
 
         let exp = Utc::now()
             .checked_add_signed(chrono::Duration::try_minutes(9).expect("valid duration"))
@@ -131,10 +345,131 @@ mod tests {
         assert!(result.exp > exp as usize);
     }
 
+    #[tokio::test]
+    async fn test_auth_tokens_carry_amr_and_distinct_jti() {
+        let user_id = UserId::default();
+        let amr = [AuthMethod::Password, AuthMethod::Totp];
+        let token = generate_auth_token(&user_id, &amr, &keyring(), &policy(), TTL).unwrap();
+        let other_token = generate_auth_token(&user_id, &amr, &keyring(), &policy(), TTL).unwrap();
+        let claims = validate_token(&token, &keyring(), &policy()).unwrap();
+        let other_claims = validate_token(&other_token, &keyring(), &policy()).unwrap();
+        assert_eq!(claims.amr, amr);
+        assert!(!claims.jti.is_empty());
+        assert_ne!(claims.jti, other_claims.jti);
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_checks_issuer_and_audience() {
+        let user_id = UserId::default();
+        let token = generate_auth_token(
+            &user_id,
+            &[AuthMethod::Password],
+            &keyring(),
+            &policy(),
+            TTL,
+        )
+        .unwrap();
+        let claims = validate_token(&token, &keyring(), &policy()).unwrap();
+        assert_eq!(claims.iss, "https://auth.example.com");
+        assert_eq!(claims.aud, "app");
+        assert_eq!(claims.nbf, claims.iat);
+
+        let other_issuer = TokenPolicy {
+            issuer: "https://other.example.com".to_owned(),
+            ..policy()
+        };
+        assert!(validate_token(&token, &keyring(), &other_issuer).is_err());
+        let other_audience = TokenPolicy {
+            audience: "other-app".to_owned(),
+            ..policy()
+        };
+        assert!(validate_token(&token, &keyring(), &other_audience).is_err());
+    }
+
+    #[test]
+    fn test_bookkeeping_reads_emailed_tokens() {
+        let email = "test@example.com".parse().unwrap();
+        let token = generate_email_token(&email, PASSWORD_RESET_AUDIENCE, &keyring(), TTL).unwrap();
+        let exp = read_token_expiry(&token).expect("token has an expiry");
+        assert!(exp as i64 > Utc::now().timestamp() + TTL.as_secs() as i64 - 5);
+        assert!(read_token_id(&token).is_some());
+        assert_eq!(read_token_id(&Token::from("invalid_token")), None);
+    }
+
+    #[test]
+    fn test_read_token_expiry() {
+        let user_id = UserId::default();
+        let token = generate_auth_token(
+            &user_id,
+            &[AuthMethod::Password],
+            &keyring(),
+            &policy(),
+            TTL,
+        )
+        .unwrap();
+        let exp = read_token_expiry(&token).expect("token has an expiry");
+        assert!(exp as i64 > Utc::now().timestamp());
+        assert_eq!(read_token_expiry(&Token::from("invalid_token")), None);
+    }
+
+    #[tokio::test]
+    async fn test_email_token_is_only_valid_for_its_audience() {
+        let email = "test@example.com".parse().unwrap();
+        let token = generate_email_token(&email, PASSWORD_RESET_AUDIENCE, &keyring(), TTL).unwrap();
+        let claims = validate_email_token(&token, PASSWORD_RESET_AUDIENCE, &keyring()).unwrap();
+        assert_eq!(claims.sub, "test@example.com");
+        assert!(validate_email_token(&token, EMAIL_VERIFICATION_AUDIENCE, &keyring()).is_err());
+        assert!(validate_token(&token, &keyring(), &policy()).is_err());
+
+        let token = generate_auth_token(
+            &UserId::default(),
+            &[AuthMethod::Password],
+            &keyring(),
+            &policy(),
+            TTL,
+        )
+        .unwrap();
+        assert!(validate_email_token(&token, PASSWORD_RESET_AUDIENCE, &keyring()).is_err());
+    }
+
+    #[test]
+    fn test_email_tokens_are_distinct() {
+        let email = "test@example.com".parse().unwrap();
+        assert_ne!(
+            generate_email_token(&email, PASSWORD_RESET_AUDIENCE, &keyring(), TTL).unwrap(),
+            generate_email_token(&email, PASSWORD_RESET_AUDIENCE, &keyring(), TTL).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_read_token_issue() {
+        let user_id = UserId::default();
+        let token = generate_auth_token(
+            &user_id,
+            &[AuthMethod::Password],
+            &keyring(),
+            &policy(),
+            TTL,
+        )
+        .unwrap();
+        let (sub, issued_at) = read_token_issue(&token).expect("token has an issue time");
+        assert_eq!(sub, user_id.to_string());
+        assert!((Utc::now().timestamp_millis() - issued_at).abs() <= 1000);
+        assert_eq!(read_token_issue(&Token::from("invalid_token")), None);
+
+        // tokens from before `iat_ms` count from the end of their `iat` second
+        let token = create_token(
+            &serde_json::json!({ "sub": sub, "iat": 1_700_000_000 }),
+            &keyring(),
+        )
+        .unwrap();
+        assert_eq!(read_token_issue(&token), Some((sub, 1_700_000_000_999)));
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
         let token = Token::from("invalid_token");
-        let result = validate_token(&token).await;
+        let result = validate_token(&token, &keyring(), &policy());
         assert!(result.is_err());
     }
 }
@@ -1,17 +1,55 @@
-use dotenvy::dotenv;
-use lazy_static::lazy_static;
-
 pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const REFRESH_COOKIE_NAME: &str = "refresh_token";
+/// The `aud` claim of password reset tokens.
+pub const PASSWORD_RESET_AUDIENCE: &str = "password-reset";
+/// The `aud` claim of email verification tokens.
+pub const EMAIL_VERIFICATION_AUDIENCE: &str = "email-verification";
+/// Ties together the log lines for one request, across services.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Carries `sha256=` and the hex HMAC-SHA256 of a webhook's body, keyed
+/// with the shared secret.
+pub const ACCOUNT_EVENT_SIGNATURE_HEADER: &str = "x-account-event-signature";
+/// How many times an account event is posted to the webhook before it is
+/// given up on.
+pub const ACCOUNT_EVENT_WEBHOOK_ATTEMPTS: u32 = 3;
+/// How long the first retry of a webhook waits; each one after doubles.
+pub const ACCOUNT_EVENT_WEBHOOK_RETRY_SECONDS: u64 = 1;
+/// How long one webhook request may take.
+pub const ACCOUNT_EVENT_WEBHOOK_TIMEOUT_SECONDS: u64 = 10;
 
-lazy_static! {
-    pub static ref JWT_SECRET: String = set_token();
-}
-
-fn set_token() -> String {
-    dotenv().ok();
-    let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-    if secret.is_empty() {
-        panic!("JWT_SECRET cannot be empty");
-    }
-    secret
-}
+// Defaults for the corresponding `Settings`
+/// How far clocks may be off when checking an access token's `exp` and `nbf`.
+pub const JWT_LEEWAY_SECONDS: u64 = 60;
+/// How long a refresh token can be exchanged for a new access token.
+pub const REFRESH_TOKEN_TTL_SECONDS: u64 = 14 * 24 * 60 * 60;
+/// How long an emailed password reset token stays valid.
+pub const PASSWORD_RESET_TOKEN_TTL_SECONDS: u64 = 15 * 60;
+/// How long the link emailed at signup verifies the address for.
+pub const EMAIL_VERIFICATION_TOKEN_TTL_SECONDS: u64 = 24 * 60 * 60;
+/// How long an emailed 2FA code stays valid.
+pub const TWO_FA_CODE_TTL_SECONDS: u64 = 600;
+/// How many 30 second steps either side of now an authenticator app's
+/// clock may be off by.
+pub const TOTP_SKEW_STEPS: u8 = 1;
+/// How many recovery codes a user is given at a time.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+/// How many wrong guesses a 2FA code survives before it is invalidated.
+pub const MAX_TWO_FA_ATTEMPTS: u32 = 5;
+/// How often expired tokens are swept out of the banned token store.
+pub const BANNED_TOKEN_SWEEP_INTERVAL_SECONDS: u64 = 60;
+/// How often the store sizes reported by `/metrics` are counted.
+pub const STORE_METRICS_INTERVAL_SECONDS: u64 = 30;
+/// How many signup, login, 2FA and password reset requests one client IP may make per window.
+pub const RATE_LIMIT_PER_IP: u32 = 30;
+/// How many signup, login, 2FA and password reset requests may target one email per window.
+pub const RATE_LIMIT_PER_EMAIL: u32 = 10;
+/// The window the rate limits apply to.
+pub const RATE_LIMIT_WINDOW_SECONDS: u64 = 60;
+/// How many failed logins in a row lock an account.
+pub const MAX_FAILED_LOGINS: u32 = 5;
+/// How long an account's first lockout lasts; each one after doubles.
+pub const LOCKOUT_SECONDS: u64 = 60;
+/// The longest an account is locked out for.
+pub const MAX_LOCKOUT_SECONDS: u64 = 60 * 60;
+/// How long failed logins count against an account after the last one.
+pub const FAILED_LOGIN_MEMORY_SECONDS: u64 = 24 * 60 * 60;
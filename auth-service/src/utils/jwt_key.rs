@@ -0,0 +1,594 @@
+use aws_lc_rs::signature::{Ed25519KeyPair, KeyPair};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    errors::ErrorKind,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use crate::settings::JwtKeySource;
+
+#[derive(Debug)]
+pub enum JwtKeyError {
+    ReadFailed(std::io::Error),
+    InvalidKey(jsonwebtoken::errors::Error),
+    InvalidKeyring(String),
+    UnsupportedAlgorithm(String),
+}
+
+impl fmt::Display for JwtKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JwtKeyError::ReadFailed(e) => write!(f, "failed to read key: {}", e),
+            JwtKeyError::InvalidKey(e) => write!(f, "invalid key: {}", e),
+            JwtKeyError::InvalidKeyring(message) => write!(f, "invalid keyring: {}", message),
+            JwtKeyError::UnsupportedAlgorithm(algorithm) => {
+                write!(f, "unsupported algorithm {}", algorithm)
+            }
+        }
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for JwtKeyError {
+    fn from(error: jsonwebtoken::errors::Error) -> Self {
+        JwtKeyError::InvalidKey(error)
+    }
+}
+
+/// A key that tokens are signed or verified with, identified by a `kid`
+/// that is sent in the JWT header. Asymmetric keys also carry a public
+/// JWK so that other services can verify tokens against
+/// `/.well-known/jwks.json`.
+pub struct JwtKey {
+    kid: String,
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    jwk: Option<Jwk>,
+}
+
+impl JwtKey {
+    /// A shared HS256 secret. Anyone who can verify tokens signed with it
+    /// can also mint them, so it is never published.
+    pub fn from_secret(secret: &[u8]) -> Self {
+        let canonical = format!(
+            r#"{{"k":"{}","kty":"oct"}}"#,
+            URL_SAFE_NO_PAD.encode(secret)
+        );
+        Self {
+            kid: thumbprint(&canonical),
+            algorithm: Algorithm::HS256,
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            jwk: None,
+        }
+    }
+
+    /// A PEM-encoded RSA (`RS256`) or Ed25519 (`EdDSA`) private key. The
+    /// public key is derived from it.
+    pub fn from_pem(algorithm: Algorithm, private_key: &[u8]) -> Result<Self, JwtKeyError> {
+        let (encoding_key, parameters) = match algorithm {
+            Algorithm::RS256 => {
+                let encoding_key = EncodingKey::from_rsa_pem(private_key)?;
+                let parameters = Jwk::from_encoding_key(&encoding_key, algorithm)?.algorithm;
+                (encoding_key, parameters)
+            }
+            Algorithm::EdDSA => {
+                let encoding_key = EncodingKey::from_ed_pem(private_key)?;
+                let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(encoding_key.inner())
+                    .map_err(|_| JwtKeyError::InvalidKey(ErrorKind::InvalidEddsaKey.into()))?;
+                let parameters = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref()),
+                });
+                (encoding_key, parameters)
+            }
+            other => return Err(JwtKeyError::UnsupportedAlgorithm(format!("{:?}", other))),
+        };
+        let canonical = match &parameters {
+            AlgorithmParameters::RSA(rsa) => {
+                format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, rsa.e, rsa.n)
+            }
+            AlgorithmParameters::OctetKeyPair(okp) => {
+                format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, okp.x)
+            }
+            _ => unreachable!("only RSA and Ed25519 keys are supported"),
+        };
+        let kid = thumbprint(&canonical);
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(match algorithm {
+                    Algorithm::RS256 => KeyAlgorithm::RS256,
+                    _ => KeyAlgorithm::EdDSA,
+                }),
+                key_id: Some(kid.clone()),
+                ..Default::default()
+            },
+            algorithm: parameters,
+        };
+        Ok(Self {
+            kid,
+            algorithm,
+            encoding_key,
+            decoding_key: DecodingKey::from_jwk(&jwk)?,
+            jwk: Some(jwk),
+        })
+    }
+
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+}
+
+// RFC 7638: the base64url SHA-256 hash of the key's required members,
+// in lexicographic order and without whitespace.
+fn thumbprint(canonical: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+}
+
+/// One current key that new tokens are signed with, plus older keys whose
+/// tokens are still accepted until they expire. Retiring a key is a matter
+/// of dropping it from the keyring.
+pub struct JwtKeyring {
+    signing_key: JwtKey,
+    verification_keys: Vec<JwtKey>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "algorithm")]
+enum KeyringEntry {
+    #[serde(rename = "HS256")]
+    Hs256 { secret: String },
+    #[serde(rename = "RS256")]
+    Rs256 { private_key_file: PathBuf },
+    #[serde(rename = "EdDSA")]
+    EdDsa { private_key_file: PathBuf },
+}
+
+#[derive(Deserialize)]
+struct KeyringFile {
+    keys: Vec<KeyringEntry>,
+}
+
+impl JwtKeyring {
+    pub fn new(signing_key: JwtKey, verification_keys: Vec<JwtKey>) -> Self {
+        Self {
+            signing_key,
+            verification_keys,
+        }
+    }
+
+    /// Load a keyring from a JSON file listing its keys, newest first:
+    /// `{"keys": [{"algorithm": "EdDSA", "private_key_file": "new.pem"},
+    /// {"algorithm": "HS256", "secret": "..."}]}`. The first key signs new
+    /// tokens. Key file paths are relative to the keyring file.
+    pub fn from_file(path: &Path) -> Result<Self, JwtKeyError> {
+        let contents = std::fs::read_to_string(path).map_err(JwtKeyError::ReadFailed)?;
+        let file: KeyringFile = serde_json::from_str(&contents)
+            .map_err(|e| JwtKeyError::InvalidKeyring(e.to_string()))?;
+        let base = path.parent().unwrap_or(Path::new("."));
+        let read_pem =
+            |file: &Path| std::fs::read(base.join(file)).map_err(JwtKeyError::ReadFailed);
+        let mut keys = file
+            .keys
+            .into_iter()
+            .map(|entry| match entry {
+                KeyringEntry::Hs256 { secret } => Ok(JwtKey::from_secret(secret.as_bytes())),
+                KeyringEntry::Rs256 { private_key_file } => {
+                    JwtKey::from_pem(Algorithm::RS256, &read_pem(&private_key_file)?)
+                }
+                KeyringEntry::EdDsa { private_key_file } => {
+                    JwtKey::from_pem(Algorithm::EdDSA, &read_pem(&private_key_file)?)
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if keys.is_empty() {
+            return Err(JwtKeyError::InvalidKeyring(
+                "a keyring needs at least one key".to_owned(),
+            ));
+        }
+        let signing_key = keys.remove(0);
+        Ok(Self::new(signing_key, keys))
+    }
+
+    /// Load the keyring described by the settings: a keyring file, or a
+    /// keyring holding a single key.
+    pub fn from_source(source: &JwtKeySource) -> Result<Self, JwtKeyError> {
+        match source {
+            JwtKeySource::KeyringFile(path) => Self::from_file(path),
+            JwtKeySource::PrivateKeyFile { path, algorithm } => {
+                let private_key = std::fs::read(path).map_err(JwtKeyError::ReadFailed)?;
+                Ok(Self::new(
+                    JwtKey::from_pem(*algorithm, &private_key)?,
+                    vec![],
+                ))
+            }
+            JwtKeySource::Secret(secret) => {
+                Ok(Self::new(JwtKey::from_secret(secret.as_bytes()), vec![]))
+            }
+        }
+    }
+
+    pub fn signing_key(&self) -> &JwtKey {
+        &self.signing_key
+    }
+
+    /// Find an active key, current or not, by its `kid`.
+    pub fn find(&self, kid: &str) -> Option<&JwtKey> {
+        std::iter::once(&self.signing_key)
+            .chain(&self.verification_keys)
+            .find(|key| key.kid == kid)
+    }
+
+    /// Sign `claims` with the current key.
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        let key = &self.signing_key;
+        let mut header = Header::new(key.algorithm);
+        header.kid = Some(key.kid.clone());
+        encode(&header, claims, &key.encoding_key)
+    }
+
+    /// Verify `token` against the key named by its `kid`, rejecting keys
+    /// that are not (or no longer) in the keyring.
+    pub fn decode<T: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> Result<T, jsonwebtoken::errors::Error> {
+        self.decode_with(token, |_| {})
+    }
+
+    /// Like [`JwtKeyring::decode`], but only for tokens whose `aud` claim
+    /// names `audience`. Tokens with an `aud` claim are never accepted by
+    /// plain `decode`, so tokens issued for one purpose can't be passed off
+    /// as access tokens.
+    pub fn decode_for_audience<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        audience: &str,
+    ) -> Result<T, jsonwebtoken::errors::Error> {
+        self.decode_with(token, |validation| {
+            validation.set_audience(&[audience]);
+            validation.set_required_spec_claims(&["exp", "aud"]);
+        })
+    }
+
+    /// Like [`JwtKeyring::decode_for_audience`], but also requiring an
+    /// `iss` claim of `issuer` and honouring `nbf`. Times are allowed to
+    /// be off by `leeway`, for clock skew between services.
+    pub fn decode_from_issuer<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        issuer: &str,
+        audience: &str,
+        leeway: Duration,
+    ) -> Result<T, jsonwebtoken::errors::Error> {
+        self.decode_with(token, |validation| {
+            validation.set_issuer(&[issuer]);
+            validation.set_audience(&[audience]);
+            validation.set_required_spec_claims(&["exp", "nbf", "iss", "aud", "sub"]);
+            validation.validate_nbf = true;
+            validation.leeway = leeway.as_secs();
+        })
+    }
+
+    fn decode_with<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        configure: impl FnOnce(&mut Validation),
+    ) -> Result<T, jsonwebtoken::errors::Error> {
+        let key = match decode_header(token)?.kid {
+            Some(kid) => self.find(&kid).ok_or(ErrorKind::InvalidToken)?,
+            // tokens issued before keys had IDs can only be for the current key
+            None => &self.signing_key,
+        };
+        let mut validation = Validation::new(key.algorithm);
+        configure(&mut validation);
+        decode::<T>(token, &key.decoding_key, &validation).map(|data| data.claims)
+    }
+
+    /// The public keys to publish; shared secrets are left out.
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: std::iter::once(&self.signing_key)
+                .chain(&self.verification_keys)
+                .filter_map(|key| key.jwk.clone())
+                .collect(),
+        }
+    }
+}
+
+/// The keyring in use, shared between requests. Replacing it (e.g. after
+/// the keyring file has been edited) doesn't affect requests that already
+/// hold the old one.
+#[derive(Clone)]
+pub struct SharedKeyring(Arc<RwLock<Arc<JwtKeyring>>>);
+
+impl SharedKeyring {
+    pub fn new(keyring: JwtKeyring) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(keyring))))
+    }
+
+    pub fn current(&self) -> Arc<JwtKeyring> {
+        self.0.read().expect("JWT keyring lock poisoned").clone()
+    }
+
+    pub fn replace(&self, keyring: JwtKeyring) {
+        *self.0.write().expect("JWT keyring lock poisoned") = Arc::new(keyring);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSA_PRIVATE_KEY: &[u8] = include_bytes!("../../tests/fixtures/rsa_private.pem");
+    const ED25519_PRIVATE_KEY: &[u8] = include_bytes!("../../tests/fixtures/ed25519_private.pem");
+
+    fn rsa_key() -> JwtKey {
+        JwtKey::from_pem(Algorithm::RS256, RSA_PRIVATE_KEY).unwrap()
+    }
+
+    fn ed25519_key() -> JwtKey {
+        JwtKey::from_pem(Algorithm::EdDSA, ED25519_PRIVATE_KEY).unwrap()
+    }
+
+    fn claims() -> serde_json::Value {
+        serde_json::json!({ "sub": "test@example.com", "exp": 4_102_444_800u64 })
+    }
+
+    #[test]
+    fn test_secret_key_is_not_published() {
+        let keyring = JwtKeyring::new(JwtKey::from_secret(b"secret"), vec![]);
+        assert_eq!(keyring.signing_key().algorithm(), Algorithm::HS256);
+        assert!(keyring.jwks().keys.is_empty());
+    }
+
+    #[test]
+    fn test_rsa_key_publishes_public_jwk() {
+        let key = rsa_key();
+        let kid = key.kid().to_owned();
+        let jwks = JwtKeyring::new(key, vec![]).jwks();
+        assert_eq!(jwks.keys.len(), 1);
+        let jwk = jwks.find(&kid).expect("kid should match the JWK");
+        assert_eq!(jwk.common.key_algorithm, Some(KeyAlgorithm::RS256));
+        assert!(matches!(jwk.algorithm, AlgorithmParameters::RSA(_)));
+    }
+
+    #[test]
+    fn test_ed25519_key_publishes_public_jwk() {
+        let key = ed25519_key();
+        let kid = key.kid().to_owned();
+        let jwks = JwtKeyring::new(key, vec![]).jwks();
+        let jwk = jwks.find(&kid).expect("kid should match the JWK");
+        assert_eq!(jwk.common.key_algorithm, Some(KeyAlgorithm::EdDSA));
+        match &jwk.algorithm {
+            AlgorithmParameters::OctetKeyPair(okp) => {
+                assert_eq!(URL_SAFE_NO_PAD.decode(&okp.x).unwrap().len(), 32)
+            }
+            other => panic!("Expected an OKP key, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_tokens_verify_against_published_jwk() {
+        for key in [rsa_key(), ed25519_key()] {
+            let keyring = JwtKeyring::new(key, vec![]);
+            let token = keyring.encode(&claims()).unwrap();
+
+            // what another service would do with the published key set
+            let header = decode_header(&token).unwrap();
+            let jwk = keyring
+                .jwks()
+                .find(&header.kid.unwrap())
+                .cloned()
+                .expect("kid should be published");
+            let decoding_key = DecodingKey::from_jwk(&jwk).unwrap();
+            assert!(decode::<serde_json::Value>(
+                &token,
+                &decoding_key,
+                &Validation::new(header.alg)
+            )
+            .is_ok());
+        }
+    }
+
+    #[test]
+    fn test_kid_is_stable() {
+        assert_eq!(rsa_key().kid(), rsa_key().kid());
+        assert_eq!(
+            JwtKey::from_secret(b"secret").kid(),
+            JwtKey::from_secret(b"secret").kid()
+        );
+        assert_ne!(
+            JwtKey::from_secret(b"secret").kid(),
+            JwtKey::from_secret(b"other").kid()
+        );
+    }
+
+    #[test]
+    fn test_mismatched_key_type_fails() {
+        assert!(JwtKey::from_pem(Algorithm::EdDSA, RSA_PRIVATE_KEY).is_err());
+        assert!(JwtKey::from_pem(Algorithm::RS256, b"not a key").is_err());
+    }
+
+    #[test]
+    fn test_unsupported_algorithm_fails() {
+        assert!(matches!(
+            JwtKey::from_pem(Algorithm::ES256, RSA_PRIVATE_KEY),
+            Err(JwtKeyError::UnsupportedAlgorithm(_))
+        ));
+    }
+
+    #[test]
+    fn test_rotated_keys_still_verify_old_tokens() {
+        let old_keyring = JwtKeyring::new(JwtKey::from_secret(b"secret"), vec![]);
+        let old_token = old_keyring.encode(&claims()).unwrap();
+
+        let keyring = JwtKeyring::new(ed25519_key(), vec![JwtKey::from_secret(b"secret")]);
+        let new_token = keyring.encode(&claims()).unwrap();
+        assert_eq!(
+            decode_header(&new_token).unwrap().kid.as_deref(),
+            Some(keyring.signing_key().kid())
+        );
+        assert!(keyring.decode::<serde_json::Value>(&old_token).is_ok());
+        assert!(keyring.decode::<serde_json::Value>(&new_token).is_ok());
+        // the secret is still accepted, but never published
+        assert_eq!(keyring.jwks().keys.len(), 1);
+    }
+
+    #[test]
+    fn test_retired_keys_are_rejected() {
+        let old_keyring = JwtKeyring::new(rsa_key(), vec![]);
+        let old_token = old_keyring.encode(&claims()).unwrap();
+
+        let keyring = JwtKeyring::new(ed25519_key(), vec![]);
+        assert_eq!(
+            keyring
+                .decode::<serde_json::Value>(&old_token)
+                .expect_err("Retired key should be rejected")
+                .kind(),
+            &ErrorKind::InvalidToken
+        );
+    }
+
+    #[test]
+    fn test_forged_kid_is_rejected() {
+        // a token signed with some other secret but naming a real key
+        let keyring = JwtKeyring::new(JwtKey::from_secret(b"secret"), vec![]);
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some(keyring.signing_key().kid().to_owned());
+        let token = encode(&header, &claims(), &EncodingKey::from_secret(b"forged")).unwrap();
+        assert!(keyring.decode::<serde_json::Value>(&token).is_err());
+    }
+
+    #[test]
+    fn test_tokens_without_kid_use_signing_key() {
+        let keyring = JwtKeyring::new(JwtKey::from_secret(b"secret"), vec![]);
+        let token = encode(
+            &Header::default(),
+            &claims(),
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        assert!(keyring.decode::<serde_json::Value>(&token).is_ok());
+    }
+
+    #[test]
+    fn test_audience_tokens_only_decode_for_their_audience() {
+        let keyring = JwtKeyring::new(JwtKey::from_secret(b"secret"), vec![]);
+        let mut reset_claims = claims();
+        reset_claims["aud"] = "password-reset".into();
+        let token = keyring.encode(&reset_claims).unwrap();
+        assert!(keyring
+            .decode_for_audience::<serde_json::Value>(&token, "password-reset")
+            .is_ok());
+        assert!(keyring
+            .decode_for_audience::<serde_json::Value>(&token, "something-else")
+            .is_err());
+        assert!(keyring.decode::<serde_json::Value>(&token).is_err());
+
+        // and tokens without an audience don't pass for ones with it
+        let token = keyring.encode(&claims()).unwrap();
+        assert!(keyring
+            .decode_for_audience::<serde_json::Value>(&token, "password-reset")
+            .is_err());
+    }
+
+    #[test]
+    fn test_issued_tokens_only_decode_from_their_issuer() {
+        let keyring = JwtKeyring::new(JwtKey::from_secret(b"secret"), vec![]);
+        let decode = |claims: &serde_json::Value, leeway: u64| {
+            let token = keyring.encode(claims).unwrap();
+            keyring.decode_from_issuer::<serde_json::Value>(
+                &token,
+                "https://auth.example.com",
+                "app",
+                Duration::from_secs(leeway),
+            )
+        };
+        let now = chrono::Utc::now().timestamp();
+        let mut access_claims = claims();
+        access_claims["iss"] = "https://auth.example.com".into();
+        access_claims["aud"] = "app".into();
+        access_claims["nbf"] = now.into();
+        assert!(decode(&access_claims, 0).is_ok());
+
+        let mut other = access_claims.clone();
+        other["iss"] = "https://evil.example.com".into();
+        assert!(decode(&other, 0).is_err());
+        let mut other = access_claims.clone();
+        other["aud"] = "password-reset".into();
+        assert!(decode(&other, 0).is_err());
+        let mut other = access_claims.clone();
+        other.as_object_mut().unwrap().remove("iss");
+        assert!(decode(&other, 0).is_err());
+
+        // not valid yet, unless the clocks may be that far apart
+        let mut early = access_claims.clone();
+        early["nbf"] = (now + 30).into();
+        assert!(decode(&early, 0).is_err());
+        assert!(decode(&early, 60).is_ok());
+    }
+
+    #[test]
+    fn test_keyring_file_lists_keys_newest_first() {
+        let keyring = JwtKeyring::from_file(Path::new("tests/fixtures/keyring.json")).unwrap();
+        assert_eq!(keyring.signing_key().algorithm(), Algorithm::EdDSA);
+        assert_eq!(keyring.signing_key().kid(), ed25519_key().kid());
+        assert!(keyring.find(rsa_key().kid()).is_some());
+        assert!(keyring.find(JwtKey::from_secret(b"secret").kid()).is_some());
+        assert_eq!(keyring.jwks().keys.len(), 2);
+    }
+
+    #[test]
+    fn test_replaced_keyring_is_used_by_all_handles() {
+        let shared = SharedKeyring::new(JwtKeyring::new(rsa_key(), vec![]));
+        let handle = shared.clone();
+        let before = handle.current();
+        shared.replace(JwtKeyring::new(ed25519_key(), vec![]));
+        assert_eq!(handle.current().signing_key().kid(), ed25519_key().kid());
+        // whoever held on to the old keyring keeps it
+        assert_eq!(before.signing_key().kid(), rsa_key().kid());
+    }
+
+    #[test]
+    fn test_keyring_from_source() {
+        let source = JwtKeySource::PrivateKeyFile {
+            path: "tests/fixtures/ed25519_private.pem".into(),
+            algorithm: Algorithm::EdDSA,
+        };
+        let keyring = JwtKeyring::from_source(&source).unwrap();
+        assert_eq!(keyring.signing_key().kid(), ed25519_key().kid());
+        let keyring = JwtKeyring::from_source(&JwtKeySource::Secret("secret".into())).unwrap();
+        assert_eq!(keyring.signing_key().algorithm(), Algorithm::HS256);
+    }
+
+    #[test]
+    fn test_empty_keyring_file_fails() {
+        let path = std::env::temp_dir().join(format!("keyring-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"{"keys": []}"#).unwrap();
+        assert!(matches!(
+            JwtKeyring::from_file(&path),
+            Err(JwtKeyError::InvalidKeyring(_))
+        ));
+        std::fs::remove_file(path).ok();
+    }
+}
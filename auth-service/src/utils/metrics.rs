@@ -0,0 +1,198 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use prometheus_client::{
+    encoding::{text::encode, EncodeLabelSet},
+    metrics::{
+        counter::Counter,
+        family::Family,
+        gauge::Gauge,
+        histogram::{exponential_buckets, Histogram},
+    },
+    registry::{Registry, Unit},
+};
+use std::{sync::Arc, time::Duration};
+use tokio::time::Instant;
+
+use crate::domain::AuthApiError;
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RequestLabels {
+    method: String,
+    route: String,
+    status: u16,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RouteLabels {
+    method: String,
+    route: String,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ErrorLabels {
+    error: String,
+}
+
+type HistogramFamily<L> = Family<L, Histogram, fn() -> Histogram>;
+
+// 5ms up to ~10s: logins are slow on purpose, as password hashing is
+fn latency_histogram() -> Histogram {
+    Histogram::new(exponential_buckets(0.005, 2.0, 12))
+}
+
+/// What `/metrics` reports, in the Prometheus text format.
+pub struct Metrics {
+    registry: Registry,
+    http_requests: Family<RequestLabels, Counter>,
+    http_request_duration: HistogramFamily<RouteLabels>,
+    api_errors: Family<ErrorLabels, Counter>,
+    banned_tokens: Gauge,
+    two_fa_codes: Gauge,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let mut registry = Registry::default();
+        let http_requests = Family::<RequestLabels, Counter>::default();
+        registry.register(
+            "http_requests",
+            "Requests handled, by route and response status",
+            http_requests.clone(),
+        );
+        let http_request_duration =
+            HistogramFamily::<RouteLabels>::new_with_constructor(latency_histogram);
+        registry.register_with_unit(
+            "http_request_duration",
+            "Time taken to handle requests, by route",
+            Unit::Seconds,
+            http_request_duration.clone(),
+        );
+        let api_errors = Family::<ErrorLabels, Counter>::default();
+        registry.register(
+            "auth_api_errors",
+            "Errors returned to clients, by kind",
+            api_errors.clone(),
+        );
+        let banned_tokens = Gauge::default();
+        registry.register(
+            "banned_tokens",
+            "Tokens held by the banned token store",
+            banned_tokens.clone(),
+        );
+        let two_fa_codes = Gauge::default();
+        registry.register(
+            "two_fa_codes",
+            "2FA codes waiting to be verified",
+            two_fa_codes.clone(),
+        );
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            api_errors,
+            banned_tokens,
+            two_fa_codes,
+        }
+    }
+}
+
+impl Metrics {
+    pub fn observe_request(
+        &self,
+        method: &Method,
+        route: &str,
+        status: StatusCode,
+        elapsed: Duration,
+    ) {
+        self.http_requests
+            .get_or_create(&RequestLabels {
+                method: method.to_string(),
+                route: route.to_owned(),
+                status: status.as_u16(),
+            })
+            .inc();
+        self.http_request_duration
+            .get_or_create(&RouteLabels {
+                method: method.to_string(),
+                route: route.to_owned(),
+            })
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_error(&self, error: &AuthApiError) {
+        self.api_errors
+            .get_or_create(&ErrorLabels {
+                error: format!("{:?}", error),
+            })
+            .inc();
+    }
+
+    pub fn set_banned_tokens(&self, size: usize) {
+        self.banned_tokens.set(size as i64);
+    }
+
+    pub fn set_two_fa_codes(&self, size: usize) {
+        self.two_fa_codes.set(size as i64);
+    }
+
+    pub fn encode(&self) -> Result<String, std::fmt::Error> {
+        let mut body = String::new();
+        encode(&mut body, &self.registry)?;
+        Ok(body)
+    }
+}
+
+// Count and time requests to each route. Routes are labelled by their
+// pattern rather than the actual path, to keep the number of series down.
+// `AuthApiError` leaves a copy of itself on its response to be counted.
+pub async fn track_metrics(
+    State(metrics): State<Arc<Metrics>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let started_at = Instant::now();
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_default();
+    let response = next.run(request).await;
+    metrics.observe_request(&method, &route, response.status(), started_at.elapsed());
+    if let Some(error) = response.extensions().get::<AuthApiError>() {
+        metrics.observe_error(error);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_requests_and_errors() {
+        let metrics = Metrics::default();
+        metrics.observe_request(
+            &Method::POST,
+            "/login",
+            StatusCode::UNAUTHORIZED,
+            Duration::from_millis(20),
+        );
+        metrics.observe_error(&AuthApiError::IncorrectCredentials);
+        metrics.set_banned_tokens(3);
+        let body = metrics.encode().unwrap();
+        assert!(
+            body.contains(r#"http_requests_total{method="POST",route="/login",status="401"} 1"#)
+        );
+        assert!(body.contains(
+            r#"http_request_duration_seconds_bucket{le="0.04",method="POST",route="/login"} 1"#
+        ));
+        assert!(body.contains(r#"auth_api_errors_total{error="IncorrectCredentials"} 1"#));
+        assert!(body.contains("banned_tokens 3"));
+        assert!(body.contains("two_fa_codes 0"));
+    }
+}
@@ -1,2 +1,6 @@
 pub mod auth;
 pub mod constants;
+pub mod jwt_key;
+pub mod metrics;
+pub mod rate_limit;
+pub mod telemetry;
@@ -0,0 +1,93 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, Request, State},
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::{net::SocketAddr, time::Duration};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthApiError, RateLimit, RateLimitDecision},
+};
+
+// Far more than any of the rate limited routes accept
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+#[derive(Deserialize)]
+struct Target {
+    email: Option<String>,
+}
+
+// Limit requests by the client's IP, and by the email address they are
+// for, so that guessing one user's password from many addresses is as
+// slow as guessing everyone's from one. Limiter errors let the request
+// through: an outage of the limiter shouldn't lock everyone out.
+pub async fn rate_limit(
+    State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let settings = &state.settings;
+    let key = format!("ip:{}", client.ip());
+    if let Some(response) = check(&state, &key, settings.ip_rate_limit).await {
+        return response;
+    }
+
+    // The email is in the JSON body, which has to be buffered to read it
+    // and then handed on to the route.
+    let (parts, body) = request.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_BODY_BYTES).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+    // a malformed body is for the route to reject
+    if let Ok(Target { email: Some(email) }) = serde_json::from_slice(&bytes) {
+        let key = format!("email:{}", email.trim().to_lowercase());
+        if let Some(response) = check(&state, &key, settings.email_rate_limit).await {
+            return response;
+        }
+    }
+    next.run(Request::from_parts(parts, Body::from(bytes)))
+        .await
+}
+
+// Count a hit against `key`, returning the response to send instead of
+// running the route if it is over `limit`.
+async fn check(state: &AppState, key: &str, limit: RateLimit) -> Option<Response> {
+    match state.rate_limiter.hit(key, limit).await {
+        Ok(RateLimitDecision::Allowed) => None,
+        Ok(RateLimitDecision::Limited { retry_after }) => {
+            let mut response = AuthApiError::TooManyRequests.into_response();
+            response.headers_mut().insert(
+                RETRY_AFTER,
+                HeaderValue::from(retry_after_seconds(retry_after)),
+            );
+            Some(response)
+        }
+        Err(e) => {
+            tracing::warn!(error = ?e, "Failed to check rate limit");
+            None
+        }
+    }
+}
+
+// `Retry-After` is in whole seconds; round up, so clients don't come back
+// just too early.
+fn retry_after_seconds(retry_after: Duration) -> u64 {
+    retry_after.as_millis().div_ceil(1000).max(1) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_after_rounds_up_to_whole_seconds() {
+        assert_eq!(retry_after_seconds(Duration::ZERO), 1);
+        assert_eq!(retry_after_seconds(Duration::from_millis(1001)), 2);
+        assert_eq!(retry_after_seconds(Duration::from_secs(30)), 30);
+    }
+}
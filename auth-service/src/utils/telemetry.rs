@@ -0,0 +1,53 @@
+use axum::{body::Body, http::Request, middleware::Next, response::Response};
+use tracing::Span;
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+
+use crate::{settings::LogFormat, utils::constants::REQUEST_ID_HEADER};
+
+/// Send log events to stdout in `format`. Which events are logged is
+/// controlled with `RUST_LOG` (e.g. `auth_service=debug,tower_http=info`),
+/// and defaults to `info`.
+pub fn init_tracing(format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    match format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+}
+
+/// Drop a request ID sent by the caller unless it is a UUID in the form
+/// this service and app-service make them, so that the request is given a
+/// fresh one instead. Otherwise anyone could put what they liked in our
+/// logs, or pass off their requests as someone else's.
+pub async fn drop_malformed_request_id(mut request: Request<Body>, next: Next) -> Response {
+    let well_formed = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .is_some_and(|id| {
+            Uuid::try_parse(id).is_ok_and(|uuid| uuid.hyphenated().to_string() == id)
+        });
+    if !well_formed {
+        request.headers_mut().remove(REQUEST_ID_HEADER);
+    }
+    next.run(request).await
+}
+
+// One span per request, carrying the request ID so that everything logged
+// while handling the request can be found by it. The query string is left
+// out, since it is no place to log secrets from.
+pub fn make_request_span(request: &Request<Body>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        path = %request.uri().path(),
+        request_id,
+    )
+}
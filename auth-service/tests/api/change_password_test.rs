@@ -0,0 +1,140 @@
+use crate::test_helpers::{get_random_email, TestApp};
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+use serde_json::json;
+
+async fn sign_up_and_log_in(app: &TestApp) -> (String, String) {
+    let email = get_random_email();
+    let response = app
+        .post_signup(&json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let response = app
+        .post_login(&json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    (email, get_auth_token(&response))
+}
+
+fn get_auth_token(response: &reqwest::Response) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned()
+}
+
+async fn log_in(app: &TestApp, email: &str, password: &str) -> u16 {
+    app.post_login(&json!({
+        "email": email,
+        "password": password,
+    }))
+    .await
+    .status()
+    .as_u16()
+}
+
+async fn verify_token(app: &TestApp, token: &str) -> u16 {
+    app.post_verify_token(&json!({ "token": token }))
+        .await
+        .status()
+        .as_u16()
+}
+
+#[tokio::test]
+async fn should_return_200_and_change_password() {
+    let app = TestApp::new().await;
+    let (email, _) = sign_up_and_log_in(&app).await;
+
+    let response = app
+        .post_change_password(&json!({
+            "currentPassword": "password123",
+            "newPassword": "new_password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(log_in(&app, &email, "password123").await, 401);
+    assert_eq!(log_in(&app, &email, "new_password123").await, 200);
+}
+
+#[tokio::test]
+async fn should_invalidate_every_other_token() {
+    let app = TestApp::new().await;
+    let (email, login_token) = sign_up_and_log_in(&app).await;
+    let user_id = app.get_user_id(&email.parse().unwrap()).await;
+    // issued within the same second as the change, most likely
+    let other_token = app.generate_auth_token(&user_id);
+
+    let response = app
+        .post_change_password(&json!({
+            "currentPassword": "password123",
+            "newPassword": "new_password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let new_token = get_auth_token(&response);
+
+    assert_eq!(verify_token(&app, &login_token).await, 401);
+    assert_eq!(verify_token(&app, &other_token.to_string()).await, 401);
+    assert_eq!(verify_token(&app, &new_token).await, 200);
+    // the session carries on with the new tokens
+    assert_eq!(app.post_refresh().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_401_if_current_password_is_wrong() {
+    let app = TestApp::new().await;
+    let (email, _) = sign_up_and_log_in(&app).await;
+
+    let response = app
+        .post_change_password(&json!({
+            "currentPassword": "wrong-password",
+            "newPassword": "new_password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(log_in(&app, &email, "password123").await, 200);
+}
+
+#[tokio::test]
+async fn should_return_400_if_new_password_is_invalid() {
+    let app = TestApp::new().await;
+    sign_up_and_log_in(&app).await;
+
+    let response = app
+        .post_change_password(&json!({
+            "currentPassword": "password123",
+            "newPassword": "short",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_400_if_not_logged_in() {
+    let app = TestApp::new().await;
+    let response = app
+        .post_change_password(&json!({
+            "currentPassword": "password123",
+            "newPassword": "new_password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+    sign_up_and_log_in(&app).await;
+    let response = app
+        .post_change_password(&json!({ "newPassword": "new_password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+}
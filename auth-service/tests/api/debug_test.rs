@@ -0,0 +1,35 @@
+use crate::test_helpers::{TestApp, ADMIN_TOKEN};
+use auth_service::routes::BannedTokenStatsResponse;
+
+#[tokio::test]
+async fn banned_token_stats_reports_store_size() {
+    let app = TestApp::new().await;
+    let response = app.get_banned_token_stats(Some(ADMIN_TOKEN)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let stats = response
+        .json::<BannedTokenStatsResponse>()
+        .await
+        .expect("Could not deserialize body to BannedTokenStatsResponse");
+    assert_eq!(stats.size, 0);
+
+    app.create_user_and_log_in().await;
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let stats = app
+        .get_banned_token_stats(Some(ADMIN_TOKEN))
+        .await
+        .json::<BannedTokenStatsResponse>()
+        .await
+        .expect("Could not deserialize body to BannedTokenStatsResponse");
+    assert_eq!(stats.size, 1);
+}
+
+#[tokio::test]
+async fn banned_token_stats_require_admin_token() {
+    let app = TestApp::new().await;
+    let response = app.get_banned_token_stats(None).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app.get_banned_token_stats(Some("wrong-token")).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,153 @@
+use crate::test_helpers::{get_random_email, TestApp};
+use auth_service::{
+    domain::{AccountEvent, Email, UserStoreError},
+    utils::constants::JWT_COOKIE_NAME,
+};
+use reqwest::Url;
+use serde_json::json;
+
+async fn sign_up(app: &TestApp, requires_2fa: bool) -> String {
+    let email = get_random_email();
+    let response = app
+        .post_signup(&json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": requires_2fa,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    email
+}
+
+async fn log_in(app: &TestApp, email: &str) -> reqwest::Response {
+    app.post_login(&json!({
+        "email": email,
+        "password": "password123",
+    }))
+    .await
+}
+
+fn get_auth_token(response: &reqwest::Response) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned()
+}
+
+#[tokio::test]
+async fn should_return_200_and_delete_account() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app, false).await;
+    let user_id = app.get_user_id(&email.parse().unwrap()).await;
+    let response = log_in(&app, &email).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let token = get_auth_token(&response);
+
+    let response = app
+        .delete_account(&json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .cookies()
+        .any(|cookie| cookie.name() == JWT_COOKIE_NAME && cookie.value().is_empty()));
+
+    let result = app
+        .state
+        .user_store
+        .read()
+        .await
+        .get_user(&email.parse().unwrap())
+        .await;
+    assert_eq!(result.err(), Some(UserStoreError::UserNotFound));
+    let response = app.post_verify_token(&json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+    // the refresh cookie was removed along with the JWT cookie
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        app.account_event_hook.events().await,
+        vec![AccountEvent::Deleted { user_id }]
+    );
+}
+
+#[tokio::test]
+async fn should_clear_pending_2fa_code() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app, true).await;
+    let response = log_in(&app, &email).await;
+    assert_eq!(response.status().as_u16(), 206);
+    let email: Email = email.parse().unwrap();
+    assert!(app.two_fa_code_store.read().await.get(&email).await.is_ok());
+
+    // a session from an earlier login
+    let token = app.generate_auth_token(&app.get_user_id(&email).await);
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; Path=/", JWT_COOKIE_NAME, token),
+        &Url::parse(&app.address).unwrap(),
+    );
+    let response = app
+        .delete_account(&json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(app
+        .two_fa_code_store
+        .read()
+        .await
+        .get(&email)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn should_return_401_if_wrong_password() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app, false).await;
+    assert_eq!(log_in(&app, &email).await.status().as_u16(), 200);
+
+    let response = app
+        .delete_account(&json!({ "password": "wrong_password" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(log_in(&app, &email).await.status().as_u16(), 200);
+    assert!(app.account_event_hook.events().await.is_empty());
+}
+
+#[tokio::test]
+async fn should_return_400_if_no_jwt_cookie() {
+    let app = TestApp::new().await;
+    let response = app
+        .delete_account(&json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_allow_signing_up_again() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app, false).await;
+    assert_eq!(log_in(&app, &email).await.status().as_u16(), 200);
+    let response = app
+        .delete_account(&json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_signup(&json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app, false).await;
+    assert_eq!(log_in(&app, &email).await.status().as_u16(), 200);
+    let response = app.delete_account(&json!({ "pass": "password123" })).await;
+    assert_eq!(response.status().as_u16(), 422);
+}
@@ -0,0 +1,147 @@
+use crate::test_helpers::{get_random_email, TestApp, ADMIN_TOKEN, INTROSPECTION_TOKEN};
+use auth_service::{
+    domain::{Email, UserId},
+    routes::IntrospectResponse,
+    utils::constants::JWT_COOKIE_NAME,
+};
+use serde_json::{json, Value};
+
+fn get_auth_token(response: &reqwest::Response) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned()
+}
+
+async fn introspect(app: &TestApp, token: &str) -> IntrospectResponse {
+    let response = app
+        .post_introspect(Some(INTROSPECTION_TOKEN), &json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json()
+        .await
+        .expect("Could not deserialize response body to IntrospectResponse")
+}
+
+#[tokio::test]
+async fn should_describe_active_token() {
+    let app = TestApp::new().await;
+    let response = app.create_user_and_log_in().await;
+    let token = get_auth_token(&response);
+
+    let response = app
+        .post_introspect(Some(INTROSPECTION_TOKEN), &json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["active"], true);
+    // the user's ID, not their email address
+    assert!(body["sub"].as_str().unwrap().parse::<UserId>().is_ok());
+    assert_eq!(body["iss"], app.state.settings.token_policy.issuer);
+    assert_eq!(body["aud"], app.state.settings.token_policy.audience);
+    assert_eq!(body["nbf"], body["iat"]);
+    assert!(body["exp"].as_u64().unwrap() > body["iat"].as_u64().unwrap());
+    assert!(!body["jti"].as_str().unwrap().is_empty());
+    assert_eq!(body["amr"], json!(["pwd"]));
+}
+
+#[tokio::test]
+async fn should_list_second_factor_after_2fa() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let response = app
+        .post_signup(&json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let response = app
+        .post_login(&json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    let (login_attempt_id, code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get(&email.parse::<Email>().unwrap())
+        .await
+        .unwrap();
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id.as_ref(),
+            "2FACode": code.as_ref(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = introspect(&app, &get_auth_token(&response)).await;
+    let user_id = app.get_user_id(&email.parse().unwrap()).await;
+    assert_eq!(body.sub, Some(user_id.to_string()));
+    assert_eq!(
+        serde_json::to_value(body.amr).unwrap(),
+        json!(["pwd", "email"])
+    );
+
+    // a refreshed token says how the session began
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = introspect(&app, &get_auth_token(&response)).await;
+    assert_eq!(
+        serde_json::to_value(body.amr).unwrap(),
+        json!(["pwd", "email"])
+    );
+}
+
+#[tokio::test]
+async fn should_report_only_inactive_for_bad_tokens() {
+    let app = TestApp::new().await;
+    let response = app
+        .post_introspect(Some(INTROSPECTION_TOKEN), &json!({ "token": "nope" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body, json!({ "active": false }));
+}
+
+#[tokio::test]
+async fn should_report_logged_out_token_inactive() {
+    let app = TestApp::new().await;
+    let response = app.create_user_and_log_in().await;
+    let token = get_auth_token(&response);
+    assert!(introspect(&app, &token).await.active);
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = introspect(&app, &token).await;
+    assert!(!body.active);
+    assert_eq!(body.sub, None);
+}
+
+#[tokio::test]
+async fn should_require_introspection_token() {
+    let app = TestApp::new().await;
+    let body = json!({ "token": app.generate_auth_token(&UserId::default()) });
+
+    let response = app.post_introspect(None, &body).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app.post_introspect(Some("wrong-token"), &body).await;
+    assert_eq!(response.status().as_u16(), 401);
+    // the admin token is for the admin routes only
+    let response = app.post_introspect(Some(ADMIN_TOKEN), &body).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+    let response = app
+        .post_introspect(Some(INTROSPECTION_TOKEN), &json!({ "nonsense": "foo" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+}
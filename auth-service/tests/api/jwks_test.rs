@@ -0,0 +1,47 @@
+use crate::test_helpers::TestApp;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+
+#[tokio::test]
+async fn should_publish_keys_that_verify_issued_tokens() {
+    let app = TestApp::new().await;
+    let response = app.get_jwks().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let jwks = response
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize body to JwkSet");
+
+    let response = app.create_user_and_log_in().await;
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+    let header = decode_header(&token).expect("Invalid token header");
+    let kid = header.kid.expect("Token has no kid");
+    match jwks.find(&kid) {
+        // tokens signed with a shared secret can't be verified elsewhere
+        None => assert_eq!(header.alg, Algorithm::HS256),
+        Some(jwk) => {
+            let key = DecodingKey::from_jwk(jwk).expect("Invalid JWK");
+            // as another service would, knowing who issues tokens for it
+            let policy = &app.state.settings.token_policy;
+            let mut validation = Validation::new(header.alg);
+            validation.set_issuer(&[&policy.issuer]);
+            validation.set_audience(&[&policy.audience]);
+            decode::<serde_json::Value>(&token, &key, &validation)
+                .expect("Token does not verify against the published key");
+        }
+    }
+    // and shared secrets must never be published
+    assert!(jwks
+        .keys
+        .iter()
+        .all(|jwk| !matches!(jwk.algorithm, AlgorithmParameters::OctetKey(_))));
+}
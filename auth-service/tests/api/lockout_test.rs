@@ -0,0 +1,156 @@
+use crate::test_helpers::{get_random_email, TestApp, ADMIN_TOKEN};
+use auth_service::domain::AccountEvent;
+use serde_json::{json, Value};
+
+async fn sign_up(app: &TestApp) -> String {
+    let email = get_random_email();
+    let response = app
+        .post_signup(&json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    email
+}
+
+async fn log_in(app: &TestApp, email: &str, password: &str) -> u16 {
+    app.post_login(&json!({
+        "email": email,
+        "password": password,
+    }))
+    .await
+    .status()
+    .as_u16()
+}
+
+async fn lock_out(app: &TestApp, email: &str) {
+    let max_failed_attempts = app.state.settings.lockout.max_failed_attempts;
+    for _ in 1..max_failed_attempts {
+        assert_eq!(log_in(app, email, "wrong-password").await, 401);
+    }
+    assert_eq!(log_in(app, email, "wrong-password").await, 423);
+}
+
+#[tokio::test]
+async fn should_return_423_after_repeated_failed_logins() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    lock_out(&app, &email).await;
+
+    // even the right password is refused while the account is locked
+    let response = app
+        .post_login(&json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 423);
+    let body: Value = response.json().await.expect("Failed to parse error");
+    assert_eq!(body["error"], "Account locked");
+
+    assert_eq!(
+        app.account_event_hook.events().await,
+        vec![AccountEvent::Locked {
+            user_id: app.get_user_id(&email.parse().unwrap()).await,
+            locked_for_seconds: app.state.settings.lockout.base_lockout.as_secs(),
+        }]
+    );
+}
+
+#[tokio::test]
+async fn should_lock_unknown_emails_without_an_event() {
+    let app = TestApp::new().await;
+    // locked like a real account, so as not to tell the two apart
+    lock_out(&app, &get_random_email()).await;
+    assert!(app.account_event_hook.events().await.is_empty());
+}
+
+#[tokio::test]
+async fn should_forget_failed_logins_after_a_successful_one() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    let max_failed_attempts = app.state.settings.lockout.max_failed_attempts;
+    for _ in 1..max_failed_attempts {
+        assert_eq!(log_in(&app, &email, "wrong-password").await, 401);
+    }
+    assert_eq!(log_in(&app, &email, "password123").await, 200);
+    for _ in 1..max_failed_attempts {
+        assert_eq!(log_in(&app, &email, "wrong-password").await, 401);
+    }
+    assert!(app.account_event_hook.events().await.is_empty());
+}
+
+#[tokio::test]
+async fn should_count_wrong_passwords_given_while_logged_in() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    assert_eq!(log_in(&app, &email, "password123").await, 200);
+    // replacing the second factor takes the password once 2FA is on
+    let response = app
+        .post_two_fa_method(&json!({ "2FAMethod": "email" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(app.post_totp_enroll().await.status().as_u16(), 200);
+
+    let max_failed_attempts = app.state.settings.lockout.max_failed_attempts;
+    let mut statuses = Vec::new();
+    for attempt in 0..max_failed_attempts {
+        let response = match attempt % 3 {
+            0 => {
+                app.post_change_password(&json!({
+                    "currentPassword": "wrong-password",
+                    "newPassword": "new_password123",
+                }))
+                .await
+            }
+            1 => {
+                app.delete_account(&json!({ "password": "wrong-password" }))
+                    .await
+            }
+            _ => {
+                app.post_totp_confirm(&json!({
+                    "2FACode": "123456",
+                    "currentPassword": "wrong-password",
+                }))
+                .await
+            }
+        };
+        statuses.push(response.status().as_u16());
+    }
+    assert_eq!(statuses.pop(), Some(423));
+    assert!(statuses.iter().all(|&status| status == 401));
+
+    // the account is locked for logins and these routes alike
+    assert_eq!(log_in(&app, &email, "password123").await, 423);
+    let response = app
+        .delete_account(&json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 423);
+    assert_eq!(
+        app.account_event_hook.events().await,
+        vec![AccountEvent::Locked {
+            user_id: app.get_user_id(&email.parse().unwrap()).await,
+            locked_for_seconds: app.state.settings.lockout.base_lockout.as_secs(),
+        }]
+    );
+}
+
+#[tokio::test]
+async fn should_unlock_account_with_admin_token() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    lock_out(&app, &email).await;
+    let body = json!({ "email": email });
+
+    let response = app.post_unlock_account(None, &body).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app.post_unlock_account(Some("wrong-token"), &body).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(log_in(&app, &email, "password123").await, 423);
+
+    let response = app.post_unlock_account(Some(ADMIN_TOKEN), &body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(log_in(&app, &email, "password123").await, 200);
+}
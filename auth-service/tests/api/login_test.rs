@@ -1,7 +1,11 @@
 use std::str::FromStr;
+use std::time::Duration;
 
 use auth_service::{
-    domain::Email, routes::TwoFactorAuthResponse, utils::constants::JWT_COOKIE_NAME,
+    domain::{Email, LoginAttemptId},
+    routes::TwoFactorAuthResponse,
+    services::MockEmailClient,
+    utils::constants::JWT_COOKIE_NAME,
 };
 use serde_json::json;
 
@@ -73,6 +77,137 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
     assert_eq!(json_body.message, String::from("2FA required"));
 }
 
+#[tokio::test]
+async fn should_return_stored_login_attempt_id_if_2fa_enabled() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let signup_body = json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true,
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = json!({
+        "email": email,
+        "password": "password123",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize body to TwoFactorAuthResponse");
+
+    let (login_attempt_id, _) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get(&Email::from_str(&email).expect("Could not parse email"))
+        .await
+        .expect("No 2FA code stored for login attempt");
+    assert_eq!(
+        json_body
+            .login_attempt_id
+            .parse::<LoginAttemptId>()
+            .expect("Could not parse login attempt ID"),
+        login_attempt_id
+    );
+}
+
+#[tokio::test]
+async fn should_email_2fa_code_if_2fa_enabled() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let signup_body = json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true,
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = json!({
+        "email": email,
+        "password": "password123",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let email = Email::from_str(&email).expect("Could not parse email");
+    let (_, code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get(&email)
+        .await
+        .expect("No 2FA code stored for login attempt");
+    // sent after the verification email from signup
+    let sent = app.email_client.sent_emails().await;
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[1].recipient, email);
+    assert!(sent[1].content.contains(code.as_ref()));
+}
+
+#[tokio::test]
+async fn should_return_500_if_2fa_code_cannot_be_sent() {
+    let app = TestApp::with_email_client(MockEmailClient::failing()).await;
+    let email = get_random_email();
+    let signup_body = json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true,
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = json!({
+        "email": email,
+        "password": "password123",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 500);
+    assert!(app
+        .two_fa_code_store
+        .read()
+        .await
+        .get(&Email::from_str(&email).expect("Could not parse email"))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn should_not_lock_2fa_codes_while_sending_one() {
+    let app = TestApp::with_email_client(MockEmailClient::slow(Duration::from_secs(2))).await;
+    let email = get_random_email();
+    let signup_body = json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true,
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = json!({
+        "email": email,
+        "password": "password123",
+    });
+    let check_store = async {
+        // by now the login is waiting on the mail relay
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let other: Email = get_random_email().parse().unwrap();
+        tokio::time::timeout(Duration::from_millis(500), app.two_fa_code_store.write())
+            .await
+            .expect("2FA code store should not be locked while an email is sent")
+            .remove(&other)
+            .await
+            .ok();
+    };
+    let (response, ()) = tokio::join!(app.post_login(&login_body), check_store);
+    assert_eq!(response.status().as_u16(), 206);
+}
+
 #[tokio::test]
 async fn should_return_422_if_malformed_credentials() {
     let app = TestApp::new().await;
@@ -92,9 +227,10 @@ async fn should_return_400_if_invalid_credentials() {
 #[tokio::test]
 async fn should_return_401_if_incorrect_credentials() {
     let app = TestApp::new().await;
+    let email = get_random_email();
     let response = app
         .post_signup(&json!({
-            "email": "user@example.com",
+            "email": email,
             "password": "password",
             "requires2FA": false,
         }))
@@ -102,11 +238,20 @@ async fn should_return_401_if_incorrect_credentials() {
     assert_eq!(response.status().as_u16(), 201);
 
     let response = app
-        .post_login(&serde_json::json!({ "email": "user@example.com", "password": "incorrect" }))
+        .post_login(&serde_json::json!({ "email": email, "password": "incorrect" }))
         .await;
     assert_eq!(response.status().as_u16(), 401);
 }
 
-// todo
-// 206 login requires 2FA
-// 500 unexpected error
+#[tokio::test]
+async fn should_return_401_if_email_is_unknown() {
+    let app = TestApp::new().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": get_random_email(),
+            "password": "password123",
+        }))
+        .await;
+    // the same as for a wrong password, so as not to give away who is registered
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -40,7 +40,10 @@ async fn should_return_200_and_ban_token_if_valid_jwt_cookie() {
     // ensure that token is banned
     let store = app.banned_token_store.read().await;
     eprintln!("Store: {:?}", store);
-    assert!(store.is_banned(&token).await);
+    assert!(store
+        .is_banned(&token)
+        .await
+        .expect("Failed to check banned token store"));
 }
 
 // should return 400 if logout called twice in a row
@@ -1,7 +1,21 @@
+mod change_password_test;
+mod debug_test;
+mod delete_account_test;
+mod introspect_test;
+mod jwks_test;
+mod lockout_test;
 mod login_test;
 mod logout_test;
+mod metrics_test;
+mod password_reset_test;
+mod rate_limit_test;
+mod recovery_codes_test;
+mod refresh_test;
+mod request_id_test;
 mod root_test;
 mod signup_test;
 mod test_helpers;
+mod totp_test;
 mod verify_2fa_test;
+mod verify_email_test;
 mod verify_token_test;
@@ -0,0 +1,84 @@
+use crate::test_helpers::{get_random_email, TestApp, ADMIN_TOKEN, METRICS_TOKEN};
+use serde_json::json;
+use std::time::Duration;
+
+async fn get_metrics_body(app: &TestApp) -> String {
+    let response = app.get_metrics(Some(METRICS_TOKEN)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    response.text().await.expect("Failed to read metrics")
+}
+
+#[tokio::test]
+async fn should_count_requests_and_errors_by_route() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let response = app
+        .post_signup(&json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let response = app
+        .post_login(&json!({
+            "email": email,
+            "password": "wrong-password",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.get_metrics(Some(METRICS_TOKEN)).await;
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "application/openmetrics-text; version=1.0.0; charset=utf-8"
+    );
+    let body = response.text().await.expect("Failed to read metrics");
+    assert!(body.contains(r#"http_requests_total{method="POST",route="/signup",status="201"} 1"#));
+    assert!(body.contains(r#"http_requests_total{method="POST",route="/login",status="401"} 1"#));
+    assert!(body.contains(r#"http_request_duration_seconds_count{method="POST",route="/login"} 1"#));
+    assert!(body.contains(r#"auth_api_errors_total{error="IncorrectCredentials"} 1"#));
+}
+
+#[tokio::test]
+async fn should_report_store_sizes() {
+    let app = TestApp::with_args(&["--store-metrics-interval-seconds", "1"]).await;
+    let body = get_metrics_body(&app).await;
+    assert!(body.contains("banned_tokens 0"));
+    assert!(body.contains("two_fa_codes 0"));
+
+    let email = get_random_email();
+    app.post_signup(&json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true,
+    }))
+    .await;
+    let response = app
+        .post_login(&json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    app.create_user_and_log_in().await;
+    app.post_logout().await;
+
+    // the stores are counted on a timer, not when scraped
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let body = get_metrics_body(&app).await;
+    assert!(body.contains("banned_tokens 1"));
+    assert!(body.contains("two_fa_codes 1"));
+}
+
+#[tokio::test]
+async fn should_require_metrics_token() {
+    let app = TestApp::new().await;
+    let response = app.get_metrics(None).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app.get_metrics(Some("wrong-token")).await;
+    assert_eq!(response.status().as_u16(), 401);
+    // the admin token is for the admin routes only
+    let response = app.get_metrics(Some(ADMIN_TOKEN)).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
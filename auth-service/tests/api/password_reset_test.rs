@@ -0,0 +1,200 @@
+use crate::test_helpers::{get_random_email, TestApp};
+use auth_service::domain::AccountEvent;
+use serde_json::json;
+use std::time::Duration;
+
+async fn sign_up(app: &TestApp) -> String {
+    let email = get_random_email();
+    let response = app
+        .post_signup(&json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    email
+}
+
+async fn log_in(app: &TestApp, email: &str, password: &str) -> u16 {
+    app.post_login(&json!({
+        "email": email,
+        "password": password,
+    }))
+    .await
+    .status()
+    .as_u16()
+}
+
+async fn request_reset_token(app: &TestApp, email: &str) -> String {
+    let count = app.email_client.sent_emails().await.len() + 1;
+    let response = app
+        .post_password_reset_request(&json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let content = app.wait_for_emails(count).await.pop().unwrap();
+    content
+        .rsplit(' ')
+        .next()
+        .expect("No token in email")
+        .to_owned()
+}
+
+#[tokio::test]
+async fn should_reset_password_with_emailed_token() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    let token = request_reset_token(&app, &email).await;
+
+    let response = app
+        .post_password_reset_confirm(&json!({
+            "token": token,
+            "password": "new_password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(log_in(&app, &email, "password123").await, 401);
+    assert_eq!(log_in(&app, &email, "new_password123").await, 200);
+    assert_eq!(
+        app.account_event_hook.events().await,
+        vec![AccountEvent::PasswordReset {
+            user_id: app.get_user_id(&email.parse().unwrap()).await,
+        }]
+    );
+}
+
+#[tokio::test]
+async fn should_not_reveal_whether_email_is_registered() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    let count = app.email_client.sent_emails().await.len();
+
+    let registered = app
+        .post_password_reset_request(&json!({ "email": email }))
+        .await;
+    let unknown = app
+        .post_password_reset_request(&json!({ "email": get_random_email() }))
+        .await;
+    assert_eq!(registered.status().as_u16(), 202);
+    assert_eq!(unknown.status().as_u16(), 202);
+    assert_eq!(
+        registered.text().await.unwrap(),
+        unknown.text().await.unwrap()
+    );
+
+    // only the registered address gets an email
+    app.wait_for_emails(count + 1).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let sent = app.email_client.sent_emails().await;
+    assert_eq!(sent.len(), count + 1);
+    assert_eq!(sent[count].recipient.as_ref(), email);
+}
+
+#[tokio::test]
+async fn should_invalidate_access_tokens_issued_before() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    let user_id = app.get_user_id(&email.parse().unwrap()).await;
+    let token = request_reset_token(&app, &email).await;
+    // issued within the same second as the reset, most likely
+    let access_token = app.generate_auth_token(&user_id);
+
+    let response = app
+        .post_password_reset_confirm(&json!({
+            "token": token,
+            "password": "new_password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app
+        .post_verify_token(&json!({ "token": access_token.to_string() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_401_if_token_is_reused() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    let token = request_reset_token(&app, &email).await;
+
+    let body = json!({
+        "token": token,
+        "password": "new_password123",
+    });
+    let response = app.post_password_reset_confirm(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app.post_password_reset_confirm(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_401_if_access_token_is_used() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    let user_id = app.get_user_id(&email.parse().unwrap()).await;
+    let token = app.generate_auth_token(&user_id);
+
+    let response = app
+        .post_password_reset_confirm(&json!({
+            "token": token,
+            "password": "new_password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(log_in(&app, &email, "password123").await, 200);
+}
+
+#[tokio::test]
+async fn should_keep_token_if_new_password_is_invalid() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    let token = request_reset_token(&app, &email).await;
+
+    let response = app
+        .post_password_reset_confirm(&json!({
+            "token": token,
+            "password": "short",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app
+        .post_password_reset_confirm(&json!({
+            "token": token,
+            "password": "new_password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_end_existing_sessions() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    assert_eq!(log_in(&app, &email, "password123").await, 200);
+    let token = request_reset_token(&app, &email).await;
+
+    let response = app
+        .post_password_reset_confirm(&json!({
+            "token": token,
+            "password": "new_password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    // the refresh token from the login no longer works
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+    let response = app
+        .post_password_reset_request(&json!({ "mail": "test@example.com" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+    let response = app
+        .post_password_reset_confirm(&json!({ "token": "token" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+}
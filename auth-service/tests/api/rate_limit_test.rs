@@ -0,0 +1,54 @@
+use crate::test_helpers::{get_random_email, TestApp};
+use serde_json::json;
+
+#[tokio::test]
+async fn should_return_429_once_an_email_is_over_its_limit() {
+    let app = TestApp::new().await;
+    let login_body = json!({
+        "email": get_random_email(),
+        "password": "password123",
+    });
+    let max_requests = app.state.settings.email_rate_limit.max_requests;
+    for _ in 0..max_requests {
+        let response = app.post_login(&login_body).await;
+        assert_ne!(response.status().as_u16(), 429);
+    }
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response
+        .headers()
+        .get("retry-after")
+        .expect("No Retry-After header")
+        .to_str()
+        .unwrap()
+        .parse()
+        .expect("Retry-After should be in seconds");
+    assert!(retry_after >= 1);
+}
+
+#[tokio::test]
+async fn should_return_429_once_an_ip_is_over_its_limit() {
+    let app = TestApp::new().await;
+    let max_requests = app.state.settings.ip_rate_limit.max_requests;
+    for _ in 0..max_requests {
+        let response = app
+            .post_login(&json!({
+                "email": get_random_email(),
+                "password": "password123",
+            }))
+            .await;
+        assert_ne!(response.status().as_u16(), 429);
+    }
+
+    // a different email doesn't help, nor does a different route
+    let response = app
+        .post_signup(&json!({
+            "email": get_random_email(),
+            "password": "password123",
+            "requires2FA": false,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().contains_key("retry-after"));
+}
@@ -0,0 +1,206 @@
+use crate::test_helpers::{get_random_email, TestApp};
+use auth_service::{
+    domain::{AccountEvent, Email, TwoFACodeStoreError},
+    routes::{RecoveryCodesResponse, TwoFactorAuthResponse},
+    utils::constants::RECOVERY_CODE_COUNT,
+};
+use serde_json::json;
+
+async fn sign_up(app: &TestApp, requires_2fa: bool) -> (String, Option<Vec<String>>) {
+    let email = get_random_email();
+    let response = app
+        .post_signup(&json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": requires_2fa,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let body: RecoveryCodesResponse = response.json().await.unwrap();
+    (email, body.recovery_codes)
+}
+
+// Log in as far as the second factor, returning the login attempt ID
+async fn start_login(app: &TestApp, email: &str) -> String {
+    let response = app
+        .post_login(&json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    let body: TwoFactorAuthResponse = response.json().await.unwrap();
+    body.login_attempt_id
+}
+
+async fn verify_2fa(app: &TestApp, email: &str, login_attempt_id: &str, code: &str) -> u16 {
+    app.post_verify_2fa(&json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code,
+    }))
+    .await
+    .status()
+    .as_u16()
+}
+
+#[tokio::test]
+async fn should_return_codes_at_signup_with_2fa() {
+    let app = TestApp::new().await;
+    let (_, codes) = sign_up(&app, true).await;
+    let codes = codes.expect("No recovery codes returned");
+    assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+
+    let (_, codes) = sign_up(&app, false).await;
+    assert!(codes.is_none());
+}
+
+#[tokio::test]
+async fn should_log_in_once_with_each_recovery_code() {
+    let app = TestApp::new().await;
+    let (email, codes) = sign_up(&app, true).await;
+    let code = &codes.unwrap()[0];
+
+    let login_attempt_id = start_login(&app, &email).await;
+    assert_eq!(verify_2fa(&app, &email, &login_attempt_id, code).await, 200);
+    assert_eq!(
+        app.account_event_hook.events().await,
+        vec![AccountEvent::RecoveryCodeUsed {
+            user_id: app.get_user_id(&email.parse().unwrap()).await,
+            remaining_codes: RECOVERY_CODE_COUNT - 1,
+        }]
+    );
+
+    let login_attempt_id = start_login(&app, &email).await;
+    assert_eq!(verify_2fa(&app, &email, &login_attempt_id, code).await, 401);
+}
+
+#[tokio::test]
+async fn should_accept_codes_without_dashes_or_case() {
+    let app = TestApp::new().await;
+    let (email, codes) = sign_up(&app, true).await;
+    let code = codes.unwrap()[0].replace('-', "").to_uppercase();
+
+    let login_attempt_id = start_login(&app, &email).await;
+    assert_eq!(
+        verify_2fa(&app, &email, &login_attempt_id, &code).await,
+        200
+    );
+}
+
+#[tokio::test]
+async fn should_not_use_up_code_on_wrong_login_attempt() {
+    let app = TestApp::new().await;
+    let (email, codes) = sign_up(&app, true).await;
+    let code = &codes.unwrap()[0];
+
+    let login_attempt_id = start_login(&app, &email).await;
+    let wrong_attempt_id = uuid::Uuid::new_v4().to_string();
+    assert_eq!(verify_2fa(&app, &email, &wrong_attempt_id, code).await, 401);
+    assert_eq!(verify_2fa(&app, &email, &login_attempt_id, code).await, 200);
+}
+
+#[tokio::test]
+async fn should_return_401_if_wrong_recovery_code() {
+    let app = TestApp::new().await;
+    let (email, _) = sign_up(&app, true).await;
+    let login_attempt_id = start_login(&app, &email).await;
+    assert_eq!(
+        verify_2fa(&app, &email, &login_attempt_id, "abcd-efgh-jkmn-pqrs").await,
+        401
+    );
+    assert!(app.account_event_hook.events().await.is_empty());
+    // it took the login attempt with it, so it's back to the password
+    let result = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get(&email.parse::<Email>().unwrap())
+        .await;
+    assert_eq!(result.err(), Some(TwoFACodeStoreError::EmailNotFound));
+}
+
+#[tokio::test]
+async fn should_replace_codes_when_regenerated() {
+    let app = TestApp::new().await;
+    let (email, old_codes) = sign_up(&app, true).await;
+    let login_attempt_id = start_login(&app, &email).await;
+    let code = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get(&email.parse::<Email>().unwrap())
+        .await
+        .unwrap()
+        .1;
+    assert_eq!(
+        verify_2fa(&app, &email, &login_attempt_id, code.as_ref()).await,
+        200
+    );
+
+    let response = app
+        .post_recovery_codes(&json!({ "currentPassword": "wrong_password" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app
+        .post_recovery_codes(&json!({ "currentPassword": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: RecoveryCodesResponse = response.json().await.unwrap();
+    let new_codes = body.recovery_codes.unwrap();
+    assert_eq!(new_codes.len(), RECOVERY_CODE_COUNT);
+    assert_eq!(
+        app.account_event_hook.events().await,
+        vec![AccountEvent::RecoveryCodesRegenerated {
+            user_id: app.get_user_id(&email.parse().unwrap()).await,
+        }]
+    );
+
+    let login_attempt_id = start_login(&app, &email).await;
+    let old_code = &old_codes.unwrap()[0];
+    assert_eq!(
+        verify_2fa(&app, &email, &login_attempt_id, old_code).await,
+        401
+    );
+    let login_attempt_id = start_login(&app, &email).await;
+    assert_eq!(
+        verify_2fa(&app, &email, &login_attempt_id, &new_codes[0]).await,
+        200
+    );
+}
+
+#[tokio::test]
+async fn should_return_codes_when_turning_2fa_on() {
+    let app = TestApp::new().await;
+    let (email, _) = sign_up(&app, false).await;
+    let response = app
+        .post_login(&json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_two_fa_method(&json!({ "2FAMethod": "email" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: RecoveryCodesResponse = response.json().await.unwrap();
+    assert_eq!(body.recovery_codes.unwrap().len(), RECOVERY_CODE_COUNT);
+
+    // 2FA is on already, so the codes stay as they are
+    let response = app
+        .post_two_fa_method(&json!({ "2FAMethod": "email" }))
+        .await;
+    let body: RecoveryCodesResponse = response.json().await.unwrap();
+    assert!(body.recovery_codes.is_none());
+}
+
+#[tokio::test]
+async fn should_return_400_if_not_logged_in() {
+    let app = TestApp::new().await;
+    let response = app
+        .post_recovery_codes(&json!({ "currentPassword": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,93 @@
+use crate::{refute, test_helpers::TestApp};
+use auth_service::utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME};
+use reqwest::Url;
+
+fn get_refresh_cookie(response: &reqwest::Response) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh token cookie found")
+        .value()
+        .to_owned()
+}
+
+// Put a previously issued refresh token back into the client's cookie jar
+fn set_refresh_cookie(app: &TestApp, token: &str) {
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Path=/",
+            REFRESH_COOKIE_NAME, token
+        ),
+        &Url::parse("http://127.0.0.1").unwrap(),
+    );
+}
+
+#[tokio::test]
+async fn should_return_200_and_rotate_refresh_token() {
+    let app = TestApp::new().await;
+    let response = app.create_user_and_log_in().await;
+    let first_token = get_refresh_cookie(&response);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    refute!(auth_cookie.value().is_empty(), "Expected non-empty cookie");
+    let second_token = get_refresh_cookie(&response);
+    assert_ne!(first_token, second_token);
+
+    // the rotated token can be used in turn
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_400_if_missing_refresh_token() {
+    let app = TestApp::new().await;
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_401_if_unknown_refresh_token() {
+    let app = TestApp::new().await;
+    set_refresh_cookie(&app, "invalid");
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_revoke_token_family_if_refresh_token_reused() {
+    let app = TestApp::new().await;
+    let response = app.create_user_and_log_in().await;
+    let first_token = get_refresh_cookie(&response);
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let second_token = get_refresh_cookie(&response);
+
+    // replaying the first token is taken as theft...
+    set_refresh_cookie(&app, &first_token);
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // ...so the token it was rotated into stops working too
+    set_refresh_cookie(&app, &second_token);
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_revoke_refresh_token_on_logout() {
+    let app = TestApp::new().await;
+    let response = app.create_user_and_log_in().await;
+    let token = get_refresh_cookie(&response);
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    set_refresh_cookie(&app, &token);
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,60 @@
+use crate::test_helpers::TestApp;
+use auth_service::utils::constants::REQUEST_ID_HEADER;
+
+// Read the request ID a response was sent with
+fn get_request_id(response: &reqwest::Response) -> String {
+    response
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .expect("No request ID header found")
+        .to_str()
+        .expect("Request ID should be ASCII")
+        .to_owned()
+}
+
+#[tokio::test]
+async fn should_return_generated_request_id() {
+    let app = TestApp::new().await;
+    let request_id = get_request_id(&app.get_root().await);
+    assert!(uuid::Uuid::parse_str(&request_id).is_ok());
+    assert_ne!(request_id, get_request_id(&app.get_root().await));
+}
+
+#[tokio::test]
+async fn should_keep_request_id_from_caller() {
+    let app = TestApp::new().await;
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let response = app
+        .http_client
+        .post(format!("{}/verify-token", &app.address))
+        .header(REQUEST_ID_HEADER, &request_id)
+        .json(&serde_json::json!({ "token": "nope" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(get_request_id(&response), request_id);
+}
+
+#[tokio::test]
+async fn should_replace_malformed_request_id() {
+    let app = TestApp::new().await;
+    let uuid = uuid::Uuid::new_v4();
+    for request_id in [
+        "login-1234".to_owned(),
+        format!("{uuid} forged log line"),
+        uuid.to_string().to_uppercase(),
+        format!("{{{uuid}}}"),
+    ] {
+        let response = app
+            .http_client
+            .get(&app.address)
+            .header(REQUEST_ID_HEADER, &request_id)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        let returned = get_request_id(&response);
+        assert_ne!(returned, request_id);
+        assert!(uuid::Uuid::parse_str(&returned).is_ok());
+    }
+}
@@ -1,11 +1,17 @@
 use auth_service::{
     app_state::{AppState, BannedTokenStoreType, TwoFACodeStoreType},
+    domain::{AuthMethod, Email, Token, UserId},
+    services::{MockAccountEventHook, MockEmailClient, SqlUserStore},
+    settings::Settings,
+    utils::{auth::generate_auth_token, jwt_key::JwtKeyring},
     Application,
 };
 use reqwest::cookie::Jar;
 use serde::Serialize;
 use serde_json::json;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
 
 // I don't like the look of `assert!(!...)`, hence:
 /// Refute a condition, asserting that it is false.
@@ -19,20 +25,72 @@ macro_rules! refute {
     };
 }
 
+pub const ADMIN_TOKEN: &str = "test-admin-token";
+pub const INTROSPECTION_TOKEN: &str = "test-introspection-token";
+pub const METRICS_TOKEN: &str = "test-metrics-token";
+
 pub struct TestApp {
     pub address: String,
     pub cookie_jar: Arc<Jar>,
     pub http_client: reqwest::Client,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub email_client: Arc<MockEmailClient>,
+    pub account_event_hook: Arc<MockAccountEventHook>,
+    pub state: AppState,
 }
 
 impl TestApp {
     pub async fn new() -> Self {
-        let state = AppState::default();
+        Self::with_email_client(MockEmailClient::default()).await
+    }
+
+    pub async fn with_email_client(email_client: MockEmailClient) -> Self {
+        Self::build(email_client, &[]).await
+    }
+
+    /// An app with extra command line flags, e.g. `--require-email-verification`.
+    pub async fn with_args(args: &[&str]) -> Self {
+        Self::build(MockEmailClient::default(), args).await
+    }
+
+    async fn build(email_client: MockEmailClient, args: &[&str]) -> Self {
+        let email_client = Arc::new(email_client);
+        // Keys come from the environment, as in production, so the tests
+        // can be run against each kind of key.
+        let settings = Settings::load_from(
+            [
+                "auth-service",
+                "--listen-address",
+                "127.0.0.1:0",
+                "--admin-token",
+                ADMIN_TOKEN,
+                "--introspection-token",
+                INTROSPECTION_TOKEN,
+                "--metrics-token",
+                METRICS_TOKEN,
+            ]
+            .iter()
+            .chain(args),
+        )
+        .expect("Invalid test settings");
+        let jwt_keyring =
+            JwtKeyring::from_source(&settings.jwt_key).expect("Failed to load JWT keys");
+        let mut state = AppState::new(settings, jwt_keyring);
+        state.email_client = email_client.clone();
+        let account_event_hook = Arc::new(MockAccountEventHook::default());
+        state.account_event_hook = account_event_hook.clone();
+        // Run against a SQL user store when TEST_DATABASE_URL is set
+        // (e.g. `sqlite::memory:`), rather than the in-memory default.
+        if let Ok(url) = std::env::var("TEST_DATABASE_URL") {
+            let user_store = SqlUserStore::connect(&url)
+                .await
+                .expect("Failed to connect to test database");
+            state.user_store = Arc::new(RwLock::new(user_store));
+        }
         let banned_token_store = state.banned_token_store.clone();
         let two_fa_code_store = state.two_fa_code_store.clone();
-        let app = Application::build(state, "127.0.0.1:0")
+        let app = Application::build(state.clone())
             .await
             .expect("Failed to build application");
 
@@ -52,7 +110,48 @@ impl TestApp {
             http_client,
             banned_token_store,
             two_fa_code_store,
+            email_client,
+            account_event_hook,
+            state,
+        }
+    }
+
+    /// Issue an access token the way a login would.
+    pub fn generate_auth_token(&self, user_id: &UserId) -> Token {
+        generate_auth_token(
+            user_id,
+            &[AuthMethod::Password],
+            &self.state.jwt_keyring.current(),
+            &self.state.settings.token_policy,
+            self.state.settings.token_ttl,
+        )
+        .expect("Failed to generate auth token")
+    }
+
+    /// The contents of the emails sent so far, once there are `count` of
+    /// them. Some emails are sent after the response, so this gives them a
+    /// moment to arrive.
+    pub async fn wait_for_emails(&self, count: usize) -> Vec<String> {
+        for _ in 0..50 {
+            let emails = self.email_client.sent_emails().await;
+            if emails.len() >= count {
+                return emails.into_iter().map(|email| email.content).collect();
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
         }
+        panic!("Expected {} emails to be sent", count);
+    }
+
+    /// The ID of the signed up user with `email`.
+    pub async fn get_user_id(&self, email: &Email) -> UserId {
+        self.state
+            .user_store
+            .read()
+            .await
+            .get_user(email)
+            .await
+            .expect("User should exist")
+            .id
     }
 
     pub async fn get_root(&self) -> reqwest::Response {
@@ -87,9 +186,13 @@ impl TestApp {
             .expect("Failed to execute request")
     }
 
-    pub async fn post_verify_2fa(&self) -> reqwest::Response {
+    pub async fn post_verify_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
         self.http_client
             .post(format!("{}/verify-2fa", self.address))
+            .json(body)
             .send()
             .await
             .expect("Failed to execute request")
@@ -115,6 +218,188 @@ impl TestApp {
             .expect("Failed to execute request")
     }
 
+    pub async fn post_refresh(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/refresh", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_password_reset_request<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/request", self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_password_reset_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/confirm", self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-password", self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn delete_account<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: Serialize,
+    {
+        self.http_client
+            .delete(format!("{}/account", self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_totp_enroll(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/2fa/totp/enroll", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_totp_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/totp/confirm", self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_two_fa_method<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/method", self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_recovery_codes<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/recovery-codes", self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_resend_verification_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-email/resend", self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_verify_email(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/verify-email?token={}", self.address, token))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/jwks.json", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_banned_token_stats(&self, admin_token: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .get(format!("{}/debug/banned-tokens", self.address));
+        if let Some(admin_token) = admin_token {
+            request = request.bearer_auth(admin_token);
+        }
+        request.send().await.expect("Failed to execute request")
+    }
+
+    pub async fn get_metrics(&self, metrics_token: Option<&str>) -> reqwest::Response {
+        let mut request = self.http_client.get(format!("{}/metrics", self.address));
+        if let Some(metrics_token) = metrics_token {
+            request = request.bearer_auth(metrics_token);
+        }
+        request.send().await.expect("Failed to execute request")
+    }
+
+    pub async fn post_unlock_account<Body>(
+        &self,
+        admin_token: Option<&str>,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/admin/unlock-account", self.address))
+            .json(body);
+        if let Some(admin_token) = admin_token {
+            request = request.bearer_auth(admin_token);
+        }
+        request.send().await.expect("Failed to execute request")
+    }
+
+    pub async fn post_introspect<Body>(
+        &self,
+        introspection_token: Option<&str>,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/introspect", self.address))
+            .json(body);
+        if let Some(introspection_token) = introspection_token {
+            request = request.bearer_auth(introspection_token);
+        }
+        request.send().await.expect("Failed to execute request")
+    }
+
     pub async fn create_user_and_log_in(&self) -> reqwest::Response {
         let email = get_random_email();
         let signup_body = json!({
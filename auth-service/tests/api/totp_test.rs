@@ -0,0 +1,296 @@
+use crate::test_helpers::{get_random_email, TestApp};
+use auth_service::{
+    domain::TwoFAMethod,
+    routes::{EnrollTotpResponse, TwoFactorAuthResponse},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::json;
+use std::time::{SystemTime, UNIX_EPOCH};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+async fn sign_up_and_log_in(app: &TestApp) -> String {
+    let email = get_random_email();
+    let response = app
+        .post_signup(&json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let response = log_in(app, &email).await;
+    assert_eq!(response.status().as_u16(), 200);
+    email
+}
+
+async fn log_in(app: &TestApp, email: &str) -> reqwest::Response {
+    app.post_login(&json!({
+        "email": email,
+        "password": "password123",
+    }))
+    .await
+}
+
+async fn enroll(app: &TestApp) -> EnrollTotpResponse {
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.expect("Invalid enrollment response")
+}
+
+fn code_at(secret: &str, offset_steps: i64) -> String {
+    let secret = Secret::Encoded(secret.to_owned()).to_bytes().unwrap();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    TOTP::new_unchecked(Algorithm::SHA1, 6, 1, 30, secret, None, String::new())
+        .generate((now + offset_steps * 30) as u64)
+}
+
+fn current_code(secret: &str) -> String {
+    code_at(secret, 0)
+}
+
+// The code confirming enrollment uses up the current step, so logging in
+// straight after takes the next one, which is allowed for clock skew
+fn next_code(secret: &str) -> String {
+    code_at(secret, 1)
+}
+
+fn wrong_code(code: &str) -> &'static str {
+    if code == "000000" {
+        "111111"
+    } else {
+        "000000"
+    }
+}
+
+// The password is only needed once 2FA is on, and ignored before
+async fn enroll_and_confirm(app: &TestApp) -> String {
+    let enrollment = enroll(app).await;
+    let response = app
+        .post_totp_confirm(&json!({
+            "2FACode": current_code(&enrollment.secret),
+            "currentPassword": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    enrollment.secret
+}
+
+#[tokio::test]
+async fn should_return_secret_uri_and_qr_code() {
+    let app = TestApp::new().await;
+    let email = sign_up_and_log_in(&app).await;
+
+    let enrollment = enroll(&app).await;
+    assert!(enrollment.otpauth_uri.starts_with("otpauth://totp/"));
+    assert!(enrollment
+        .otpauth_uri
+        .contains(&format!("secret={}", enrollment.secret)));
+    assert!(enrollment.otpauth_uri.contains(&email.replace('@', "%40")));
+    let png = STANDARD.decode(&enrollment.qr_code_png).unwrap();
+    assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+}
+
+#[tokio::test]
+async fn should_ask_for_totp_code_at_login_once_confirmed() {
+    let app = TestApp::new().await;
+    let email = sign_up_and_log_in(&app).await;
+    let secret = enroll_and_confirm(&app).await;
+    let emails_before = app.email_client.sent_emails().await.len();
+
+    let response = log_in(&app, &email).await;
+    assert_eq!(response.status().as_u16(), 206);
+    let body: TwoFactorAuthResponse = response.json().await.unwrap();
+    assert_eq!(body.two_fa_method, TwoFAMethod::Totp);
+    // nothing is emailed for authenticator app users
+    assert_eq!(app.email_client.sent_emails().await.len(), emails_before);
+
+    let code = next_code(&secret);
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": wrong_code(&code),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_reject_a_used_totp_code() {
+    let app = TestApp::new().await;
+    let email = sign_up_and_log_in(&app).await;
+    let secret = enroll_and_confirm(&app).await;
+    let code = next_code(&secret);
+
+    let body: TwoFactorAuthResponse = log_in(&app, &email).await.json().await.unwrap();
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body: TwoFactorAuthResponse = log_in(&app, &email).await.json().await.unwrap();
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    // as is the code that confirmed enrollment, or any other before it
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": current_code(&secret),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_require_password_to_replace_enrolled_app() {
+    let app = TestApp::new().await;
+    let email = sign_up_and_log_in(&app).await;
+    let old_secret = enroll_and_confirm(&app).await;
+    let enrollment = enroll(&app).await;
+    let code = current_code(&enrollment.secret);
+
+    let response = app.post_totp_confirm(&json!({ "2FACode": code })).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app
+        .post_totp_confirm(&json!({
+            "2FACode": code,
+            "currentPassword": "wrong_password",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app
+        .post_totp_confirm(&json!({
+            "2FACode": code,
+            "currentPassword": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body: TwoFactorAuthResponse = log_in(&app, &email).await.json().await.unwrap();
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": next_code(&old_secret),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": next_code(&enrollment.secret),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_require_password_to_replace_emailed_codes() {
+    let app = TestApp::new().await;
+    sign_up_and_log_in(&app).await;
+    let response = app
+        .post_two_fa_method(&json!({ "2FAMethod": "email" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let enrollment = enroll(&app).await;
+
+    let code = current_code(&enrollment.secret);
+    let response = app.post_totp_confirm(&json!({ "2FACode": code })).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_not_change_2fa_until_confirmed() {
+    let app = TestApp::new().await;
+    let email = sign_up_and_log_in(&app).await;
+    let enrollment = enroll(&app).await;
+
+    let code = current_code(&enrollment.secret);
+    let response = app
+        .post_totp_confirm(&json!({ "2FACode": wrong_code(&code) }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(log_in(&app, &email).await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_400_if_confirming_without_enrolling() {
+    let app = TestApp::new().await;
+    sign_up_and_log_in(&app).await;
+    let response = app.post_totp_confirm(&json!({ "2FACode": "123456" })).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_switch_between_methods() {
+    let app = TestApp::new().await;
+    let email = sign_up_and_log_in(&app).await;
+    let response = app
+        .post_two_fa_method(&json!({ "2FAMethod": "totp" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_two_fa_method(&json!({ "2FAMethod": "email" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: TwoFactorAuthResponse = log_in(&app, &email).await.json().await.unwrap();
+    assert_eq!(body.two_fa_method, TwoFAMethod::Email);
+
+    enroll_and_confirm(&app).await;
+    let body: TwoFactorAuthResponse = log_in(&app, &email).await.json().await.unwrap();
+    assert_eq!(body.two_fa_method, TwoFAMethod::Totp);
+
+    let response = app
+        .post_two_fa_method(&json!({ "2FAMethod": "email" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: TwoFactorAuthResponse = log_in(&app, &email).await.json().await.unwrap();
+    assert_eq!(body.two_fa_method, TwoFAMethod::Email);
+}
+
+#[tokio::test]
+async fn should_return_400_if_not_logged_in() {
+    let app = TestApp::new().await;
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app.post_totp_confirm(&json!({ "2FACode": "123456" })).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app
+        .post_two_fa_method(&json!({ "2FAMethod": "email" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+    sign_up_and_log_in(&app).await;
+    let response = app.post_totp_confirm(&json!({ "code": "123456" })).await;
+    assert_eq!(response.status().as_u16(), 422);
+    let response = app.post_two_fa_method(&json!({ "2FAMethod": "sms" })).await;
+    assert_eq!(response.status().as_u16(), 422);
+}
@@ -1,8 +1,213 @@
-use crate::test_helpers::TestApp;
+use crate::{
+    refute,
+    test_helpers::{get_random_email, TestApp},
+};
+use auth_service::{
+    domain::Email,
+    utils::constants::{JWT_COOKIE_NAME, MAX_TWO_FA_ATTEMPTS, REFRESH_COOKIE_NAME},
+};
+use serde_json::json;
+
+// Sign up a 2FA user and log in, returning the email along with the
+// login attempt ID and code that were stored for the attempt.
+async fn sign_up_and_start_2fa(app: &TestApp) -> (String, String, String) {
+    let email = get_random_email();
+    let response = app
+        .post_signup(&json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let (login_attempt_id, code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get(&email.parse::<Email>().expect("Could not parse email"))
+        .await
+        .expect("No 2FA code stored for login attempt");
+    (
+        email,
+        login_attempt_id.as_ref().to_owned(),
+        code.as_ref().to_owned(),
+    )
+}
 
 #[tokio::test]
-async fn verify_2fa_returns_200() {
+async fn should_return_200_if_correct_code() {
     let app = TestApp::new().await;
-    let response = app.post_verify_2fa().await;
+    let (email, login_attempt_id, code) = sign_up_and_start_2fa(&app).await;
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
     assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    refute!(auth_cookie.value().is_empty(), "Expected non-empty cookie");
+    assert!(response
+        .cookies()
+        .any(|cookie| cookie.name() == REFRESH_COOKIE_NAME));
+}
+
+#[tokio::test]
+async fn should_return_401_if_same_code_twice() {
+    let app = TestApp::new().await;
+    let (email, login_attempt_id, code) = sign_up_and_start_2fa(&app).await;
+    let body = json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code,
+    });
+
+    let response = app.post_verify_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app.post_verify_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_credentials() {
+    let app = TestApp::new().await;
+    let (email, login_attempt_id, code) = sign_up_and_start_2fa(&app).await;
+    let wrong_code = if code == "000000" { "111111" } else { "000000" };
+
+    let test_cases = [
+        json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": wrong_code,
+        }),
+        json!({
+            "email": email,
+            "loginAttemptId": uuid::Uuid::new_v4().to_string(),
+            "2FACode": code,
+        }),
+        json!({
+            "email": get_random_email(),
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code,
+        }),
+    ];
+    for body in test_cases.iter() {
+        let response = app.post_verify_2fa(body).await;
+        assert_eq!(response.status().as_u16(), 401, "Failed for input: {body}");
+    }
+}
+
+#[tokio::test]
+async fn should_return_401_after_too_many_wrong_codes() {
+    let app = TestApp::new().await;
+    let (email, login_attempt_id, code) = sign_up_and_start_2fa(&app).await;
+    let wrong_code = if code == "000000" { "111111" } else { "000000" };
+
+    for _ in 0..MAX_TWO_FA_ATTEMPTS {
+        let response = app
+            .post_verify_2fa(&json!({
+                "email": email,
+                "loginAttemptId": login_attempt_id,
+                "2FACode": wrong_code,
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // the right code no longer works once the code has been invalidated
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_401_if_old_code() {
+    let app = TestApp::new().await;
+    let (email, login_attempt_id, code) = sign_up_and_start_2fa(&app).await;
+
+    // logging in again replaces the pending code
+    let response = app
+        .post_login(&json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let response = app
+        .post_verify_2fa(&json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let login_attempt_id = uuid::Uuid::new_v4().to_string();
+    let test_cases = [
+        json!({
+            "email": "invalid_email",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": "123456",
+        }),
+        json!({
+            "email": email,
+            "loginAttemptId": "not-a-uuid",
+            "2FACode": "123456",
+        }),
+        json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": "12345",
+        }),
+    ];
+    for body in test_cases.iter() {
+        let response = app.post_verify_2fa(body).await;
+        assert_eq!(response.status().as_u16(), 400, "Failed for input: {body}");
+    }
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+    let test_cases = [
+        json!({}),
+        json!({
+            "email": get_random_email(),
+            "loginAttemptId": uuid::Uuid::new_v4().to_string(),
+        }),
+        json!({
+            "email": get_random_email(),
+            "2FACode": "123456",
+        }),
+    ];
+    for body in test_cases.iter() {
+        let response = app.post_verify_2fa(body).await;
+        assert_eq!(response.status().as_u16(), 422, "Failed for input: {body}");
+    }
 }
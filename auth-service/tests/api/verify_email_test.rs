@@ -0,0 +1,181 @@
+use crate::test_helpers::{get_random_email, TestApp};
+use auth_service::services::MockEmailClient;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+async fn sign_up(app: &TestApp) -> String {
+    let email = get_random_email();
+    let response = app
+        .post_signup(&json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    email
+}
+
+async fn log_in(app: &TestApp, email: &str) -> reqwest::Response {
+    app.post_login(&json!({
+        "email": email,
+        "password": "password123",
+    }))
+    .await
+}
+
+// The token from the link in the verification email sent at signup
+async fn get_verification_token(app: &TestApp, email: &str) -> String {
+    let sent = app.email_client.sent_emails().await;
+    let content = &sent
+        .iter()
+        .find(|sent| sent.recipient.as_ref() == email)
+        .expect("No verification email sent")
+        .content;
+    let link = content.rsplit(' ').next().expect("No link in email");
+    assert!(link.starts_with(&format!(
+        "{}/verify-email?token=",
+        app.state.settings.public_url
+    )));
+    link.rsplit('=').next().unwrap().to_owned()
+}
+
+#[tokio::test]
+async fn should_start_unverified_and_verify_with_emailed_link() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    let user_email = email.parse().unwrap();
+    let user_store = app.state.user_store.clone();
+    assert!(
+        !user_store
+            .read()
+            .await
+            .get_user(&user_email)
+            .await
+            .unwrap()
+            .verified
+    );
+
+    let token = get_verification_token(&app, &email).await;
+    let response = app.get_verify_email(&token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        user_store
+            .read()
+            .await
+            .get_user(&user_email)
+            .await
+            .unwrap()
+            .verified
+    );
+}
+
+#[tokio::test]
+async fn should_allow_unverified_logins_by_default() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+    assert_eq!(log_in(&app, &email).await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_403_for_unverified_login_when_required() {
+    let app = TestApp::with_args(&["--require-email-verification"]).await;
+    let email = sign_up(&app).await;
+
+    let response = log_in(&app, &email).await;
+    assert_eq!(response.status().as_u16(), 403);
+    let body: Value = response.json().await.expect("Failed to parse error");
+    assert_eq!(body["error"], "Email not verified");
+
+    let token = get_verification_token(&app, &email).await;
+    assert_eq!(app.get_verify_email(&token).await.status().as_u16(), 200);
+    assert_eq!(log_in(&app, &email).await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_401_if_wrong_password_when_unverified() {
+    let app = TestApp::with_args(&["--require-email-verification"]).await;
+    let email = sign_up(&app).await;
+    let response = app
+        .post_login(&json!({
+            "email": email,
+            "password": "wrong-password",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_token() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+
+    let response = app.get_verify_email("invalid").await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // an access token is no good either
+    let user_id = app.get_user_id(&email.parse().unwrap()).await;
+    let token = app.generate_auth_token(&user_id);
+    let response = app.get_verify_email(&token.to_string()).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_400_if_missing_token() {
+    let app = TestApp::new().await;
+    let response = app
+        .http_client
+        .get(format!("{}/verify-email", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_resend_link_that_failed_to_send_at_signup() {
+    let app = TestApp::with_email_client(MockEmailClient::failing()).await;
+    // the account is made even though the link can't be sent
+    let email = sign_up(&app).await;
+    assert!(app.email_client.sent_emails().await.is_empty());
+
+    app.email_client.set_failing(false);
+    let response = app
+        .post_resend_verification_email(&json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.wait_for_emails(1).await;
+    let token = get_verification_token(&app, &email).await;
+    assert_eq!(app.get_verify_email(&token).await.status().as_u16(), 200);
+
+    // verified addresses aren't sent another
+    let response = app
+        .post_resend_verification_email(&json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(app.email_client.sent_emails().await.len(), 1);
+}
+
+#[tokio::test]
+async fn should_not_reveal_whether_email_is_registered_when_resending() {
+    let app = TestApp::new().await;
+    let email = sign_up(&app).await;
+
+    let response = app
+        .post_resend_verification_email(&json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let registered: Value = response.json().await.unwrap();
+    let response = app
+        .post_resend_verification_email(&json!({ "email": get_random_email() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let unknown: Value = response.json().await.unwrap();
+    assert_eq!(registered, unknown);
+
+    // one from signup and one resent, but none to the unknown address
+    app.wait_for_emails(2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(app.email_client.sent_emails().await.len(), 2);
+}
@@ -1,13 +1,15 @@
-use crate::test_helpers::{get_random_email, TestApp};
-use auth_service::utils::auth::generate_auth_token;
+use crate::test_helpers::TestApp;
+use auth_service::{
+    domain::{AuthMethod, UserId},
+    utils::auth::{generate_auth_token, TokenPolicy},
+};
 use serde_json::json;
 
 #[tokio::test]
 async fn should_return_200_if_valid_token() {
     let app = TestApp::new().await;
-    let email = get_random_email().parse().expect("Failed to parse email");
     let body = json!({
-        "token": generate_auth_token(&email).expect("Failed to generate auth token")
+        "token": app.generate_auth_token(&UserId::default())
     });
     let response = app.post_verify_token(&body).await;
     assert_eq!(response.status().as_u16(), 200);
@@ -23,16 +25,35 @@ async fn should_return_401_if_invalid_token() {
     assert_eq!(response.status().as_u16(), 401);
 }
 
+#[tokio::test]
+async fn should_return_401_if_token_is_for_another_audience() {
+    let app = TestApp::new().await;
+    let policy = TokenPolicy {
+        audience: "some-other-service".to_owned(),
+        ..app.state.settings.token_policy.clone()
+    };
+    let token = generate_auth_token(
+        &UserId::default(),
+        &[AuthMethod::Password],
+        &app.state.jwt_keyring.current(),
+        &policy,
+        app.state.settings.token_ttl,
+    )
+    .expect("Failed to generate auth token");
+    let response = app.post_verify_token(&json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
 #[tokio::test]
 async fn should_return_401_if_token_is_banned() {
     let app = TestApp::new().await;
-    let email = get_random_email().parse().expect("Failed to parse email");
-    let token = generate_auth_token(&email).expect("Failed to generate auth token");
+    let token = app.generate_auth_token(&UserId::default());
     app.banned_token_store
         .write()
         .await
         .ban(token.clone())
-        .await;
+        .await
+        .expect("Failed to ban token");
     let body = json!({
         "token": token
     });